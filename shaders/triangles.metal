@@ -1,13 +1,89 @@
 
-// Edge function (signed area * 2)
-float edge(const float2 v0, const float2 v1, const float2 p, const float dx, const float dy) {
-    return (p.x - v0.x) * (dy) - (p.y - v0.y) * (dx);
+// rasterization is done in fixed point so an edge shared by two triangles gives exactly the same result for both
+// (the cpu mirror of all this is in raster.rs, which has to be kept in sync)
+constant int SUBPIXEL_BITS = 8;
+constant int SUBPIXEL_SCALE = 1 << SUBPIXEL_BITS;
+constant int SUBPIXEL_HALF = SUBPIXEL_SCALE / 2;
+constant float GUARD_BAND = 4194304.0;  // 2^22 pixels, keeps the fixed point coordinates inside an int
+
+// snapping a screen space position onto the sub-pixel grid
+int2 to_fixed(const float2 p) {
+    return int2(metal::round(metal::clamp(p, -GUARD_BAND, GUARD_BAND) * float(SUBPIXEL_SCALE)));
+}
+
+// Edge function (signed area * 2); positive on the inner side of a -> b once the triangle's winding has been made consistent
+long edge(const int2 a, const int2 b, const long2 p) {
+    return long(b.x - a.x) * (p.y - long(a.y)) - long(b.y - a.y) * (p.x - long(a.x));
+}
+
+// top-left fill rule: a pixel center exactly on an edge only belongs to the triangle if it's a top or left edge, so shared edges get drawn once
+bool is_top_left(const int2 a, const int2 b) {
+    const int2 d = b - a;
+    return (d.y == 0 && d.x > 0) || d.y < 0;
+}
+
+// decoding the attribute word of a triangle (the w of its uint4); the layout is set by `Triangle::encode` in triangle.rs
+constant uint TRIANGLE_FLAG_TRANSLUCENT = 1u << 0;
+constant uint TRIANGLE_FLAG_EMISSIVE = 1u << 1;
+
+uint triangle_normal(const uint4 triangle) {
+    return triangle.w & 0xFF;
+}
+
+uint triangle_flags(const uint4 triangle) {
+    return (triangle.w >> 8) & 0xFF;
+}
+
+uint triangle_material(const uint4 triangle) {
+    return (triangle.w >> 16) & 0xF;
+}
+
+uint triangle_texture(const uint4 triangle) {
+    return (triangle.w >> 20) & 0xFFF;
+}
+
+// turning screen space barycentrics into ones that interpolate linearly across the triangle in view space
+// (attributes are linear in view space, but 1/w is what's linear on screen, so each weight gets scaled by its vertex's 1/w)
+float3 perspective_weights(const float3 screen_weights, const float3 inverse_w) {
+    const float3 weighted = screen_weights * inverse_w;
+    return weighted / (weighted.x + weighted.y + weighted.z);
+}
+
+// every texture is stored as its whole mip chain (16x16 down to 1x1), laid out by `TextureAtlas` in texture_atlas.rs
+constant uint TEXTURE_SIZE = 16;
+constant uint MIP_LEVELS = 5;
+constant uint MIP_OFFSETS[MIP_LEVELS] = {0, 256, 320, 336, 340};
+constant uint TEXELS_PER_TEXTURE = 341;
+constant uint MAX_TEXTURES = 1024;  // has to match MAX_TEXTURES in main.rs, which sizes the texture buffer
+// a texel's alpha says how it's drawn: below TINTED_ALPHA as is, from it up to CUTOUT_ALPHA multiplied by the vertex tint, and from CUTOUT_ALPHA up not at all
+constant uchar TINTED_ALPHA = 64;
+constant uchar CUTOUT_ALPHA = 128;
+
+// picking the mip whose texels are about a pixel across, from how far the uvs move between neighbouring pixels
+// (rounded down, so it stays on the sharper side)
+uint select_mip(const float2 uv_dx, const float2 uv_dy) {
+    const float texels_per_pixel = metal::max(metal::length(uv_dx), metal::length(uv_dy)) * float(TEXTURE_SIZE);
+    return metal::min(uint(metal::log2(metal::max(texels_per_pixel, 1.0))), MIP_LEVELS - 1);
+}
+
+// the nearest texel of a mip, with the uvs wrapped so merged faces repeat the texture instead of reading into the next one
+uchar4 sample_texture(device const uchar4* const texture_buffer, const uint texture_index, const float2 uv, const uint level) {
+    const uint size = TEXTURE_SIZE >> level;
+    const float2 wrapped = uv - metal::floor(uv);
+    const uint2 texel = metal::min(uint2(wrapped * float(size)), uint2(size - 1));  // fract can still round up to 1.0
+    const uint texture = metal::min(texture_index, MAX_TEXTURES - 1);
+    return texture_buffer[texture * TEXELS_PER_TEXTURE + MIP_OFFSETS[level] + texel.y * size + texel.x];
+}
+
+float2 interpolate_uv(const float3 weights, const float2 uv_1, const float2 uv_2, const float2 uv_3) {
+    return weights.x * uv_1 + weights.y * uv_2 + weights.z * uv_3;
 }
 
 struct Vertex {
     const float4 position;
-    const float4 uv;
-    const float4 light;
+    const float2 uv;
+    const uchar4 light;
+    const uchar4 tint;
 };
 
 kernel void ComputeShader (
@@ -52,54 +128,89 @@ kernel void ComputeShader (
 
         const uint tri_id = num_triangles[base_index + bin_id + 1];
         const uint4 triangle = triangles_buffer[tri_id];
-        const float3 triangle_normal = normals[triangle.w & 0xFFFF].xyz;
-
+        const float3 triangle_normal = normals[triangle_normal(triangle)].xyz;
+        const uint flags = triangle_flags(triangle);
+        const bool translucent = (flags & TRIANGLE_FLAG_TRANSLUCENT) != 0;
+        const bool emissive = (flags & TRIANGLE_FLAG_EMISSIVE) != 0;
+
+        // snapping to the sub-pixel grid and swapping two of the vertices if needed, so every triangle is wound the same way
+        const int2 fixed_1 = to_fixed(vertex_buffer[triangle.x].position.xy);
+        const int2 fixed_2 = to_fixed(vertex_buffer[triangle.y].position.xy);
+        const int2 fixed_3 = to_fixed(vertex_buffer[triangle.z].position.xy);
+        const long signed_area = edge(fixed_1, fixed_2, long2(fixed_3));
+        if (signed_area == 0) continue;  // degenerate, covers nothing
+        const bool flipped = signed_area < 0;
+        const int2 p1 = fixed_1;
+        const int2 p2 = flipped ? fixed_3 : fixed_2;
+        const int2 p3 = flipped ? fixed_2 : fixed_3;
+        
         device const Vertex* const tri_1 = &vertex_buffer[triangle.x];
-        device const Vertex* const tri_2 = &vertex_buffer[triangle.y];
-        device const Vertex* const tri_3 = &vertex_buffer[triangle.z];
+        device const Vertex* const tri_2 = &vertex_buffer[flipped ? triangle.z : triangle.y];
+        device const Vertex* const tri_3 = &vertex_buffer[flipped ? triangle.y : triangle.z];
 
         const float3 v1 = tri_1->position.xyz;
         const float3 v2 = tri_2->position.xyz;
         const float3 v3 = tri_3->position.xyz;
-
-        // transforming the vertexes based on rotation and position
-        const uint maxX = uint(metal::ceil(metal::max(v1.x, metal::max(v2.x, v3.x))));
-        const uint minX = uint(metal::floor(metal::min(v1.x, metal::min(v2.x, v3.x))));
-        const uint maxY = uint(metal::ceil(metal::max(v1.y, metal::max(v2.y, v3.y))));
-        const uint minY = uint(metal::floor(metal::min(v1.y, metal::min(v2.y, v3.y))));
-        if (maxY < gid_base.y || minY > gid_max.y || maxX < gid_base.x || minX > gid_max.x) continue;
-
-        const float light_intensity = metal::dot(triangle_normal, sun_direction) * 0.5 + 0.5;
-        uchar texture_index = uchar(triangle.w >> 16);
-
-        const float dx_32 = v3.x - v2.x;
-        const float dy_32 = v3.y - v2.y;
-        const float dx_13 = v1.x - v3.x;
-        const float dy_13 = v1.y - v3.y;
-
-        const float area = 1.0 / edge(v1.xy, v2.xy, v3.xy, (v2.x - v1.x), (v2.y - v1.y));
-        for (uint x = gid_base.x; x < gid_max.x; x++) {
-            for (uint y = gid_base.y; y < gid_max.y; y++) {
-                const float2 float_coord = float2(float(x), float(y));
-
-                const float w0 = edge(v2.xy, v3.xy, float_coord, dx_32, dy_32) * area;
-                const float w1 = edge(v3.xy, v1.xy, float_coord, dx_13, dy_13) * area;
-                const float w2 = 1.0 - w0 - w1;
-
+        const float3 inverse_w = float3(tri_1->position.w, tri_2->position.w, tri_3->position.w);
+
+        // the pixels whose centers could be inside, clamped to this cell and the screen (signed, so vertices off the left or top don't wrap around)
+        const int min_x = metal::max(int(gid_base.x), (metal::min(p1.x, metal::min(p2.x, p3.x)) - SUBPIXEL_HALF + SUBPIXEL_SCALE - 1) >> SUBPIXEL_BITS);
+        const int max_x = metal::min(int(metal::min(gid_max.x, width)) - 1, (metal::max(p1.x, metal::max(p2.x, p3.x)) - SUBPIXEL_HALF) >> SUBPIXEL_BITS);
+        const int min_y = metal::max(int(gid_base.y), (metal::min(p1.y, metal::min(p2.y, p3.y)) - SUBPIXEL_HALF + SUBPIXEL_SCALE - 1) >> SUBPIXEL_BITS);
+        const int max_y = metal::min(int(metal::min(gid_max.y, height)) - 1, (metal::max(p1.y, metal::max(p2.y, p3.y)) - SUBPIXEL_HALF) >> SUBPIXEL_BITS);
+        if (min_x > max_x || min_y > max_y) continue;
+
+        const float light_intensity = emissive ? 1.0 : metal::dot(triangle_normal, sun_direction) * 0.5 + 0.5;
+        const uint texture_index = triangle_texture(triangle);
+
+        const float inverse_area = 1.0 / float(metal::abs(signed_area));
+        const long bias_1 = is_top_left(p2, p3) ? 0 : -1;  // for the edge opposite each vertex
+        const long bias_2 = is_top_left(p3, p1) ? 0 : -1;
+        const long bias_3 = is_top_left(p1, p2) ? 0 : -1;
+        // how the screen space weights change from one pixel to the next, for the uv derivatives that pick the mip
+        const float3 weights_dx = float3(p2.y - p3.y, p3.y - p1.y, p1.y - p2.y) * (float(SUBPIXEL_SCALE) * inverse_area);
+        const float3 weights_dy = float3(p3.x - p2.x, p1.x - p3.x, p2.x - p1.x) * (float(SUBPIXEL_SCALE) * inverse_area);
+        for (int x = min_x; x <= max_x; x++) {
+            for (int y = min_y; y <= max_y; y++) {
+                // sampling at the pixel's center rather than its corner
+                const long2 sample = long2(long(x) * SUBPIXEL_SCALE + SUBPIXEL_HALF, long(y) * SUBPIXEL_SCALE + SUBPIXEL_HALF);
+                const long edge_1 = edge(p2, p3, sample);
+                const long edge_2 = edge(p3, p1, sample);
+                const long edge_3 = edge(p1, p2, sample);
+                if (edge_1 + bias_1 < 0 || edge_2 + bias_2 < 0 || edge_3 + bias_3 < 0) continue;
+
+                const float w0 = float(edge_1) * inverse_area;
+                const float w1 = float(edge_2) * inverse_area;
+                const float w2 = float(edge_3) * inverse_area;
+
+                // the depth is already divided by w so it's interpolated as is, everything else gets corrected for perspective
                 const float depth = v1.z * w0 + v2.z * w1 + v3.z * w2;
-                const float uv_u = 16.0 * (w0 * tri_1->uv.x + w1 * tri_2->uv.x + w2 * tri_3->uv.x);
-                const float uv_v = 16.0 * (w0 * tri_1->uv.y + w1 * tri_2->uv.y + w2 * tri_3->uv.y);
-                const float3 light = w0 * tri_1->light.xyz + w1 * tri_2->light.xyz + w2 * tri_3->light.xyz;
-
-                const uint uv_x = uint(uv_u);
-                const uint uv_y = uint(uv_v);
-                uchar4 texture_col = texture_buffer[texture_index * 256 + uv_y * 16 + uv_x];
-
-                const uint depth_index = x + y * width;
-                if (texture_col.w == 255 || x < minX || x >= width || y < minY || y >= height || w0 < 0.0 || w1 < 0.0 || w2 < 0.0 || depth >= depth_buffer[depth_index]) continue;
+                const float3 screen_weights = float3(w0, w1, w2);
+                const float3 weights = perspective_weights(screen_weights, inverse_w);
+                const float2 uv = interpolate_uv(weights, tri_1->uv, tri_2->uv, tri_3->uv);
+                const float3 light = emissive ? float3(1.0) : (weights.x * float3(tri_1->light.xyz) + weights.y * float3(tri_2->light.xyz) + weights.z * float3(tri_3->light.xyz)) * (1.0 / 255.0);
+
+                // the uvs one pixel over in each direction; perspective makes them change across the triangle, so they're worked out per pixel
+                const float2 uv_dx = interpolate_uv(perspective_weights(screen_weights + weights_dx, inverse_w), tri_1->uv, tri_2->uv, tri_3->uv) - uv;
+                const float2 uv_dy = interpolate_uv(perspective_weights(screen_weights + weights_dy, inverse_w), tri_1->uv, tri_2->uv, tri_3->uv) - uv;
+                const uchar4 texel = sample_texture(texture_buffer, texture_index, uv, select_mip(uv_dx, uv_dy));
+                // grayscale grass and foliage get their color from the biome the vertices are in
+                const float3 tint = (weights.x * float3(tri_1->tint.xyz) + weights.y * float3(tri_2->tint.xyz) + weights.z * float3(tri_3->tint.xyz)) * (1.0 / 255.0);
+                const float3 texture_col = texel.w >= TINTED_ALPHA ? float3(texel.xyz) * tint : float3(texel.xyz);
+
+                const uint depth_index = uint(x) + uint(y) * width;
+                // cut out once mostly transparent (the mips average the alpha, so a threshold rather than just 255)
+                if (texel.w >= CUTOUT_ALPHA || depth >= depth_buffer[depth_index]) continue;
+                const uint pixel_index = uint(x) * 3 + (height - 1 - uint(y)) * pitch;  // rows are flipped; y = 0 is the last row
+                if (translucent) {
+                    // blended over whatever was drawn before it without writing depth (so it's only right if it's drawn last, see the sorting todo)
+                    pixels[pixel_index + 0] = uchar((float(pixels[pixel_index + 0]) + texture_col.x * light_intensity * light.x) * 0.5);
+                    pixels[pixel_index + 1] = uchar((float(pixels[pixel_index + 1]) + texture_col.y * light_intensity * light.y) * 0.5);
+                    pixels[pixel_index + 2] = uchar((float(pixels[pixel_index + 2]) + texture_col.z * light_intensity * light.z) * 0.5);
+                    continue;
+                }
                 depth_buffer[depth_index] = depth;
 
-                const uint pixel_index = x * 3 + (height - y) * pitch;
                 pixels[pixel_index + 0] = uchar(texture_col.x * light_intensity * light.x);
                 pixels[pixel_index + 1] = uchar(texture_col.y * light_intensity * light.y);
                 pixels[pixel_index + 2] = uchar(texture_col.z * light_intensity * light.z);
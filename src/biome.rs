@@ -0,0 +1,149 @@
+use fastnoise_lite::{FastNoiseLite, FractalType, NoiseType};
+
+// biomes come from two smooth noise fields over the world's columns, temperature and humidity (both 0 to 1)
+// grass and foliage textures are grayscale where they're tinted (see `texture_atlas::TINTED_ALPHA`) and get multiplied by a color picked from the climate,
+// which the chunk mesher writes into each vertex, averaged over the columns around it so the colors blend across biome borders
+
+/// How far (in columns) the climate around a vertex is averaged over
+static BLEND_RADIUS: i32 = 3;
+/// How quickly the climate changes across the world; roughly one biome every few hundred blocks
+static CLIMATE_FREQUENCY: f32 = 0.004;
+/// Levels of temperature and humidity packed into a vertex (4 bits each)
+const CLIMATE_LEVELS: u8 = 16;
+
+/// The grass color at the four extremes of the climate, mixed between for everything in the middle
+static COLD_DRY_TINT: [f32; 3] = [128.0, 170.0, 140.0];
+static COLD_WET_TINT: [f32; 3] = [90.0, 150.0, 110.0];
+static HOT_DRY_TINT: [f32; 3] = [190.0, 180.0, 85.0];
+static HOT_WET_TINT: [f32; 3] = [70.0, 185.0, 50.0];
+
+/// The temperature and humidity of every column in a world, from its seed
+pub struct Climate {
+    temperature: FastNoiseLite,
+    humidity: FastNoiseLite,
+}
+
+impl Climate {
+    pub fn new(seed: u64) -> Self {
+        let noise = |seed: i32| {
+            let mut noise = FastNoiseLite::with_seed(seed);
+            noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+            noise.set_fractal_type(Some(FractalType::FBm));
+            noise.set_fractal_octaves(Some(3));
+            noise.set_frequency(Some(CLIMATE_FREQUENCY));
+            noise
+        };
+        // the noise only takes 32 bit seeds, so the two halves get folded together
+        let seed = (seed ^ (seed >> 32)) as i32;
+        Climate { temperature: noise(seed), humidity: noise(seed.wrapping_add(1)) }
+    }
+    
+    /// The temperature and humidity of one column
+    pub fn at(&self, x: i32, z: i32) -> (f32, f32) {
+        let sample = |noise: &FastNoiseLite| (noise.get_noise_2d(x as f32, z as f32) * 0.5 + 0.5).clamp(0.0, 1.0);
+        (sample(&self.temperature), sample(&self.humidity))
+    }
+    
+    /// The packed climate (see `pack`) at each corner between the columns of the chunk starting at `(origin_x, origin_z)`,
+    /// indexed [x][z] with corner 0 being the -x/-z corner of the first column; each one is averaged over the columns around it
+    pub fn chunk_corners(&self, origin_x: i32, origin_z: i32) -> [[u8; 17]; 17] {
+        // every column the corners average over, sampled once
+        let width = (16 + BLEND_RADIUS * 2) as usize;
+        let mut columns = vec![(0.0, 0.0); width * width];
+        for x in 0..width {
+            for z in 0..width {
+                columns[x * width + z] = self.at(origin_x - BLEND_RADIUS + x as i32, origin_z - BLEND_RADIUS + z as i32);
+            }
+        }
+        let mut corners = [[0; 17]; 17];
+        let count = (BLEND_RADIUS * 2 * BLEND_RADIUS * 2) as f32;
+        for (corner_x, row) in corners.iter_mut().enumerate() {
+            for (corner_z, corner) in row.iter_mut().enumerate() {
+                let (mut temperature, mut humidity) = (0.0, 0.0);
+                for x in corner_x..corner_x + BLEND_RADIUS as usize * 2 {
+                    for z in corner_z..corner_z + BLEND_RADIUS as usize * 2 {
+                        temperature += columns[x * width + z].0;
+                        humidity += columns[x * width + z].1;
+                    }
+                }
+                *corner = pack(temperature / count, humidity / count);
+            }
+        }
+        corners
+    }
+}
+
+/// Squeezes a climate into a byte for the chunk vertices: temperature in the high 4 bits and humidity in the low 4
+pub fn pack(temperature: f32, humidity: f32) -> u8 {
+    let level = |value: f32| (value.clamp(0.0, 1.0) * (CLIMATE_LEVELS - 1) as f32).round() as u8;
+    level(temperature) << 4 | level(humidity)
+}
+
+pub fn unpack(packed: u8) -> (f32, f32) {
+    ((packed >> 4) as f32 / (CLIMATE_LEVELS - 1) as f32, (packed & 0xF) as f32 / (CLIMATE_LEVELS - 1) as f32)
+}
+
+/// What tinted texels get multiplied by in a climate
+pub fn tint(temperature: f32, humidity: f32) -> [u8; 3] {
+    let mix = |a: [f32; 3], b: [f32; 3], t: f32| [0, 1, 2].map(|channel| a[channel] + (b[channel] - a[channel]) * t);
+    let cold = mix(COLD_DRY_TINT, COLD_WET_TINT, humidity);
+    let hot = mix(HOT_DRY_TINT, HOT_WET_TINT, humidity);
+    mix(cold, hot, temperature).map(|channel| channel.round().clamp(0.0, 255.0) as u8)
+}
+
+/// The name of the biome a climate falls in (just for showing, the tint doesn't care about the borders)
+pub fn name(temperature: f32, humidity: f32) -> &'static str {
+    match (temperature, humidity) {
+        (t, h) if t < 0.3 => if h < 0.5 { "tundra" } else { "taiga" },
+        (t, h) if t < 0.7 => if h < 0.3 { "plains" } else if h < 0.7 { "forest" } else { "swamp" },
+        (_, h) => if h < 0.4 { "savanna" } else { "jungle" },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn the_climate_only_depends_on_the_seed() {
+        let samples = |climate: &Climate| (0..64).map(|i| climate.at(i * 37, i * -53)).collect::<Vec<_>>();
+        let climate = Climate::new(1234);
+        assert!(samples(&climate) == samples(&Climate::new(1234)), "the same seed gave a different climate");
+        assert!(samples(&climate) != samples(&Climate::new(4321)), "a different seed gave the same climate");
+    }
+    
+    #[test]
+    fn packing_keeps_values_within_half_a_level() {
+        for (temperature, humidity) in [(0.0, 1.0), (0.33, 0.71), (1.0, 0.0), (0.4, 0.25)] {
+            let (unpacked_temperature, unpacked_humidity) = unpack(pack(temperature, humidity));
+            assert!(
+                (unpacked_temperature - temperature).abs() <= 0.5 / 15.0 && (unpacked_humidity - humidity).abs() <= 0.5 / 15.0,
+                "packing ({}, {}) came back as ({}, {})", temperature, humidity, unpacked_temperature, unpacked_humidity,
+            );
+        }
+    }
+    
+    #[test]
+    fn chunks_blend_across_their_edges() {
+        let climate = Climate::new(1234);
+        let (first, second) = (climate.chunk_corners(0, 0), climate.chunk_corners(16, 0));
+        assert_eq!(first[16], second[0], "chunks next to each other should agree along the edge between them");
+        // corners next to each other change by at most a level
+        for (column, next) in first.iter().zip(&first[1..]) {
+            for (a, b) in column.iter().zip(next).map(|(a, b)| (unpack(*a), unpack(*b))) {
+                let step = ((a.0 - b.0).abs() * 15.0).round().max(((a.1 - b.1).abs() * 15.0).round());
+                assert!(step <= 1.0, "the climate jumped {} levels between {:?} and {:?}", step, a, b);
+            }
+        }
+    }
+    
+    #[test]
+    fn wide_areas_get_a_range_of_tints() {
+        let climate = Climate::new(1234);
+        let tints: std::collections::HashSet<[u8; 3]> = (0..32).flat_map(|x| (0..32).map(move |z| (x, z)))
+            .map(|(x, z)| climate.at(x * 64, z * 64))
+            .map(|(temperature, humidity)| tint(temperature, humidity))
+            .collect();
+        assert!(tints.len() >= 20, "only {} grass tints across a 2048 block wide area", tints.len());
+    }
+}
@@ -0,0 +1,135 @@
+use crate::block_shape::BlockShape;
+use crate::block_state::{StateProperty, AGE, AXIS, FACING, FALLING, HALF, LEVEL};
+use crate::fluid::Fluid;
+use crate::triangle::TriangleFlags;
+
+// what each cell of `Chunk::tile_data` means; 0 is always air
+// a cell is the block id in the low 16 bits and that block's state in the high 16 (named properties like which way it faces, see `block_state`)
+
+pub type BlockId = u32;
+
+pub const AIR: BlockId = 0;
+pub const GRASS: BlockId = 1;
+pub const DIRT: BlockId = 2;
+pub const STONE: BlockId = 3;
+pub const COBBLESTONE: BlockId = 4;
+pub const OBSIDIAN: BlockId = 5;
+pub const WATER: BlockId = 6;
+pub const LAVA: BlockId = 7;
+pub const SAND: BlockId = 8;
+pub const GRAVEL: BlockId = 9;
+pub const LOG: BlockId = 10;
+pub const LEAVES: BlockId = 11;
+pub const WHEAT: BlockId = 12;
+pub const STONE_SLAB: BlockId = 13;
+pub const COBBLESTONE_STAIRS: BlockId = 14;
+pub const FENCE: BlockId = 15;
+pub const TALL_GRASS: BlockId = 16;
+pub const TABLE: BlockId = 17;
+pub const FIRE: BlockId = 18;
+pub const COAL_ORE: BlockId = 19;
+pub const IRON_ORE: BlockId = 20;
+pub const GOLD_ORE: BlockId = 21;
+
+const ID_MASK: u32 = 0xFFFF;
+const STATE_SHIFT: u32 = 16;
+
+/// The fixed properties of a kind of block
+#[derive(Clone, Copy, Debug)]
+pub struct BlockProperties {
+    pub name: &'static str,
+    pub solid: bool,  // blocks movement
+    pub opaque: bool,  // can't be seen through, so it hides whatever faces its shape covers
+    pub textures: [u16; 3],  // top, bottom and sides (for blocks with an `AXIS`, the two ends and the sides)
+    pub face_flags: TriangleFlags,
+    pub fluid: Option<Fluid>,
+    pub falls: bool,  // drops down whenever there's nothing solid under it (see `block_updates`)
+    pub shape: BlockShape,
+    pub states: &'static [&'static StateProperty],  // what its state holds, packed in this order (see `block_state`)
+}
+
+/// Indexed by block id
+static BLOCKS: [BlockProperties; 22] = [
+    BlockProperties { name: "air", solid: false, opaque: false, textures: [0, 0, 0], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Cube, states: &[] },
+    BlockProperties { name: "grass", solid: true, opaque: true, textures: [1, 2, 0], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Cube, states: &[] },
+    BlockProperties { name: "dirt", solid: true, opaque: true, textures: [2, 2, 2], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Cube, states: &[] },
+    BlockProperties { name: "stone", solid: true, opaque: true, textures: [3, 3, 3], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Cube, states: &[] },
+    BlockProperties { name: "cobblestone", solid: true, opaque: true, textures: [4, 4, 4], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Cube, states: &[] },
+    BlockProperties { name: "obsidian", solid: true, opaque: true, textures: [5, 5, 5], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Cube, states: &[] },
+    BlockProperties { name: "water", solid: false, opaque: false, textures: [6, 6, 6], face_flags: TriangleFlags::TRANSLUCENT, fluid: Some(Fluid::Water), falls: false, shape: BlockShape::Cube, states: &[&LEVEL, &FALLING] },
+    BlockProperties { name: "lava", solid: false, opaque: true, textures: [7, 7, 7], face_flags: TriangleFlags::EMISSIVE, fluid: Some(Fluid::Lava), falls: false, shape: BlockShape::Cube, states: &[&LEVEL, &FALLING] },
+    BlockProperties { name: "sand", solid: true, opaque: true, textures: [8, 8, 8], face_flags: TriangleFlags::NONE, fluid: None, falls: true, shape: BlockShape::Cube, states: &[] },
+    BlockProperties { name: "gravel", solid: true, opaque: true, textures: [9, 9, 9], face_flags: TriangleFlags::NONE, fluid: None, falls: true, shape: BlockShape::Cube, states: &[] },
+    BlockProperties { name: "log", solid: true, opaque: true, textures: [14, 14, 10], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Cube, states: &[&AXIS] },
+    BlockProperties { name: "leaves", solid: true, opaque: false, textures: [11, 11, 11], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Cube, states: &[] },
+    BlockProperties { name: "wheat", solid: false, opaque: false, textures: [12, 12, 12], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Cross, states: &[&AGE] },
+    BlockProperties { name: "stone slab", solid: true, opaque: true, textures: [3, 3, 3], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Slab, states: &[&HALF] },
+    BlockProperties { name: "cobblestone stairs", solid: true, opaque: true, textures: [4, 4, 4], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Stairs, states: &[&FACING, &HALF] },
+    BlockProperties { name: "fence", solid: true, opaque: true, textures: [10, 10, 10], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Fence, states: &[] },
+    BlockProperties { name: "tall grass", solid: false, opaque: false, textures: [13, 13, 13], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Cross, states: &[] },
+    BlockProperties { name: "table", solid: true, opaque: true, textures: [10, 10, 10], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Model("table"), states: &[] },
+    BlockProperties { name: "fire", solid: false, opaque: false, textures: [15, 15, 15], face_flags: TriangleFlags::EMISSIVE, fluid: None, falls: false, shape: BlockShape::Cross, states: &[] },
+    BlockProperties { name: "coal ore", solid: true, opaque: true, textures: [16, 16, 16], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Cube, states: &[] },
+    BlockProperties { name: "iron ore", solid: true, opaque: true, textures: [17, 17, 17], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Cube, states: &[] },
+    BlockProperties { name: "gold ore", solid: true, opaque: true, textures: [18, 18, 18], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Cube, states: &[] },
+];
+
+/// Stands in for ids that aren't registered (from newer worlds or corrupted data); solid so nothing falls through them
+static UNKNOWN_BLOCK: BlockProperties = BlockProperties { name: "unknown", solid: true, opaque: true, textures: [2, 2, 2], face_flags: TriangleFlags::NONE, fluid: None, falls: false, shape: BlockShape::Cube, states: &[] };
+
+/// The block id of a cell, without its state
+pub fn id(cell: u32) -> BlockId {
+    cell & ID_MASK
+}
+
+pub fn state(cell: u32) -> u16 {
+    (cell >> STATE_SHIFT) as u16
+}
+
+pub fn with_state(id: BlockId, state: u16) -> u32 {
+    (id & ID_MASK) | (state as u32) << STATE_SHIFT
+}
+
+/// The block id with that name, like `cobblestone stairs`
+pub fn by_name(name: &str) -> Option<BlockId> {
+    BLOCKS.iter().position(|properties| properties.name == name).map(|id| id as BlockId)
+}
+
+pub fn properties(cell: u32) -> &'static BlockProperties {
+    BLOCKS.get(id(cell) as usize).unwrap_or(&UNKNOWN_BLOCK)
+}
+
+pub fn is_solid(cell: u32) -> bool {
+    properties(cell).solid
+}
+
+/// Whether it's a full cube that can't be seen through (so it hides everything next to it, and blocks the occlusion culling)
+pub fn is_opaque(cell: u32) -> bool {
+    let properties = properties(cell);
+    properties.opaque && properties.shape == BlockShape::Cube
+}
+
+pub fn fluid(cell: u32) -> Option<Fluid> {
+    properties(cell).fluid
+}
+
+/// Whether the whole `face` side of `cell`, touching `neighbour`, is hidden by it
+/// (anything opaque, or more of the same block, that fills the side touching it; see `block_shape::face_hidden`)
+pub fn hides_face(neighbour: u32, cell: u32, face: usize) -> bool {
+    crate::block_shape::full_face_hidden(cell, face, neighbour)
+}
+
+/// Which texture goes on `face` of `cell`, and whether it's turned a quarter so its grain runs along the block's `AXIS`
+pub fn face_texture(cell: u32, face: usize) -> (u16, bool) {
+    let [top, bottom, sides] = properties(cell).textures;
+    let Some(axis) = crate::block_state::get(cell, &AXIS) else {
+        return (match face { 0 => top, 1 => bottom, _ => sides }, false);
+    };
+    if face / 2 == axis as usize {
+        (if face.is_multiple_of(2) { top } else { bottom }, false)
+    } else {
+        // side textures run up v, so turn them whenever the block runs along the face's u instead
+        let (_, u_axis, _) = crate::block_shape::face_axes(face);
+        (sides, u_axis == [1, 0, 2][axis as usize])
+    }
+}
@@ -0,0 +1,291 @@
+use crate::block;
+use crate::block_state::{self, FACING, HALF};
+
+// the geometry of blocks that aren't full cubes, as boxes measured in sixteenths of a block from its lowest corner
+// the chunk mesher turns the boxes into faces, and works out which faces hide each other from the parts of them that touch
+// faces go by their normal index: +y, -y, +x, -x, +z, -z (so a face's opposite is `face ^ 1`)
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockShape {
+    Cube,
+    /// half a block, in whichever `HALF` its state says
+    Slab,
+    /// a slab (in its `HALF`) with a quarter block on the other half along the side it's `FACING`
+    Stairs,
+    /// a post with rails to any fences or full blocks next to it
+    Fence,
+    /// two sprites crossed diagonally through the block, for plants
+    Cross,
+    /// boxes loaded from `<name>.json` by `load_models` (drawn as a cube if it didn't load)
+    Model(&'static str),
+}
+
+/// An axis aligned box inside a block, in sixteenths (0 to 16 on each axis)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+pub struct ShapeBox {
+    pub from: [u8; 3],
+    pub to: [u8; 3],
+}
+
+impl ShapeBox {
+    pub const fn new(from: [u8; 3], to: [u8; 3]) -> Self {
+        ShapeBox { from, to }
+    }
+}
+
+static FULL_BLOCK: ShapeBox = ShapeBox::new([0, 0, 0], [16, 16, 16]);
+
+/// A model file is just a list of boxes, like `{ "boxes": [{ "from": [0, 0, 0], "to": [16, 2, 16] }] }`
+#[derive(serde::Deserialize)]
+struct ModelFile {
+    boxes: Vec<ShapeBox>,
+}
+
+static MODELS: std::sync::OnceLock<std::collections::HashMap<String, Vec<ShapeBox>>> = std::sync::OnceLock::new();
+
+/// Loads every `.json` model in `directory` (only once, before anything gets meshed); returns how many loaded
+/// models that are missing or fail to load get logged and drawn as full cubes instead
+pub fn load_models(directory: &str) -> Result<usize, String> {
+    let entries = std::fs::read_dir(directory).map_err(|e| format!("Failed to read the model directory '{}': {}", directory, e))?;
+    let mut models = std::collections::HashMap::new();
+    for entry in entries {
+        let path = entry.map_err(|e| format!("Failed to read the model directory '{}': {}", directory, e))?.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        match load_model(&path) {
+            Ok(boxes) => {
+                models.insert(name, boxes);
+            },
+            Err(error) => log_warn!("Skipping the block model '{}': {}", path.display(), error),
+        }
+    }
+    let count = models.len();
+    MODELS.set(models).map_err(|_| "Block models were already loaded".to_string())?;
+    Ok(count)
+}
+
+fn load_model(path: &std::path::Path) -> Result<Vec<ShapeBox>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let model: ModelFile = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    for shape_box in &model.boxes {
+        if (0..3).any(|axis| shape_box.from[axis] >= shape_box.to[axis] || shape_box.to[axis] > 16) {
+            return Err(format!("The box {:?} to {:?} is empty or goes outside of its block", shape_box.from, shape_box.to));
+        }
+    }
+    Ok(model.boxes)
+}
+
+pub fn shape(cell: u32) -> BlockShape {
+    block::properties(cell).shape
+}
+
+/// Whether a fence next to it would connect to it
+fn fence_connects(neighbour: u32) -> bool {
+    shape(neighbour) == BlockShape::Fence || block::is_opaque(neighbour)
+}
+
+/// The boxes making up a block, given its horizontal neighbours (+x, -x, +z, -z) for the shapes that connect to them
+/// crosses have none, they're meshed separately as sprites
+pub fn boxes(cell: u32, neighbours: [u32; 4]) -> Vec<ShapeBox> {
+    let top = block_state::get(cell, &HALF) == Some(1);
+    match shape(cell) {
+        BlockShape::Cube => vec![FULL_BLOCK],
+        BlockShape::Slab if top => vec![ShapeBox::new([0, 8, 0], [16, 16, 16])],
+        BlockShape::Slab => vec![ShapeBox::new([0, 0, 0], [16, 8, 16])],
+        BlockShape::Stairs => {
+            let (base, step) = if top { ((8, 16), (0, 8)) } else { ((0, 8), (8, 16)) };
+            let step_box = match block_state::get(cell, &FACING).unwrap_or(0) {
+                0 => ShapeBox::new([8, step.0, 0], [16, step.1, 16]),
+                1 => ShapeBox::new([0, step.0, 0], [8, step.1, 16]),
+                2 => ShapeBox::new([0, step.0, 8], [16, step.1, 16]),
+                _ => ShapeBox::new([0, step.0, 0], [16, step.1, 8]),
+            };
+            vec![ShapeBox::new([0, base.0, 0], [16, base.1, 16]), step_box]
+        },
+        BlockShape::Fence => {
+            let mut boxes = vec![ShapeBox::new([6, 0, 6], [10, 16, 10])];
+            // two rails to each side it connects to
+            for (side, neighbour) in neighbours.into_iter().enumerate() {
+                if !fence_connects(neighbour) {
+                    continue;
+                }
+                for (bottom, top) in [(6, 9), (12, 15)] {
+                    boxes.push(match side {
+                        0 => ShapeBox::new([10, bottom, 7], [16, top, 9]),
+                        1 => ShapeBox::new([0, bottom, 7], [6, top, 9]),
+                        2 => ShapeBox::new([7, bottom, 10], [9, top, 16]),
+                        _ => ShapeBox::new([7, bottom, 0], [9, top, 6]),
+                    });
+                }
+            }
+            boxes
+        },
+        BlockShape::Cross => vec![],
+        BlockShape::Model(name) => MODELS.get().and_then(|models| models.get(name)).cloned().unwrap_or_else(|| vec![FULL_BLOCK]),
+    }
+}
+
+/// Fences stop things a block and a half up, so they can't be stepped or jumped over (in sixteenths)
+static FENCE_COLLISION_HEIGHT: u8 = 24;
+
+/// The boxes a block stops movement with, which is what gets drawn except for fences:
+/// their post and a full height arm to each side they connect to, `FENCE_COLLISION_HEIGHT` tall (so reaching into the block above)
+pub fn collision_boxes(cell: u32, neighbours: [u32; 4]) -> Vec<ShapeBox> {
+    if shape(cell) != BlockShape::Fence {
+        return boxes(cell, neighbours);
+    }
+    let height = FENCE_COLLISION_HEIGHT;
+    let mut boxes = vec![ShapeBox::new([6, 0, 6], [10, height, 10])];
+    for (side, neighbour) in neighbours.into_iter().enumerate() {
+        if fence_connects(neighbour) {
+            boxes.push(match side {
+                0 => ShapeBox::new([10, 0, 6], [16, height, 10]),
+                1 => ShapeBox::new([0, 0, 6], [6, height, 10]),
+                2 => ShapeBox::new([6, 0, 10], [10, height, 16]),
+                _ => ShapeBox::new([6, 0, 0], [10, height, 6]),
+            });
+        }
+    }
+    boxes
+}
+
+/// The axis a face points along, and the other two (which its rects are measured along)
+pub fn face_axes(face: usize) -> (usize, usize, usize) {
+    match face {
+        0 | 1 => (1, 0, 2),
+        2 | 3 => (0, 2, 1),
+        _ => (2, 0, 1),
+    }
+}
+
+/// The part of `face`'s side of the block a box covers as (u from, u to, v from, v to), if it reaches that side
+pub fn box_face_rect(shape_box: &ShapeBox, face: usize) -> Option<[u8; 4]> {
+    let (axis, u, v) = face_axes(face);
+    let on_side = if face.is_multiple_of(2) { shape_box.to[axis] == 16 } else { shape_box.from[axis] == 0 };
+    on_side.then_some([shape_box.from[u], shape_box.to[u], shape_box.from[v], shape_box.to[v]])
+}
+
+/// Whether the rects together cover every sixteenth of `rect`
+pub fn rects_cover(rects: impl Iterator<Item = [u8; 4]>, rect: [u8; 4]) -> bool {
+    // a row of bits per sixteenth along v
+    let mut covered = [0u16; 16];
+    for [u_from, u_to, v_from, v_to] in rects {
+        let bits = ((1u32 << u_to) - (1u32 << u_from)) as u16;
+        for row in &mut covered[v_from as usize..v_to as usize] {
+            *row |= bits;
+        }
+    }
+    let needed = ((1u32 << rect[1]) - (1u32 << rect[0])) as u16;
+    covered[rect[2] as usize..rect[3] as usize].iter().all(|row| row & needed == needed)
+}
+
+/// Whether the part `rect` of `cell`'s `face` side is hidden by the block next to it on that side:
+/// it has to be opaque (or more of the same block) and fill all of that part of the side touching it
+pub fn face_hidden(cell: u32, face: usize, rect: [u8; 4], neighbour: u32) -> bool {
+    let properties = block::properties(neighbour);
+    if !properties.opaque && block::id(neighbour) != block::id(cell) {
+        return false;
+    }
+    match properties.shape {
+        BlockShape::Cube => true,
+        BlockShape::Cross => false,
+        // without its neighbours a fence is just its post, so rails touching it from the side always get drawn
+        _ => rects_cover(boxes(neighbour, [block::AIR; 4]).iter().filter_map(|shape_box| box_face_rect(shape_box, face ^ 1)), rect),
+    }
+}
+
+/// Whether a whole side of `cell` is hidden by `neighbour`
+pub fn full_face_hidden(cell: u32, face: usize, neighbour: u32) -> bool {
+    face_hidden(cell, face, [0, 16, 0, 16], neighbour)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::shader_handling::Float4;
+    
+    /// The faces (by normal) a tile gets meshed with, in a chunk with a stone floor at y = 0 and the given blocks at y = 1
+    fn mesh_faces(blocks: &[(usize, usize, u32)], tile: (usize, usize)) -> [usize; 6] {
+        let mut chunk = Chunk::new(Float4::new(0.0, 0.0, 0.0, 0.0), 0);
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.tile_data[x][0][z] = block::STONE;
+            }
+        }
+        for (x, z, cell) in blocks {
+            chunk.tile_data[*x][1][*z] = *cell;
+        }
+        chunk.remesh_tile(tile.0, 1, tile.1, 16, 0);
+        let mut faces = [0; 6];
+        for triangle in &chunk.mesh_tris[0] {
+            faces[triangle.normal as usize] += 1;
+        }
+        faces.map(|triangles| triangles / 2)
+    }
+
+    #[test]
+    fn slabs_hide_nothing_but_get_hidden_by_blocks() {
+        let blocks = [(5, 5, block::STONE), (6, 5, block::STONE_SLAB)];
+        assert_eq!(mesh_faces(&blocks, (5, 5)), [1, 0, 1, 1, 1, 1], "a slab shouldn't hide the side of the stone next to it");
+        assert_eq!(mesh_faces(&blocks, (6, 5)), [1, 0, 1, 0, 1, 1], "the stone should hide the slab's side");
+    }
+    
+    #[test]
+    fn slabs_hide_each_other_only_at_the_same_height() {
+        let top_slab = block_state::with(block::STONE_SLAB, &HALF, 1);
+        assert_eq!(mesh_faces(&[(5, 5, block::STONE_SLAB), (6, 5, block::STONE_SLAB)], (5, 5)), [1, 0, 0, 1, 1, 1]);
+        assert_eq!(mesh_faces(&[(5, 5, block::STONE_SLAB), (6, 5, top_slab)], (5, 5)), [1, 0, 1, 1, 1, 1]);
+    }
+    
+    #[test]
+    fn stairs_hide_what_their_full_side_touches() {
+        // facing +x, so the +x side is full and the -x side only half
+        let stairs = block::COBBLESTONE_STAIRS;
+        assert_eq!(mesh_faces(&[(5, 5, block::STONE), (4, 5, stairs)], (5, 5))[3], 0, "the stone behind the stairs should be hidden");
+        assert_eq!(mesh_faces(&[(5, 5, block::STONE), (6, 5, stairs)], (5, 5))[2], 1, "the stone in front of the stairs should be drawn");
+        // the covered part of the base's top and the step's inside faces aren't drawn
+        assert_eq!(mesh_faces(&[(5, 5, stairs)], (5, 5)), [2, 0, 2, 2, 2, 2]);
+    }
+    
+    #[test]
+    fn fences_grow_rails_to_other_fences() {
+        assert_eq!(mesh_faces(&[(5, 5, block::FENCE)], (5, 5)), [1, 0, 1, 1, 1, 1], "a fence on its own should just be its post");
+        let connected = mesh_faces(&[(5, 5, block::FENCE), (6, 5, block::FENCE)], (5, 5));
+        assert_eq!(connected.iter().sum::<usize>(), 5 + 2 * 5, "expected the post and two rails, got {:?}", connected);
+    }
+    
+    #[test]
+    fn plants_are_two_sprites_and_hide_nothing() {
+        let blocks = [(5, 5, block::TALL_GRASS), (6, 5, block::STONE)];
+        assert_eq!(mesh_faces(&blocks, (5, 5)), [2, 0, 0, 0, 0, 0]);
+        assert_eq!(mesh_faces(&blocks, (6, 5))[3], 1, "the stone next to the plant should keep its side");
+    }
+    
+    #[test]
+    fn fence_collision_is_taller_and_follows_the_rails() {
+        let post = collision_boxes(block::FENCE, [block::AIR; 4]);
+        assert_eq!(post, [ShapeBox::new([6, 0, 6], [10, FENCE_COLLISION_HEIGHT, 10])]);
+        let connected = collision_boxes(block::FENCE, [block::FENCE, block::AIR, block::STONE, block::AIR]);
+        assert_eq!(connected.len(), 3);
+        assert!(connected.contains(&ShapeBox::new([10, 0, 6], [16, FENCE_COLLISION_HEIGHT, 10])), "no arm to the +x fence: {:?}", connected);
+        assert!(connected.contains(&ShapeBox::new([6, 0, 10], [10, FENCE_COLLISION_HEIGHT, 16])), "no arm to the +z stone: {:?}", connected);
+        // everything else collides with what's drawn
+        let slab = block_state::with(block::STONE_SLAB, &HALF, 1);
+        assert_eq!(collision_boxes(slab, [block::AIR; 4]), boxes(slab, [block::AIR; 4]));
+    }
+    
+    #[test]
+    fn models_load_into_boxes_and_bad_ones_get_rejected() {
+        let path = std::env::temp_dir().join(format!("test_block_model_{}.json", std::process::id()));
+        std::fs::write(&path, r#"{ "boxes": [{ "from": [0, 0, 0], "to": [16, 2, 16] }, { "from": [7, 2, 7], "to": [9, 12, 9] }] }"#).unwrap();
+        let model = load_model(&path);
+        std::fs::write(&path, r#"{ "boxes": [{ "from": [0, 0, 0], "to": [16, 20, 16] }] }"#).unwrap();
+        let sticking_out = load_model(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(model, Ok(vec![ShapeBox::new([0, 0, 0], [16, 2, 16]), ShapeBox::new([7, 2, 7], [9, 12, 9])]));
+        assert!(sticking_out.is_err(), "a box sticking out of its block shouldn't load");
+    }
+}
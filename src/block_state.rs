@@ -0,0 +1,207 @@
+use crate::block::{self, BlockId};
+use crate::math::Vec3;
+
+// named properties (facing, axis, half...) packed into a cell's state (see `block::state`)
+// each block lists the properties it has, which get packed one after the other from the lowest bit in that order,
+// each taking as few bits as its values need, so a value is just its index into the property's values
+
+#[derive(Debug)]
+pub struct StateProperty {
+    pub name: &'static str,
+    pub values: &'static [&'static str],
+}
+
+impl StateProperty {
+    fn bits(&self) -> u32 {
+        usize::BITS - (self.values.len() - 1).leading_zeros()
+    }
+}
+
+static NUMBERS: [&str; 8] = ["0", "1", "2", "3", "4", "5", "6", "7"];
+
+/// How far a fluid has flowed from its source
+pub static LEVEL: StateProperty = StateProperty { name: "level", values: &NUMBERS };
+/// Whether a fluid's being fed from above rather than flowing sideways
+pub static FALLING: StateProperty = StateProperty { name: "falling", values: &["false", "true"] };
+/// How grown a crop is
+pub static AGE: StateProperty = StateProperty { name: "age", values: &NUMBERS };
+/// Which half of its block a slab (or the base of stairs) is in
+pub static HALF: StateProperty = StateProperty { name: "half", values: &["bottom", "top"] };
+/// Which way something points, in the same order as the side faces (+x, -x, +z, -z)
+pub static FACING: StateProperty = StateProperty { name: "facing", values: &["east", "west", "south", "north"] };
+/// Which axis something runs along, like the grain of a log; upright comes first so a bare log stands up,
+/// which also lines each value up with the pair of faces it runs through (`face / 2`)
+pub static AXIS: StateProperty = StateProperty { name: "axis", values: &["y", "x", "z"] };
+
+/// Each of the block's properties along with where it's packed in the state
+fn layout(id: BlockId) -> impl Iterator<Item = (&'static StateProperty, u32)> {
+    block::properties(id).states.iter().scan(0, move |shift, property| {
+        let packed = (*property, *shift);
+        *shift += property.bits();
+        debug_assert!(*shift <= 16, "Block {} has more state than fits in a cell", block::properties(id).name);
+        Some(packed)
+    })
+}
+
+/// The index of a property's value in a cell, or `None` if its block doesn't have that property
+pub fn get(cell: u32, property: &StateProperty) -> Option<u16> {
+    let (_, shift) = layout(block::id(cell)).find(|(other, _)| std::ptr::eq(*other, property))?;
+    Some((block::state(cell) >> shift) & ((1 << property.bits()) - 1))
+}
+
+/// The cell with one of its properties changed (or unchanged if its block doesn't have that property)
+pub fn with(cell: u32, property: &StateProperty, value: u16) -> u32 {
+    let Some((_, shift)) = layout(block::id(cell)).find(|(other, _)| std::ptr::eq(*other, property)) else {
+        return cell;
+    };
+    debug_assert!((value as usize) < property.values.len(), "{} isn't a valid value for {}", value, property.name);
+    let mask = ((1 << property.bits()) - 1) << shift;
+    block::with_state(block::id(cell), (block::state(cell) & !mask) | (value << shift) & mask)
+}
+
+/// Like `stone slab[half=top]`, or just the block's name if it has no properties
+pub fn describe(cell: u32) -> String {
+    let properties: Vec<String> = layout(block::id(cell))
+        .map(|(property, _)| {
+            let value = get(cell, property).unwrap_or(0) as usize;
+            format!("{}={}", property.name, property.values.get(value).unwrap_or(&"?"))
+        })
+        .collect();
+    let name = block::properties(cell).name;
+    if properties.is_empty() { name.to_string() } else { format!("{}[{}]", name, properties.join(",")) }
+}
+
+/// The other way around from `describe`; any properties left out are left at their first value
+pub fn parse(text: &str) -> Result<u32, String> {
+    let (name, properties) = match text.split_once('[') {
+        Some((name, rest)) => (name, rest.strip_suffix(']').ok_or_else(|| format!("Missing a closing ']' in '{}'", text))?),
+        None => (text, ""),
+    };
+    let mut cell = block::by_name(name.trim()).ok_or_else(|| format!("There's no block called '{}'", name.trim()))?;
+    for pair in properties.split(',').filter(|pair| !pair.trim().is_empty()) {
+        let (key, value) = pair.split_once('=').ok_or_else(|| format!("'{}' should look like property=value", pair))?;
+        let (property, _) = layout(cell).find(|(property, _)| property.name == key.trim())
+            .ok_or_else(|| format!("A {} has no '{}' property", name.trim(), key.trim()))?;
+        let index = property.values.iter().position(|possible| *possible == value.trim())
+            .ok_or_else(|| format!("'{}' isn't a {}, it can be {}", value.trim(), property.name, property.values.join(", ")))?;
+        cell = with(cell, property, index as u16);
+    }
+    Ok(cell)
+}
+
+/// Which of `FACING` a direction points most along, ignoring its height
+pub fn facing_of(direction: Vec3) -> u16 {
+    if direction.x.abs() > direction.z.abs() {
+        if direction.x > 0.0 { 0 } else { 1 }
+    } else if direction.z > 0.0 { 2 } else { 3 }
+}
+
+/// The cell for placing a block against `face` of another, `height` (0 to 1) of the way up the block that was clicked,
+/// while looking along `look`:
+/// anything with an axis runs out from the face it was placed on, stairs face the way they were placed looking,
+/// and slabs and stairs go on top when placed against the underside of something or the top half of a side
+pub fn placed(id: BlockId, face: usize, height: f32, look: Vec3) -> u32 {
+    let mut cell = id;
+    for (property, _) in layout(id) {
+        let value = if std::ptr::eq(property, &AXIS) {
+            face as u16 / 2
+        } else if std::ptr::eq(property, &FACING) {
+            facing_of(look)
+        } else if std::ptr::eq(property, &HALF) {
+            match face {
+                0 => 0,
+                1 => 1,
+                _ => (height > 0.5) as u16,
+            }
+        } else {
+            continue;
+        };
+        cell = with(cell, property, value);
+    }
+    cell
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Every block id that has state, along with its properties
+    fn stateful_blocks() -> Vec<(BlockId, Vec<&'static StateProperty>)> {
+        (0..256)
+            .map(|id| (id, layout(id).map(|(property, _)| property).collect::<Vec<_>>()))
+            .filter(|(_, properties)| !properties.is_empty())
+            .collect()
+    }
+    
+    #[test]
+    fn every_property_round_trips_through_the_state() {
+        let blocks = stateful_blocks();
+        assert!(blocks.len() >= 6, "expected more blocks with state, got {:?}", blocks.iter().map(|(id, _)| *id).collect::<Vec<_>>());
+        for (id, properties) in blocks {
+            // every other property is set to its last value, so packing one can't be hiding that it clobbers its neighbours
+            let filled = properties.iter().fold(id, |cell, other| with(cell, other, other.values.len() as u16 - 1));
+            for property in &properties {
+                for value in 0..property.values.len() as u16 {
+                    let cell = with(filled, property, value);
+                    assert_eq!(block::id(cell), id);
+                    assert_eq!(get(cell, property), Some(value), "{} didn't keep {}={}", block::properties(id).name, property.name, value);
+                    for other in properties.iter().filter(|other| !std::ptr::eq(**other, *property)) {
+                        assert_eq!(get(cell, other), Some(other.values.len() as u16 - 1), "setting {} on {} changed {}", property.name, block::properties(id).name, other.name);
+                    }
+                    assert_eq!(parse(&describe(cell)), Ok(cell), "'{}' didn't parse back", describe(cell));
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn properties_pack_from_the_lowest_bit_in_order() {
+        let stairs = with(with(block::COBBLESTONE_STAIRS, &FACING, 3), &HALF, 1);
+        assert_eq!(block::state(stairs), 0b111);
+        assert_eq!(get(stairs, &AXIS), None, "stairs don't have an axis");
+        assert_eq!(with(block::STONE, &HALF, 1), block::STONE, "setting a property a block doesn't have should leave it alone");
+    }
+    
+    #[test]
+    fn bad_descriptions_dont_parse() {
+        assert_eq!(parse("stone"), Ok(block::STONE));
+        for bad in ["stone[axis=y]", "log[axis=w]", "cobblestone stairs[facing=north", "nothing"] {
+            assert!(parse(bad).is_err(), "'{}' shouldn't parse", bad);
+        }
+    }
+    
+    #[test]
+    fn axes_run_out_from_the_clicked_face() {
+        for (face, axis) in [(0, "y"), (1, "y"), (2, "x"), (3, "x"), (4, "z"), (5, "z")] {
+            let log = placed(block::LOG, face, 0.5, Vec3::new(0.0, 0.0, -1.0));
+            assert_eq!(describe(log), format!("log[axis={}]", axis), "placed against face {}", face);
+        }
+    }
+
+    #[test]
+    fn facing_follows_the_look_direction() {
+        let looks = [
+            (Vec3::new(0.9, -0.3, 0.2), "east"),
+            (Vec3::new(-0.9, -0.3, 0.2), "west"),
+            (Vec3::new(0.2, 0.5, 0.7), "south"),
+            (Vec3::new(-0.3, -0.9, -0.4), "north"),
+        ];
+        for (look, facing) in looks {
+            let stairs = placed(block::COBBLESTONE_STAIRS, 0, 1.0, look);
+            assert_eq!(describe(stairs), format!("cobblestone stairs[facing={},half=bottom]", facing), "looking along {:?}", look);
+        }
+    }
+    
+    #[test]
+    fn halves_follow_where_the_face_was_clicked() {
+        let half = |face: usize, height: f32| describe(placed(block::STONE_SLAB, face, height, Vec3::X));
+        assert_eq!(half(0, 1.0), "stone slab[half=bottom]", "placed on top of something");
+        assert_eq!(half(1, 0.0), "stone slab[half=top]", "placed against the underside of something");
+        for face in 2..6 {
+            assert_eq!(half(face, 0.75), "stone slab[half=top]", "placed high on side {}", face);
+            assert_eq!(half(face, 0.25), "stone slab[half=bottom]", "placed low on side {}", face);
+        }
+        // stairs flip the same way
+        assert_eq!(get(placed(block::COBBLESTONE_STAIRS, 1, 0.0, Vec3::X), &HALF), Some(1));
+    }
+}
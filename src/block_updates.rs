@@ -0,0 +1,307 @@
+use crate::block;
+use crate::block_state::{self, AGE};
+use crate::fluid;
+use crate::world::{BlockPosition, World};
+use rand::{Rng, SeedableRng};
+
+// everything blocks do on their own, ran once per simulation tick:
+// scheduled updates for blocks something changed next to (fluids flowing, sand falling, crops popping off), soonest first,
+// then random ticks for the slow stuff that happens whenever (grass spreading, crops growing, leaves decaying)
+// the only randomness is the seeded rng and everything's visited in a fixed order, so the same seed and edits always give the same world
+
+/// How many blocks get picked at random in each chunk every tick
+static RANDOM_TICKS_PER_CHUNK: usize = 3;
+/// Chunks changed by random ticks only get marked for remeshing this often, so a slowly spreading field doesn't remesh every tick
+static RANDOM_TICK_REMESH_INTERVAL: u64 = 20;
+/// How many ticks a falling block takes to drop one block
+static FALL_DELAY: u64 = 2;
+/// How many ticks a crop takes to notice it's lost the ground under it
+static CROP_SUPPORT_DELAY: u64 = 1;
+/// Leaves further than this from any log (in every direction) decay
+static LEAF_SUPPORT_DISTANCE: i32 = 4;
+/// The chance a crop grows a stage each time it's randomly ticked
+static CROP_GROWTH_CHANCE: f64 = 0.35;
+/// How many `AGE`s a crop grows through, with the last one being fully grown
+pub static CROP_STAGES: u16 = 8;
+
+/// How long after a change next to it a block reacts to it, or `None` if it doesn't care
+fn update_delay(cell: u32) -> Option<u64> {
+    if let Some(fluid) = block::fluid(cell) {
+        return Some(fluid.tick_delay());
+    }
+    if block::properties(cell).falls {
+        return Some(FALL_DELAY);
+    }
+    (block::id(cell) == block::WHEAT).then_some(CROP_SUPPORT_DELAY)
+}
+
+fn supports_crop(cell: u32) -> bool {
+    matches!(block::id(cell), block::DIRT | block::GRASS)
+}
+
+/// (due tick, position), with the reverse making the max heap give the soonest first (and the lowest position for ties)
+type ScheduledUpdate = std::cmp::Reverse<(u64, (i32, i32, i32))>;
+
+/// The block update queue and random ticks for a world
+pub struct BlockUpdates {
+    tick: u64,
+    scheduled: std::collections::BinaryHeap<ScheduledUpdate>,
+    pending: std::collections::HashSet<BlockPosition>,  // already scheduled, so nothing gets queued twice
+    random: rand::rngs::StdRng,
+    // chunks changed since they were last marked as mutated, which happens at the end of the tick
+    // (or every `RANDOM_TICK_REMESH_INTERVAL` ticks for changes made by random ticks)
+    changed_chunks: std::collections::BTreeSet<usize>,
+    randomly_changed_chunks: std::collections::BTreeSet<usize>,
+}
+
+impl BlockUpdates {
+    pub fn new(seed: u64) -> Self {
+        BlockUpdates {
+            tick: 0,
+            scheduled: std::collections::BinaryHeap::new(),
+            pending: std::collections::HashSet::new(),
+            random: rand::rngs::StdRng::seed_from_u64(seed),
+            changed_chunks: std::collections::BTreeSet::new(),
+            randomly_changed_chunks: std::collections::BTreeSet::new(),
+        }
+    }
+    
+    #[cfg(test)]
+    pub fn pending_updates(&self) -> usize {
+        self.pending.len()
+    }
+    
+    /// Queues an update for the block if it's something that reacts to its neighbours
+    fn schedule(&mut self, world: &World, position: BlockPosition) {
+        let Some(delay) = update_delay(world.block(position)) else {
+            return;
+        };
+        if self.pending.insert(position) {
+            self.scheduled.push(std::cmp::Reverse((self.tick + delay, (position.x, position.y, position.z))));
+        }
+    }
+    
+    /// Lets the block and everything next to it react to it changing (call after any edit to the world)
+    pub fn block_changed(&mut self, world: &World, position: BlockPosition) {
+        self.schedule(world, position);
+        self.schedule(world, position.above());
+        self.schedule(world, position.below());
+        for neighbour in position.horizontal_neighbours() {
+            self.schedule(world, neighbour);
+        }
+    }
+    
+    /// Changes a block from an update, with its chunk marked for remeshing at the end of the tick
+    pub fn set(&mut self, world: &mut World, position: BlockPosition, cell: u32) {
+        if let Some(chunk_index) = world.set_block_unmarked(position, cell) {
+            self.changed_chunks.insert(chunk_index);
+            self.block_changed(world, position);
+        }
+    }
+    
+    fn set_from_random_tick(&mut self, world: &mut World, position: BlockPosition, cell: u32) {
+        if let Some(chunk_index) = world.set_block_unmarked(position, cell) {
+            self.randomly_changed_chunks.insert(chunk_index);
+            self.block_changed(world, position);
+        }
+    }
+    
+    /// Advances one tick, running every scheduled update that's due and then the random ticks
+    /// returns how many chunks got marked as mutated (so whether it's worth asking for a remesh)
+    pub fn tick(&mut self, world: &mut World) -> usize {
+        let _span = crate::logging::span("world", "block_updates");
+        self.tick += 1;
+        while let Some(std::cmp::Reverse((due, (x, y, z)))) = self.scheduled.peek().copied() {
+            if due > self.tick {
+                break;
+            }
+            self.scheduled.pop();
+            let position = BlockPosition::new(x, y, z);
+            self.pending.remove(&position);
+            self.update(world, position);
+        }
+        
+        for chunk_index in 0..world.chunks().len() {
+            let origin = world.chunks()[chunk_index].position;
+            for _ in 0..RANDOM_TICKS_PER_CHUNK {
+                // 4 bits for each axis
+                let picked: u16 = self.random.random();
+                let position = BlockPosition::new(
+                    origin.x as i32 + (picked & 0xF) as i32,
+                    origin.y as i32 + (picked >> 4 & 0xF) as i32,
+                    origin.z as i32 + (picked >> 8 & 0xF) as i32,
+                );
+                self.random_tick(world, position);
+            }
+        }
+        
+        if self.tick.is_multiple_of(RANDOM_TICK_REMESH_INTERVAL) {
+            let randomly_changed = std::mem::take(&mut self.randomly_changed_chunks);
+            self.changed_chunks.extend(randomly_changed);
+        }
+        let marked = self.changed_chunks.len();
+        for chunk_index in std::mem::take(&mut self.changed_chunks) {
+            world.mark_mutated(chunk_index);
+        }
+        marked
+    }
+    
+    /// Lets a block react to something next to it changing
+    fn update(&mut self, world: &mut World, position: BlockPosition) {
+        let cell = world.block(position);
+        if block::fluid(cell).is_some() {
+            fluid::update(self, world, position);
+        } else if block::properties(cell).falls {
+            // one block at a time, rescheduling itself by changing until it lands
+            let below = position.below();
+            if world.is_loaded(below) && !world.is_solid(below) {
+                self.set(world, position, block::AIR);
+                self.set(world, below, cell);
+            }
+        } else if block::id(cell) == block::WHEAT && !supports_crop(world.block(position.below())) {
+            self.set(world, position, block::AIR);
+        }
+    }
+    
+    fn random_tick(&mut self, world: &mut World, position: BlockPosition) {
+        let cell = world.block(position);
+        match block::id(cell) {
+            block::GRASS => {
+                // grass dies under anything opaque, otherwise it spreads to nearby dirt that isn't covered
+                if block::is_opaque(world.block(position.above())) {
+                    self.set_from_random_tick(world, position, block::DIRT);
+                    return;
+                }
+                let target = BlockPosition::new(
+                    position.x + self.random.random_range(-1..=1),
+                    position.y + self.random.random_range(-2..=1),
+                    position.z + self.random.random_range(-1..=1),
+                );
+                if world.block(target) == block::DIRT && !block::is_opaque(world.block(target.above())) {
+                    self.set_from_random_tick(world, target, block::GRASS);
+                }
+            },
+            block::WHEAT => {
+                let stage = block_state::get(cell, &AGE).unwrap_or(0);
+                if stage + 1 < CROP_STAGES && self.random.random_bool(CROP_GROWTH_CHANCE) {
+                    self.set_from_random_tick(world, position, block_state::with(cell, &AGE, stage + 1));
+                }
+            },
+            block::LEAVES => {
+                let range = -LEAF_SUPPORT_DISTANCE..=LEAF_SUPPORT_DISTANCE;
+                let supported = range.clone().any(|x| range.clone().any(|y| range.clone().any(|z| {
+                    block::id(world.block(BlockPosition::new(position.x + x, position.y + y, position.z + z))) == block::LOG
+                })));
+                if !supported {
+                    self.set_from_random_tick(world, position, block::AIR);
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn place(updates: &mut BlockUpdates, world: &mut World, position: BlockPosition, cell: u32) {
+        world.set_block(position, cell);
+        updates.block_changed(world, position);
+    }
+    
+    /// Lets grass spread over a dirt floor from two blocks of it, checking chunks only get marked as mutated in batches
+    fn grow_grass(seed: u64, ticks: u64) -> World {
+        let mut world = World::test_floor(block::DIRT);
+        let mut updates = BlockUpdates::new(seed);
+        world.set_block(BlockPosition::new(8, 0, 8), block::GRASS);
+        world.set_block(BlockPosition::new(24, 0, 8), block::GRASS);
+        world.chunks_mut()[1].mutated = false;
+        world.chunks_mut()[2].mutated = false;
+        for tick in 1..=ticks {
+            updates.tick(&mut world);
+            if world.chunks().iter().any(|chunk| chunk.mutated) {
+                assert!(tick.is_multiple_of(RANDOM_TICK_REMESH_INTERVAL), "random ticks marked a chunk as mutated on tick {}, between batches", tick);
+                for chunk in world.chunks_mut() {
+                    chunk.mutated = false;
+                }
+            }
+        }
+        world
+    }
+    
+    #[test]
+    fn falling_blocks_land_and_stacks_drop_together() {
+        let mut world = World::test_floor(block::STONE);
+        let mut updates = BlockUpdates::new(0);
+        place(&mut updates, &mut world, BlockPosition::new(4, 6, 4), block::SAND);
+        for y in 1..5 {
+            let cell = if y == 1 { block::STONE } else { block::GRAVEL };
+            place(&mut updates, &mut world, BlockPosition::new(20, y, 8), cell);
+        }
+        for _ in 0..40 {
+            updates.tick(&mut world);
+        }
+        assert_eq!(world.block(BlockPosition::new(4, 1, 4)), block::SAND, "sand dropped from y = 6 should land on the floor");
+        assert_eq!(world.count(block::SAND), 1);
+        
+        updates.set(&mut world, BlockPosition::new(20, 1, 8), block::AIR);
+        for _ in 0..40 {
+            updates.tick(&mut world);
+        }
+        let column: Vec<u32> = (1..5).map(|y| world.block(BlockPosition::new(20, y, 8))).collect();
+        assert_eq!(column, [block::GRAVEL, block::GRAVEL, block::GRAVEL, block::AIR], "the gravel stack should drop a block once its support goes");
+        assert_eq!(updates.pending_updates(), 0);
+    }
+    
+    #[test]
+    fn grass_spreads_with_chunks_marked_in_batches() {
+        let world = grow_grass(1, 20_000);
+        assert!(world.count(block::GRASS) >= 10, "only {} blocks of grass after 20000 ticks", world.count(block::GRASS));
+    }
+    
+    #[test]
+    fn the_same_seed_gives_the_same_world() {
+        let tiles = |world: &World| world.chunks().iter().map(|chunk| chunk.tile_data).collect::<Vec<_>>();
+        let world = tiles(&grow_grass(1, 5_000));
+        assert!(world == tiles(&grow_grass(1, 5_000)), "the same seed gave different worlds");
+        assert!(world != tiles(&grow_grass(2, 5_000)), "a different seed gave the same world");
+    }
+    
+    #[test]
+    fn covered_grass_dies_and_crops_ripen_then_pop_off() {
+        let mut world = World::test_floor(block::GRASS);
+        let mut updates = BlockUpdates::new(3);
+        for x in 0..16 {
+            world.set_block(BlockPosition::new(x, 1, 3), block::STONE);
+            world.set_block(BlockPosition::new(x, 1, 10), block::WHEAT);
+        }
+        for _ in 0..100_000 {
+            updates.tick(&mut world);
+        }
+        let row = |world: &World, y: i32, z: i32| (0..16).map(|x| world.block(BlockPosition::new(x, y, z))).collect::<Vec<_>>();
+        assert_eq!(row(&world, 0, 3), [block::DIRT; 16], "grass under stone should turn to dirt");
+        let ripe = block_state::with(block::WHEAT, &AGE, CROP_STAGES - 1);
+        assert_eq!(row(&world, 1, 10), [ripe; 16], "every crop should have grown to its last stage");
+        
+        updates.set(&mut world, BlockPosition::new(5, 0, 10), block::AIR);
+        updates.tick(&mut world);
+        updates.tick(&mut world);
+        assert_eq!(world.block(BlockPosition::new(5, 1, 10)), block::AIR, "a crop should pop off once the ground under it is gone");
+    }
+    
+    #[test]
+    fn leaves_away_from_logs_decay() {
+        let mut world = World::test_floor(block::STONE);
+        let mut updates = BlockUpdates::new(4);
+        world.set_block(BlockPosition::new(4, 1, 8), block::LOG);
+        for x in (5..8).chain(20..23) {
+            world.set_block(BlockPosition::new(x, 1, 8), block::LEAVES);
+        }
+        for _ in 0..40_000 {
+            updates.tick(&mut world);
+        }
+        assert_eq!(world.count(block::LEAVES), 3, "only the leaves next to the log should be left");
+        assert_eq!(world.block(BlockPosition::new(6, 1, 8)), block::LEAVES);
+    }
+}
@@ -0,0 +1,340 @@
+use crate::math::{Mat4, Quat, Vec3};
+
+/// How far the camera can look up or down (just short of straight up so yaw doesn't flip)
+static PITCH_LIMIT: f32 = 89.0 * std::f32::consts::PI / 180.0;
+
+/// The true isometric angles: looking down the diagonal of a cube (atan(1/sqrt(2)) ≈ 35.264°) from 45° around
+static ISOMETRIC_PITCH: f32 = -35.264 * std::f32::consts::PI / 180.0;
+static ISOMETRIC_YAW: f32 = 45.0 * std::f32::consts::PI / 180.0;
+
+/// How far behind the focus point an orthographic camera sits, so terrain around it doesn't end up behind the near plane
+static ORTHOGRAPHIC_BACKOFF: f32 = 512.0;
+
+static MIN_ORTHOGRAPHIC_HEIGHT: f32 = 2.0;
+static MAX_ORTHOGRAPHIC_HEIGHT: f32 = 1024.0;
+
+/// How the view space gets projected onto the screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Perspective,
+    Orthographic {
+        half_height: f32,  // half of the visible world height; changed by zooming rather than moving
+    },
+}
+
+/// The extent of the view volume at a given view space depth: |x| <= z * slope_x + offset_x (same for y)
+/// perspective volumes only have slopes and orthographic volumes only have offsets
+#[derive(Clone, Copy, Debug)]
+pub struct ViewBounds {
+    pub slope_x: f32,
+    pub slope_y: f32,
+    pub offset_x: f32,
+    pub offset_y: f32,
+}
+
+/// Tunables for how the camera responds to input
+#[derive(Clone, Copy, Debug)]
+pub struct CameraControls {
+    pub max_speed: f32,           // units per second
+    pub zoom_speed: f32,          // fraction of the orthographic height per second (or per wheel notch)
+    pub sprint_multiplier: f32,
+    pub acceleration: f32,        // how quickly the velocity catches up to the input (per second)
+    pub mouse_sensitivity: f32,   // radians per pixel of relative mouse motion
+    pub turn_speed: f32,          // radians per second for the arrow keys
+}
+
+impl Default for CameraControls {
+    fn default() -> Self {
+        CameraControls {
+            max_speed: 24.0,
+            zoom_speed: 1.5,
+            sprint_multiplier: 3.0,
+            acceleration: 10.0,
+            mouse_sensitivity: 0.0025,
+            turn_speed: 1.5,
+        }
+    }
+}
+
+/// The input gathered for a single frame
+/// movement axes are in the range -1..1 and relative to where the camera is facing
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CameraInput {
+    pub forward: f32,
+    pub left: f32,
+    pub up: f32,
+    pub sprint: bool,
+    pub yaw: f32,    // radians to turn this frame (mouse deltas are already scaled)
+    pub pitch: f32,
+    pub zoom: f32,   // positive zooms in; only used by orthographic projections
+}
+
+/// The camera owns everything needed to build the view and projection for a frame
+/// view space is +z forward, +y up and +x to the left of the screen (see `Mat4::perspective`)
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    yaw: f32,
+    pitch: f32,
+    orientation: Quat,  // world -> view rotation, rebuilt from yaw and pitch
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+    pub projection: Projection,
+    isometric: bool,  // locks the angles to the isometric preset
+    pub controls: CameraControls,
+}
+
+impl Camera {
+    pub fn new(position: Vec3, fov_y_degrees: f32, near: f32, far: f32) -> Self {
+        let mut camera = Camera {
+            position,
+            velocity: Vec3::ZERO,
+            yaw: 0.0,
+            pitch: 0.0,
+            orientation: Quat::IDENTITY,
+            fov_y: fov_y_degrees.to_radians(),
+            near,
+            far,
+            projection: Projection::Perspective,
+            isometric: false,
+            controls: CameraControls::default(),
+        };
+        camera.rebuild_orientation();
+        camera
+    }
+    
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+    
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+    
+    /// Switches between perspective and a free orthographic camera (keeping the current angles)
+    pub fn toggle_orthographic(&mut self) {
+        self.isometric = false;
+        self.projection = match self.projection {
+            Projection::Perspective => Projection::Orthographic { half_height: 32.0 },
+            Projection::Orthographic { .. } => Projection::Perspective,
+        };
+    }
+    
+    /// Switches to (or back out of) the fixed isometric preset
+    pub fn toggle_isometric(&mut self) {
+        if self.isometric {
+            self.isometric = false;
+            self.projection = Projection::Perspective;
+            return;
+        }
+        self.isometric = true;
+        if let Projection::Perspective = self.projection {
+            self.projection = Projection::Orthographic { half_height: 32.0 };
+        }
+        self.yaw = ISOMETRIC_YAW;
+        self.pitch = ISOMETRIC_PITCH;
+        self.rebuild_orientation();
+    }
+    
+    /// Scales the visible area of an orthographic camera; `amount` > 0 zooms in
+    pub fn zoom(&mut self, amount: f32) {
+        if let Projection::Orthographic { half_height } = &mut self.projection {
+            *half_height = (*half_height * (-amount).exp()).clamp(MIN_ORTHOGRAPHIC_HEIGHT, MAX_ORTHOGRAPHIC_HEIGHT);
+        }
+    }
+    
+    /// Turns the camera, clamping the pitch so it can't flip over the top
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = (self.yaw + yaw).rem_euclid(std::f32::consts::TAU);
+        self.pitch = (self.pitch + pitch).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+        self.rebuild_orientation();
+    }
+    
+    fn rebuild_orientation(&mut self) {
+        // pitch is applied after yaw so looking up and down is always around the camera's own x axis
+        self.orientation = Quat::from_euler(self.pitch, self.yaw, 0.0);
+    }
+    
+    /// The direction the camera is looking in world space
+    pub fn forward(&self) -> Vec3 {
+        self.orientation.conjugate().rotate(Vec3::Z)
+    }
+    
+    /// The forward direction flattened onto the ground, used for walking so looking down doesn't slow movement
+    pub fn forward_flat(&self) -> Vec3 {
+        Quat::from_axis_angle(Vec3::Y, -self.yaw).rotate(Vec3::Z)
+    }
+    
+    /// The direction that moves things towards the left side of the screen
+    pub fn left_flat(&self) -> Vec3 {
+        Quat::from_axis_angle(Vec3::Y, -self.yaw).rotate(Vec3::X)
+    }
+    
+    /// Where the view is actually rendered from; orthographic cameras are pulled back along the view direction
+    /// so `position` stays the point being looked at
+    pub fn eye_position(&self) -> Vec3 {
+        match self.projection {
+            Projection::Perspective => self.position,
+            Projection::Orthographic { .. } => self.position - self.forward() * ORTHOGRAPHIC_BACKOFF,
+        }
+    }
+    
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::view(self.eye_position(), self.orientation)
+    }
+    
+    pub fn projection_matrix(&self, aspect: f32) -> Mat4 {
+        match self.projection {
+            Projection::Perspective => Mat4::perspective(self.fov_y, aspect, self.near, self.far),
+            Projection::Orthographic { half_height } => Mat4::orthographic(half_height * aspect, half_height, self.near, self.far),
+        }
+    }
+    
+    pub fn view_bounds(&self, aspect: f32) -> ViewBounds {
+        match self.projection {
+            Projection::Perspective => {
+                let tan_half_fov_y = (self.fov_y * 0.5).tan();
+                ViewBounds { slope_x: tan_half_fov_y * aspect, slope_y: tan_half_fov_y, offset_x: 0.0, offset_y: 0.0 }
+            },
+            Projection::Orthographic { half_height } => {
+                ViewBounds { slope_x: 0.0, slope_y: 0.0, offset_x: half_height * aspect, offset_y: half_height }
+            },
+        }
+    }
+    
+    /// The direction from a view space point back to the camera, used for backface culling
+    /// every point shares the same one under an orthographic projection
+    pub fn view_vector(&self, view_position: Vec3) -> Vec3 {
+        match self.projection {
+            Projection::Perspective => (-view_position).normalized(),
+            Projection::Orthographic { .. } => -Vec3::Z,
+        }
+    }
+    
+    /// Applies the turning and zooming from a frame of input, scaled by the frame time
+    /// done every frame rather than every tick so looking around stays responsive; returns true if the view changed
+    pub fn look(&mut self, input: &CameraInput, delta_time: f32) -> bool {
+        let turned = !self.isometric && (input.yaw != 0.0 || input.pitch != 0.0);
+        if turned {
+            self.rotate(input.yaw, input.pitch);
+        }
+        
+        // orthographic cameras zoom where a perspective one would move up or down
+        let zoom = match self.projection {
+            Projection::Perspective => 0.0,
+            Projection::Orthographic { .. } => input.zoom + input.up * self.controls.zoom_speed * delta_time,
+        };
+        let zoomed = zoom != 0.0;
+        if zoomed {
+            self.zoom(zoom);
+        }
+        turned || zoomed
+    }
+    
+    /// Runs one simulation tick of movement from the held keys; returns true if the camera moved
+    pub fn step(&mut self, input: &CameraInput, delta_time: f32) -> bool {
+        let vertical = match self.projection {
+            Projection::Perspective => input.up,
+            Projection::Orthographic { .. } => 0.0,  // zooms instead (see `look`)
+        };
+        
+        let mut wish = self.forward_flat() * input.forward + self.left_flat() * input.left + Vec3::Y * vertical;
+        if wish.length_squared() > 1.0 {
+            wish = wish.normalized();  // diagonal movement shouldn't be faster
+        }
+        let speed = self.controls.max_speed * if input.sprint { self.controls.sprint_multiplier } else { 1.0 };
+        let target_velocity = wish * speed;
+        
+        // easing towards the target gives both acceleration and deceleration without a separate friction term
+        let blend = (self.controls.acceleration * delta_time).min(1.0);
+        self.velocity = self.velocity.lerp(target_velocity, blend);
+        if target_velocity == Vec3::ZERO && self.velocity.length_squared() < 0.0001 {
+            self.velocity = Vec3::ZERO;
+        }
+        
+        let moved = self.velocity != Vec3::ZERO;
+        self.position += self.velocity * delta_time;
+        moved
+    }
+    
+    /// The camera as it should be drawn `alpha` of the way from the last tick's position to this one's
+    pub fn interpolated(&self, previous_position: Vec3, alpha: f32) -> Camera {
+        let mut camera = *self;
+        camera.position = previous_position.lerp(self.position, alpha);
+        camera
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn camera() -> Camera {
+        Camera::new(Vec3::new(0.0, 10.0, 0.0), 70.0, 0.1, 500.0)
+    }
+    
+    #[test]
+    fn pitch_stops_short_of_straight_up_and_down() {
+        let mut camera = camera();
+        camera.rotate(0.0, 10.0);
+        assert_eq!(camera.pitch(), PITCH_LIMIT);
+        assert!(camera.forward().y < 1.0 && camera.forward().z > 0.0, "looking straight up or over the top: {:?}", camera.forward());
+        camera.rotate(0.0, -20.0);
+        assert_eq!(camera.pitch(), -PITCH_LIMIT);
+        assert!(camera.forward().y > -1.0 && camera.forward().z > 0.0, "looking straight down or over the bottom: {:?}", camera.forward());
+    }
+    
+    #[test]
+    fn yaw_wraps_around_a_full_turn() {
+        let mut camera = camera();
+        camera.rotate(-1.0, 0.0);
+        assert!((camera.yaw() - (std::f32::consts::TAU - 1.0)).abs() < 1e-5, "turning left from 0 gave a yaw of {}", camera.yaw());
+        camera.rotate(std::f32::consts::TAU * 3.0 + 2.0, 0.0);
+        assert!((camera.yaw() - 1.0).abs() < 1e-4, "three and a bit turns gave a yaw of {}", camera.yaw());
+        assert!((0.0..std::f32::consts::TAU).contains(&camera.yaw()));
+    }
+    
+    #[test]
+    fn the_isometric_preset_looks_down_a_cube_diagonal() {
+        let mut camera = camera();
+        camera.rotate(1.0, 0.5);
+        camera.toggle_isometric();
+        assert!(matches!(camera.projection, Projection::Orthographic { .. }));
+        let forward = camera.forward();
+        let diagonal = 1.0 / 3f32.sqrt();
+        assert!((forward.x.abs() - diagonal).abs() < 1e-3 && (forward.y + diagonal).abs() < 1e-3 && (forward.z.abs() - diagonal).abs() < 1e-3,
+            "not looking down a cube diagonal: {:?}", forward);
+        // and the angles stay locked
+        assert!(!camera.look(&CameraInput { yaw: 1.0, pitch: 1.0, ..CameraInput::default() }, 0.1));
+        assert_eq!((camera.yaw(), camera.pitch()), (ISOMETRIC_YAW, ISOMETRIC_PITCH));
+        camera.toggle_isometric();
+        assert_eq!(camera.projection, Projection::Perspective);
+    }
+
+    #[test]
+    fn zooming_stays_within_its_limits() {
+        let mut camera = camera();
+        camera.zoom(1.0);
+        assert_eq!(camera.projection, Projection::Perspective, "zooming a perspective camera did something");
+        camera.toggle_orthographic();
+        camera.zoom(2f32.ln());
+        assert_eq!(camera.projection, Projection::Orthographic { half_height: 16.0 });
+        camera.zoom(100.0);
+        assert_eq!(camera.projection, Projection::Orthographic { half_height: MIN_ORTHOGRAPHIC_HEIGHT });
+        camera.zoom(-100.0);
+        assert_eq!(camera.projection, Projection::Orthographic { half_height: MAX_ORTHOGRAPHIC_HEIGHT });
+    }
+    
+    #[test]
+    fn orthographic_view_vectors_are_all_the_same() {
+        let mut camera = camera();
+        let points = [Vec3::new(0.0, 0.0, 10.0), Vec3::new(-30.0, 12.0, 5.0), Vec3::new(4.0, -8.0, 200.0)];
+        assert!((camera.view_vector(points[0]) - camera.view_vector(points[1])).length() > 0.1, "perspective view vectors should differ");
+        camera.toggle_orthographic();
+        for point in points {
+            assert_eq!(camera.view_vector(point), -Vec3::Z);
+        }
+    }
+}
@@ -1,74 +1,131 @@
-use crate::shader_handling::{Float2, Float4, Uint4, Vertex};
+use crate::shader_handling::{Float2, Float4, Uchar4, Vertex};
+use crate::triangle::{Triangle, TriangleFlags};
 use crate::meshing::Mesh;
+use crate::occlusion::ChunkConnectivity;
+use crate::block;
+use crate::block_shape::{self, BlockShape};
+use crate::block_state;
+use crate::fluid;
+use crate::block_updates;
+use crate::biome;
 
-pub fn generate_cube(mesh: &mut Mesh, size: f32, position: Float4, chunk_priority: usize, chunk_index: usize) {
-    mesh.mutated(true);
-    let half_size = size / 2.0;
-    let mut vertices = vec![
-        // Front face
-        Vertex::new(Float4::new(position.x - half_size, position.y - half_size, position.z + half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x + half_size, position.y - half_size, position.z + half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x - half_size, position.y + half_size, position.z + half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x + half_size, position.y + half_size, position.z + half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        
-        Vertex::new(Float4::new(position.x - half_size, position.y - half_size, position.z - half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x + half_size, position.y - half_size, position.z - half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x - half_size, position.y + half_size, position.z - half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x + half_size, position.y + half_size, position.z - half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        
-        Vertex::new(Float4::new(position.x + half_size, position.y - half_size, position.z - half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x + half_size, position.y - half_size, position.z + half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x + half_size, position.y + half_size, position.z - half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x + half_size, position.y + half_size, position.z + half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        
-        Vertex::new(Float4::new(position.x - half_size, position.y - half_size, position.z - half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x - half_size, position.y - half_size, position.z + half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x - half_size, position.y + half_size, position.z - half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x - half_size, position.y + half_size, position.z + half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        
-        Vertex::new(Float4::new(position.x - half_size, position.y + half_size, position.z - half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x - half_size, position.y + half_size, position.z + half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x + half_size, position.y + half_size, position.z - half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x + half_size, position.y + half_size, position.z + half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        
-        Vertex::new(Float4::new(position.x - half_size, position.y - half_size, position.z - half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x - half_size, position.y - half_size, position.z + half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x + half_size, position.y - half_size, position.z - half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-        Vertex::new(Float4::new(position.x + half_size, position.y - half_size, position.z + half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, Float4::new(1.0, 1.0, 1.0, 0.0)),
-    ];
-    let start_index = mesh.vertices_original_ref().len() as u32;
-    mesh.append_vertices(&mut vertices, chunk_priority, chunk_index);
+/// How far outside the chunk (in tiles) a packed vertex can sit; the coarse lods overhang the chunk by up to half their tile size
+static CHUNK_VERTEX_BIAS: f32 = 8.0;
+
+/// A face of a tile's cube as (normal, neighbour offset, corners in the same order as `remesh_tile`)
+type CubeFace = (u32, (i32, i32, i32), [(f32, f32, f32); 4]);
+
+static CUBE_FACES: [CubeFace; 6] = [
+    (3, (-1, 0, 0), [(-0.5, -0.5, -0.5), (-0.5, -0.5, 0.5), (-0.5, 0.5, -0.5), (-0.5, 0.5, 0.5)]),
+    (1, (0, -1, 0), [(-0.5, -0.5, -0.5), (-0.5, -0.5, 0.5), (0.5, -0.5, -0.5), (0.5, -0.5, 0.5)]),
+    (5, (0, 0, -1), [(-0.5, -0.5, -0.5), (0.5, -0.5, -0.5), (-0.5, 0.5, -0.5), (0.5, 0.5, -0.5)]),
+    (2, (1, 0, 0), [(0.5, -0.5, -0.5), (0.5, -0.5, 0.5), (0.5, 0.5, -0.5), (0.5, 0.5, 0.5)]),
+    (0, (0, 1, 0), [(-0.5, 0.5, -0.5), (-0.5, 0.5, 0.5), (0.5, 0.5, -0.5), (0.5, 0.5, 0.5)]),
+    (4, (0, 0, 1), [(-0.5, -0.5, 0.5), (0.5, -0.5, 0.5), (-0.5, 0.5, 0.5), (0.5, 0.5, 0.5)]),
+];
+
+/// The uv of a point on `face`, given how far across the face's tile it is on each axis (0 to 1)
+/// textures lie along x and z on the tops and bottoms, and sit upright on the sides with v going down from the top;
+/// `repeat` tiles them that many times across (for the merged faces of coarse lods), and `rotated` turns them a quarter
+fn face_uv(face: usize, across: [f32; 3], repeat: f32, rotated: bool) -> (f32, f32) {
+    let (u, v) = if face < 2 {
+        (across[0], across[2])
+    } else {
+        (across[block_shape::face_axes(face).1], 1.0 - across[1])
+    };
+    let (u, v) = if rotated { (1.0 - v, u) } else { (u, v) };
+    (u * repeat, v * repeat)
+}
+
+/// A chunk vertex packed into 8 bytes, with its position relative to the chunk's origin (kept once per chunk in the mesh)
+/// position_face: x, y, z in sixteenths of a tile plus the bias (9 bits each, so block shapes and fluid surfaces land exactly),
+/// then the face/normal index (3 bits)
+/// attributes: u, v in sixteenths of a texture, up to 32 of them so merged faces can repeat (9 bits each), then the light (8 bits)
+/// the climate for biome tinting (8 bits, see `biome::pack`) fills the spare bits: its low 6 at the top of attributes and its high 2 at the top of position_face
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct ChunkVertex {
+    position_face: u32,
+    attributes: u32,
+}
+
+impl ChunkVertex {
+    /// `local_position` has to land on a sixteenth of a tile and stay within the bias of the chunk, and `uv` on a sixteenth of a texture
+    pub fn new(local_position: Float4, face: u32, uv: (f32, f32), light: u8) -> Self {
+        let pack_axis = |value: f32| -> u32 {
+            let sixteenths = ((value + CHUNK_VERTEX_BIAS) * 16.0).round();
+            debug_assert!((0.0..512.0).contains(&sixteenths), "Chunk vertex coordinate {} is outside the packable range", value);
+            sixteenths.clamp(0.0, 511.0) as u32
+        };
+        let pack_uv = |value: f32| -> u32 {
+            debug_assert!((0.0..32.0).contains(&value));
+            (value * 16.0).round().clamp(0.0, 511.0) as u32
+        };
+        debug_assert!(face < 8);
+        ChunkVertex {
+            position_face: pack_axis(local_position.x) | pack_axis(local_position.y) << 9 | pack_axis(local_position.z) << 18 | (face & 0x7) << 27,
+            attributes: pack_uv(uv.0) | pack_uv(uv.1) << 9 | (light as u32) << 18,
+        }
+    }
     
-    // adding the triangle linkages
-    let mut triangles = vec![
-        Uint4::new(start_index, start_index + 1, start_index + 3, 4),
-        Uint4::new(start_index, start_index + 2, start_index + 3, 4),
-        
-        Uint4::new(start_index + 4, start_index + 1 + 4, start_index + 3 + 4, 5 | (0 << 16)),
-        Uint4::new(start_index + 4, start_index + 2 + 4, start_index + 3 + 4, 5 | (0 << 16)),
-        
-        Uint4::new(start_index + 8, start_index + 1 + 8, start_index + 3 + 8, 2 | (0 << 16)),
-        Uint4::new(start_index + 8, start_index + 2 + 8, start_index + 3 + 8, 2 | (0 << 16)),
-        
-        Uint4::new(start_index + 12, start_index + 1 + 12, start_index + 3 + 12, 3 | (0 << 16)),
-        Uint4::new(start_index + 12, start_index + 2 + 12, start_index + 3 + 12, 3 | (0 << 16)),
-        
-        Uint4::new(start_index + 16, start_index + 1 + 16, start_index + 3 + 16, 0 | (1 << 16)),
-        Uint4::new(start_index + 16, start_index + 2 + 16, start_index + 3 + 16, 0 | (1 << 16)),
-        
-        Uint4::new(start_index + 20, start_index + 1 + 20, start_index + 3 + 20, 1 | (2 << 16)),
-        Uint4::new(start_index + 20, start_index + 2 + 20, start_index + 3 + 20, 1 | (2 << 16)),
-    ];
-    mesh.append_indices(&mut triangles, chunk_index);
+    /// The same vertex with the climate its tint comes from
+    pub fn with_climate(mut self, climate: u8) -> Self {
+        self.attributes = (self.attributes & 0x03FF_FFFF) | (climate as u32 & 0x3F) << 26;
+        self.position_face = (self.position_face & 0x3FFF_FFFF) | (climate as u32 >> 6) << 30;
+        self
+    }
+    
+    /// The same vertex moved down by `sixteenths` of a tile (for the partial heights of fluids)
+    pub fn lowered(mut self, sixteenths: u32) -> Self {
+        let y = (self.position_face >> 9) & 0x1FF;
+        debug_assert!(sixteenths <= y);
+        self.position_face = (self.position_face & !(0x1FF << 9)) | (y.saturating_sub(sixteenths) & 0x1FF) << 9;
+        self
+    }
+    
+    pub fn local_position(&self) -> Float4 {
+        let unpack_axis = |shift: u32| -> f32 {
+            ((self.position_face >> shift) & 0x1FF) as f32 / 16.0 - CHUNK_VERTEX_BIAS
+        };
+        Float4::new(unpack_axis(0), unpack_axis(9), unpack_axis(18), 0.0)
+    }
+    
+    pub fn position(&self, chunk_origin: Float4) -> Float4 {
+        let local = self.local_position();
+        Float4::new(chunk_origin.x + local.x, chunk_origin.y + local.y, chunk_origin.z + local.z, 0.0)
+    }
+    
+    pub fn uv(&self) -> Float2 {
+        Float2::new((self.attributes & 0x1FF) as f32 / 16.0, ((self.attributes >> 9) & 0x1FF) as f32 / 16.0)
+    }
+    
+    pub fn light(&self) -> u8 {
+        (self.attributes >> 18) as u8
+    }
+    
+    pub fn climate(&self) -> u8 {
+        (self.attributes >> 26) as u8 | ((self.position_face >> 30) as u8) << 6
+    }
+    
+    /// Expands the vertex back into the full format the renderer uses (before any transformation)
+    pub fn unpack(&self, chunk_origin: Float4) -> Vertex {
+        let light = self.light();
+        let (temperature, humidity) = biome::unpack(self.climate());
+        let [red, green, blue] = biome::tint(temperature, humidity);
+        Vertex::new(self.position(chunk_origin), self.uv(), Uchar4::new(light, light, light, 0)).with_tint(Uchar4::new(red, green, blue, 0))
+    }
 }
 
 pub struct Chunk {
     pub position: Float4,
     pub tile_data: [[[u32; 16]; 16]; 16],
-    pub mesh_vert: [Vec<Vertex>; 5],  // 16, 8, 4, 2, 1 wide for each respective lod
-    pub mesh_tris: [Vec<Uint4>; 5],  // 16, 8, 4, 2, 1 wide for each respective lod
+    pub mesh_vert: [Vec<ChunkVertex>; 5],  // 16, 8, 4, 2, 1 wide for each respective lod
+    pub mesh_tris: [Vec<Triangle>; 5],  // 16, 8, 4, 2, 1 wide for each respective lod
     pub chunk_index: usize,  // used for culling in the main mesh
     pub mutated: bool,
+    pub meshed_lods: [bool; 5],  // which of mesh_vert/mesh_tris are up to date with the tiles
+    pub connectivity: ChunkConnectivity,  // which faces can see each other, used for occlusion culling
+    pub climate: [[u8; 17]; 17],  // the packed climate at each corner between columns, [x][z], for tinting (see `biome::Climate::chunk_corners`)
 }
 
 impl Chunk {
@@ -80,6 +137,9 @@ impl Chunk {
             mesh_vert: [vec![], vec![], vec![], vec![], vec![]],
             chunk_index,
             mutated: false,
+            meshed_lods: [false; 5],
+            connectivity: ChunkConnectivity::fully_open(),
+            climate: [[biome::pack(0.5, 0.5); 17]; 17],
         }
     }
     
@@ -93,63 +153,167 @@ impl Chunk {
         let mut triangles = vec![];
         let scale = 16 / scale;  // converting to tile size
         
-        let lighting = Float4::new(1.0, 1.0, 1.0, 0.0);  // todo! implement proper lighting
+        let lighting = 255u8;  // todo! implement proper lighting
+        let block = self.most_common_block_in_region((x, y, z), scale, &[]);
+        let properties = block::properties(block);
+        if scale == 1 && properties.fluid.is_some() {
+            self.remesh_fluid_tile(x, y, z, block, resolution);
+            return;
+        }
+        // further away everything's drawn as cubes
+        if scale == 1 && properties.shape != BlockShape::Cube {
+            self.remesh_shaped_tile(x, y, z, block, resolution);
+            return;
+        }
         let mut start_index = self.mesh_vert[resolution].len() as u32;
-        if x > 0 && self.most_common_block_in_region((x-scale, y, z), scale, &[]) == 0u32 {  // todo! use at some point another metric to determine if a tile is solid
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 - half_size, self.position.y + y as f32 - half_size, self.position.z + z as f32 - half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 - half_size, self.position.y + y as f32 - half_size, self.position.z + z as f32 + half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 - half_size, self.position.y + y as f32 + half_size, self.position.z + z as f32 - half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 - half_size, self.position.y + y as f32 + half_size, self.position.z + z as f32 + half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, lighting));
-            triangles.push(Uint4::new(start_index, start_index + 1, start_index + 3, 3 | (0 << 16)));
-            triangles.push(Uint4::new(start_index, start_index + 2, start_index + 3, 3 | (0 << 16)));
+        for (normal, (dx, dy, dz), corners) in &CUBE_FACES {
+            // only the faces inside the chunk: the negative ones past the first tile, the positive ones before the last
+            let inside = |coordinate: usize, offset: i32| match offset {
+                -1 => coordinate > 0,
+                1 => coordinate < 15,
+                _ => true,
+            };
+            if !(inside(x, *dx) && inside(y, *dy) && inside(z, *dz)) {
+                continue;
+            }
+            let step = |coordinate: usize, offset: i32| (coordinate as i32 + offset * scale as i32) as usize;
+            let neighbour = self.most_common_block_in_region((step(x, *dx), step(y, *dy), step(z, *dz)), scale, &[]);
+            if block::hides_face(neighbour, block, *normal as usize) {
+                continue;
+            }
+            let (texture, rotated) = block::face_texture(block, *normal as usize);
+            for (cx, cy, cz) in corners {
+                let position = Float4::new(x as f32 + cx * half_size * 2.0, y as f32 + cy * half_size * 2.0, z as f32 + cz * half_size * 2.0, 0.0);
+                let uv = face_uv(*normal as usize, [cx + 0.5, cy + 0.5, cz + 0.5], scale as f32, rotated);
+                vertices.push(ChunkVertex::new(position, *normal, uv, lighting));
+            }
+            triangles.push(Triangle::new([start_index, start_index + 1, start_index + 3], *normal as u8, texture).with_flags(properties.face_flags));
+            triangles.push(Triangle::new([start_index, start_index + 2, start_index + 3], *normal as u8, texture).with_flags(properties.face_flags));
             start_index += 4;
         }
-        if y > 0 && self.most_common_block_in_region((x, y-scale, z), scale, &[]) == 0u32 {  // todo! use at some point another metric to determine if a tile is solid
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 - half_size, self.position.y + y as f32 - half_size, self.position.z + z as f32 - half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 - half_size, self.position.y + y as f32 - half_size, self.position.z + z as f32 + half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 + half_size, self.position.y + y as f32 - half_size, self.position.z + z as f32 - half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 + half_size, self.position.y + y as f32 - half_size, self.position.z + z as f32 + half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, lighting));
-            triangles.push(Uint4::new(start_index, start_index + 1, start_index + 3, 1 | (2 << 16)));
-            triangles.push(Uint4::new(start_index, start_index + 2, start_index + 3, 1 | (2 << 16)));
+        self.mesh_vert[resolution].append(&mut vertices);
+        self.mesh_tris[resolution].append(&mut triangles);
+    }
+    
+    /// Meshes a full detail fluid tile, with its surface lowered the further it's flowed from its source
+    fn remesh_fluid_tile(&mut self, x: usize, y: usize, z: usize, cell: u32, resolution: usize) {
+        let properties = block::properties(cell);
+        let lighting = 255u8;
+        let fluid_above = y < 15 && block::fluid(self.tile_data[x][y + 1][z]) == properties.fluid;
+        let drop = fluid::surface_drop(cell, fluid_above);
+        let mut start_index = self.mesh_vert[resolution].len() as u32;
+        for (normal, (dx, dy, dz), corners) in &CUBE_FACES {
+            let (nx, ny, nz) = (x as i32 + dx, y as i32 + dy, z as i32 + dz);
+            if nx < 0 || ny < 0 || nz < 0 || nx > 15 || ny > 15 || nz > 15 {
+                continue;  // ignoring chunk boundaries, the same as `remesh_tile`
+            }
+            let neighbour = self.tile_data[nx as usize][ny as usize][nz as usize];
+            // a lowered surface leaves a gap under whatever's above it, so only more fluid hides the top
+            let hidden = if *dy == 1 { fluid_above } else { block::hides_face(neighbour, cell, *normal as usize) };
+            if hidden {
+                continue;
+            }
+            let (texture, _) = block::face_texture(cell, *normal as usize);
+            for (cx, cy, cz) in corners {
+                let uv = face_uv(*normal as usize, [cx + 0.5, cy + 0.5, cz + 0.5], 1.0, false);
+                let vertex = ChunkVertex::new(Float4::new(x as f32 + cx, y as f32 + cy, z as f32 + cz, 0.0), *normal, uv, lighting);
+                self.mesh_vert[resolution].push(if *cy > 0.0 { vertex.lowered(drop) } else { vertex });
+            }
+            self.mesh_tris[resolution].push(Triangle::new([start_index, start_index + 1, start_index + 3], *normal as u8, texture).with_flags(properties.face_flags));
+            self.mesh_tris[resolution].push(Triangle::new([start_index, start_index + 2, start_index + 3], *normal as u8, texture).with_flags(properties.face_flags));
             start_index += 4;
         }
-        if z > 0 && self.most_common_block_in_region((x, y, z-scale), scale, &[]) == 0u32 {  // todo! use at some point another metric to determine if a tile is solid
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 - half_size, self.position.y + y as f32 - half_size, self.position.z + z as f32 - half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 + half_size, self.position.y + y as f32 - half_size, self.position.z + z as f32 - half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 - half_size, self.position.y + y as f32 + half_size, self.position.z + z as f32 - half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 + half_size, self.position.y + y as f32 + half_size, self.position.z + z as f32 - half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, lighting));
-            triangles.push(Uint4::new(start_index, start_index + 1, start_index + 3, 5 | (0 << 16)));
-            triangles.push(Uint4::new(start_index, start_index + 2, start_index + 3, 5 | (0 << 16)));
-            start_index += 4;
+    }
+    
+    /// The block next to a full detail tile, or `None` past the edge of the chunk
+    fn neighbour_tile(&self, x: usize, y: usize, z: usize, (dx, dy, dz): (i32, i32, i32)) -> Option<u32> {
+        let (nx, ny, nz) = (x as i32 + dx, y as i32 + dy, z as i32 + dz);
+        if nx < 0 || ny < 0 || nz < 0 || nx > 15 || ny > 15 || nz > 15 {
+            return None;
         }
-        if x < 15 && self.most_common_block_in_region((x+scale, y, z), scale, &[]) == 0u32 {  // todo! use at some point another metric to determine if a tile is solid
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 + half_size, self.position.y + y as f32 - half_size, self.position.z + z as f32 - half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 + half_size, self.position.y + y as f32 - half_size, self.position.z + z as f32 + half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 + half_size, self.position.y + y as f32 + half_size, self.position.z + z as f32 - half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 + half_size, self.position.y + y as f32 + half_size, self.position.z + z as f32 + half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, lighting));
-            triangles.push(Uint4::new(start_index, start_index + 1, start_index + 3, 2 | (0 << 16)));
-            triangles.push(Uint4::new(start_index, start_index + 2, start_index + 3, 2 | (0 << 16)));
-            start_index += 4;
+        Some(self.tile_data[nx as usize][ny as usize][nz as usize])
+    }
+    
+    /// Meshes a full detail tile that isn't a plain cube, box by box, leaving out the parts of faces that something covers
+    fn remesh_shaped_tile(&mut self, x: usize, y: usize, z: usize, cell: u32, resolution: usize) {
+        let properties = block::properties(cell);
+        if properties.shape == BlockShape::Cross {
+            self.remesh_cross_tile(x, y, z, cell, resolution);
+            return;
         }
-        if y < 15 && self.most_common_block_in_region((x, y+scale, z), scale, &[]) == 0u32 {  // todo! use at some point another metric to determine if a tile is solid
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 - half_size, self.position.y + y as f32 + half_size, self.position.z + z as f32 - half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 - half_size, self.position.y + y as f32 + half_size, self.position.z + z as f32 + half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 + half_size, self.position.y + y as f32 + half_size, self.position.z + z as f32 - half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 + half_size, self.position.y + y as f32 + half_size, self.position.z + z as f32 + half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, lighting));
-            triangles.push(Uint4::new(start_index, start_index + 1, start_index + 3, 0 | (1 << 16)));
-            triangles.push(Uint4::new(start_index, start_index + 2, start_index + 3, 0 | (1 << 16)));
-            start_index += 4;
+        let lighting = 255u8;
+        // anything past the edge of the chunk is left as air, so fences don't connect across it
+        let horizontal = [(1, 0, 0), (-1, 0, 0), (0, 0, 1), (0, 0, -1)].map(|offset| self.neighbour_tile(x, y, z, offset).unwrap_or(block::AIR));
+        let boxes = block_shape::boxes(cell, horizontal);
+        let mut start_index = self.mesh_vert[resolution].len() as u32;
+        for (box_index, shape_box) in boxes.iter().enumerate() {
+            for (normal, offset, corners) in &CUBE_FACES {
+                let face = *normal as usize;
+                let (axis, u, v) = block_shape::face_axes(face);
+                let hidden = match block_shape::box_face_rect(shape_box, face) {
+                    // on the side of the block, so it's down to the neighbour (ignoring chunk boundaries, the same as `remesh_tile`)
+                    Some(rect) => match self.neighbour_tile(x, y, z, *offset) {
+                        Some(neighbour) => block_shape::face_hidden(cell, face, rect, neighbour),
+                        None => true,
+                    },
+                    // inside the block, so only the block's other boxes can cover it
+                    None => {
+                        let plane = if face.is_multiple_of(2) { shape_box.to[axis] } else { shape_box.from[axis] };
+                        let touching = boxes.iter().enumerate().filter(|(other_index, other)| {
+                            *other_index != box_index && if face.is_multiple_of(2) { other.from[axis] == plane } else { other.to[axis] == plane }
+                        });
+                        let rect = [shape_box.from[u], shape_box.to[u], shape_box.from[v], shape_box.to[v]];
+                        block_shape::rects_cover(touching.map(|(_, other)| [other.from[u], other.to[u], other.from[v], other.to[v]]), rect)
+                    },
+                };
+                if hidden {
+                    continue;
+                }
+                let (texture, rotated) = block::face_texture(cell, face);
+                // the cube's corners pulled in to the box, in sixteenths across the block
+                let pull_in = |axis: usize, corner: f32| if corner > 0.0 { shape_box.to[axis] } else { shape_box.from[axis] } as f32;
+                for corner in corners {
+                    let sixteenths = [pull_in(0, corner.0), pull_in(1, corner.1), pull_in(2, corner.2)];
+                    let uv = face_uv(face, sixteenths.map(|sixteenth| sixteenth / 16.0), 1.0, rotated);
+                    let position = Float4::new(
+                        x as f32 - 0.5 + sixteenths[0] / 16.0,
+                        y as f32 - 0.5 + sixteenths[1] / 16.0,
+                        z as f32 - 0.5 + sixteenths[2] / 16.0,
+                        0.0,
+                    );
+                    self.mesh_vert[resolution].push(ChunkVertex::new(position, *normal, uv, lighting));
+                }
+                self.mesh_tris[resolution].push(Triangle::new([start_index, start_index + 1, start_index + 3], *normal as u8, texture).with_flags(properties.face_flags));
+                self.mesh_tris[resolution].push(Triangle::new([start_index, start_index + 2, start_index + 3], *normal as u8, texture).with_flags(properties.face_flags));
+                start_index += 4;
+            }
         }
-        if z < 15 && self.most_common_block_in_region((x, y, z+scale), scale, &[]) == 0u32 {  // todo! use at some point another metric to determine if a tile is solid
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 - half_size, self.position.y + y as f32 - half_size, self.position.z + z as f32 + half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 + half_size, self.position.y + y as f32 - half_size, self.position.z + z as f32 + half_size, 0.0), Float2 { x: 0.0, y: 1.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 - half_size, self.position.y + y as f32 + half_size, self.position.z + z as f32 + half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, lighting));
-            vertices.push(Vertex::new(Float4::new(self.position.x + x as f32 + half_size, self.position.y + y as f32 + half_size, self.position.z + z as f32 + half_size, 0.0), Float2 { x: 1.0, y: 0.0 }, lighting));
-            triangles.push(Uint4::new(start_index, start_index + 1, start_index + 3, 4));
-            triangles.push(Uint4::new(start_index, start_index + 2, start_index + 3, 4));
+    }
+    
+    /// Meshes a plant as two sprites crossed through the tile, seen from both sides; crops get taller as they grow
+    fn remesh_cross_tile(&mut self, x: usize, y: usize, z: usize, cell: u32, resolution: usize) {
+        let properties = block::properties(cell);
+        let lighting = 255u8;
+        // in sixteenths below the top of the tile, from nearly flat when just planted to full height when ripe
+        let drop = if block::id(cell) == block::WHEAT {
+            let stage = block_state::get(cell, &block_state::AGE).unwrap_or(0).min(block_updates::CROP_STAGES - 1) as f32;
+            ((block_updates::CROP_STAGES as f32 - 1.0 - stage) * 14.0 / (block_updates::CROP_STAGES as f32 - 1.0)).round()
+        } else {
+            0.0
+        };
+        let top = 0.5 - drop / 16.0;
+        let flags = properties.face_flags | TriangleFlags::DOUBLE_SIDED;
+        let mut start_index = self.mesh_vert[resolution].len() as u32;
+        for (from, to) in [((-0.5, -0.5), (0.5, 0.5)), ((-0.5, 0.5), (0.5, -0.5))] {
+            // the top of the texture gets cut off rather than squashed when it's shorter
+            for ((dx, dz), dy, uv) in [(from, -0.5, (0.0, 1.0)), (to, -0.5, (1.0, 1.0)), (from, top, (0.0, drop / 16.0)), (to, top, (1.0, drop / 16.0))] {
+                self.mesh_vert[resolution].push(ChunkVertex::new(Float4::new(x as f32 + dx, y as f32 + dy, z as f32 + dz, 0.0), 0, uv, lighting));
+            }
+            // sprites are lit as if they face up, since they'd look wrong shaded from one side and not the other
+            self.mesh_tris[resolution].push(Triangle::new([start_index, start_index + 1, start_index + 3], 0, properties.textures[2]).with_flags(flags));
+            self.mesh_tris[resolution].push(Triangle::new([start_index, start_index + 2, start_index + 3], 0, properties.textures[2]).with_flags(flags));
+            start_index += 4;
         }
-        self.mesh_vert[resolution].append(&mut vertices);
-        self.mesh_tris[resolution].append(&mut triangles);
     }
     
     pub fn most_common_block_in_region(&self, start: (usize, usize, usize), tile_size: usize, ignored: &[u32]) -> u32 {
@@ -181,8 +345,16 @@ impl Chunk {
     
     pub fn remesh_chunk(&mut self, mesh: &mut Mesh, chunk_priority: usize, resolution: usize) {
         static RES_SCALES: [usize; 5] = [16, 8, 4, 2, 1];
-        if self.mutated || true {
+        let _span = crate::logging::span("meshing", "remesh_chunk");
+        if self.mutated {
+            // only depends on the tiles, not the lod, so it doesn't need to be redone every remesh
+            self.connectivity = ChunkConnectivity::from_tiles(&self.tile_data);
+            self.meshed_lods = [false; 5];
             self.mutated = false;
+        }
+        // chunks nothing changed in just reuse whatever was meshed for this lod last time
+        if !self.meshed_lods[resolution] {
+            self.meshed_lods[resolution] = true;
             self.mesh_tris[resolution].clear();
             self.mesh_vert[resolution].clear();
             let scale = RES_SCALES[resolution];
@@ -195,10 +367,18 @@ impl Chunk {
                     }
                 }
             }
+            // each vertex takes the climate of the nearest corner between columns (the coarse lods overhang the chunk, so they're clamped to its edge)
+            for vertex in &mut self.mesh_vert[resolution] {
+                let position = vertex.local_position();
+                let corner = |coordinate: f32| (coordinate + 0.5).round().clamp(0.0, 16.0) as usize;
+                *vertex = vertex.with_climate(self.climate[corner(position.x)][corner(position.z)]);
+            }
         }
         let start_index = mesh.vertices_original_ref().len() as u32;
         mesh.append_vertices(&mut self.mesh_vert[resolution], chunk_priority, self.chunk_index);
-        mesh.append_indices(&mut self.mesh_tris[resolution].iter().map(|tri| Uint4::new(tri.x + start_index, tri.y + start_index, tri.z + start_index, tri.w)).collect(), self.chunk_index);
+        mesh.append_indices(&mut self.mesh_tris[resolution].iter().map(|tri| tri.offset_indices(start_index)).collect(), self.chunk_index);
+        mesh.set_chunk_connectivity(self.chunk_index, self.connectivity);
+        mesh.set_chunk_lod(self.chunk_index, resolution);
     }
 }
 
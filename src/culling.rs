@@ -0,0 +1,315 @@
+use crate::math::Mat4;
+use crate::shader_handling::Float4;
+
+/// An axis aligned bounding box in world space
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Aabb {
+    pub min: Float4,
+    pub max: Float4,
+}
+
+impl Aabb {
+    pub fn new(min: Float4, max: Float4) -> Self {
+        Aabb { min, max }
+    }
+    
+    /// Creates the bounding box of a chunk from its position and size (the same layout as `Mesh::chunks`)
+    pub fn from_position_size(position: Float4, size: Float4) -> Self {
+        Aabb {
+            min: Float4::new(position.x, position.y, position.z, 0.0),
+            max: Float4::new(position.x + size.x, position.y + size.y, position.z + size.z, 0.0),
+        }
+    }
+    
+    /// An inverted box which any union will replace
+    pub fn empty() -> Self {
+        Aabb {
+            min: Float4::new(f32::MAX, f32::MAX, f32::MAX, 0.0),
+            max: Float4::new(f32::MIN, f32::MIN, f32::MIN, 0.0),
+        }
+    }
+    
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Float4::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z), 0.0),
+            max: Float4::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z), 0.0),
+        }
+    }
+    
+    pub fn center(&self) -> Float4 {
+        Float4::new(
+            (self.min.x + self.max.x) * 0.5,
+            (self.min.y + self.max.y) * 0.5,
+            (self.min.z + self.max.z) * 0.5,
+            0.0,
+        )
+    }
+}
+
+/// The result of testing a volume against the view frustum
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Containment {
+    Outside,
+    Intersecting,
+    Inside,
+}
+
+/// A plane stored as a (unit) normal in xyz and the distance term in w
+/// points with `normal.dot(p) + w >= 0` are on the inside
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Plane {
+    pub equation: Float4,
+}
+
+impl Plane {
+    pub fn new(normal: Float4, distance: f32) -> Self {
+        Plane { equation: Float4::new(normal.x, normal.y, normal.z, distance) }
+    }
+    
+    pub fn signed_distance(&self, point: &Float4) -> f32 {
+        self.equation.dot(point) + self.equation.w
+    }
+}
+
+/// The view frustum as a set of world space planes (left, right, bottom, top, near, far)
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extracts the world space planes from a view-projection matrix (Gribb/Hartmann)
+    /// relies on w being positive in front of the camera, which `Mat4::perspective` guarantees
+    pub fn from_matrix(view_projection: &Mat4) -> Self {
+        let (row_x, row_y, row_z, row_w) = (view_projection.row(0), view_projection.row(1), view_projection.row(2), view_projection.row(3));
+        let equations = [
+            row_w + row_x,
+            row_w - row_x,
+            row_w + row_y,
+            row_w - row_y,
+            row_w + row_z,
+            row_w - row_z,
+        ];
+        let mut planes = [Plane::default(); 6];
+        for (plane, equation) in planes.iter_mut().zip(equations) {
+            let length = equation.xyz().length();
+            *plane = Plane::new((equation.xyz() / length).to_float4(0.0), equation.w / length);
+        }
+        Frustum { planes }
+    }
+    
+    /// Classifies a box against every plane using its most positive and negative corners along each normal
+    pub fn classify_aabb(&self, aabb: &Aabb) -> Containment {
+        let mut result = Containment::Inside;
+        for plane in &self.planes {
+            let n = &plane.equation;
+            let positive = Float4::new(
+                if n.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if n.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if n.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+                0.0,
+            );
+            if plane.signed_distance(&positive) < 0.0 {
+                return Containment::Outside;
+            }
+            let negative = Float4::new(
+                if n.x >= 0.0 { aabb.min.x } else { aabb.max.x },
+                if n.y >= 0.0 { aabb.min.y } else { aabb.max.y },
+                if n.z >= 0.0 { aabb.min.z } else { aabb.max.z },
+                0.0,
+            );
+            if plane.signed_distance(&negative) < 0.0 {
+                result = Containment::Intersecting;
+            }
+        }
+        result
+    }
+}
+
+/// Leaves hold at most this many chunks before being split
+const BVH_LEAF_SIZE: usize = 4;
+
+#[derive(Clone, Debug)]
+struct BvhNode {
+    bounds: Aabb,
+    // the chunks of this node's whole subtree are order[start..end]
+    start: usize,
+    end: usize,
+    // children are only valid for interior nodes
+    left: usize,
+    right: usize,
+    leaf: bool,
+}
+
+/// A bounding volume hierarchy over the chunk bounding boxes of a mesh
+/// large off screen regions get rejected (or fully accepted) by a single test on an interior node
+#[derive(Clone, Debug, Default)]
+pub struct ChunkBvh {
+    nodes: Vec<BvhNode>,
+    order: Vec<usize>,
+    chunk_bounds: Vec<Aabb>,
+}
+
+impl ChunkBvh {
+    pub fn new(chunk_bounds: Vec<Aabb>) -> Self {
+        let mut bvh = ChunkBvh {
+            nodes: Vec::with_capacity(chunk_bounds.len() * 2 / BVH_LEAF_SIZE + 1),
+            order: (0..chunk_bounds.len()).collect(),
+            chunk_bounds,
+        };
+        if !bvh.chunk_bounds.is_empty() {
+            bvh.build_node(0, bvh.order.len());
+        }
+        bvh
+    }
+    
+    /// The number of chunks the hierarchy was built over
+    pub fn chunk_count(&self) -> usize {
+        self.chunk_bounds.len()
+    }
+    
+    fn build_node(&mut self, start: usize, end: usize) -> usize {
+        let mut bounds = Aabb::empty();
+        let mut centroid_bounds = Aabb::empty();
+        for &chunk in &self.order[start..end] {
+            bounds = bounds.union(&self.chunk_bounds[chunk]);
+            let center = self.chunk_bounds[chunk].center();
+            centroid_bounds = centroid_bounds.union(&Aabb::new(center, center));
+        }
+        
+        let node_index = self.nodes.len();
+        self.nodes.push(BvhNode { bounds, start, end, left: 0, right: 0, leaf: true });
+        if end - start <= BVH_LEAF_SIZE {
+            return node_index;
+        }
+        
+        // median split along the longest axis of the centroids
+        let extent = Float4::new(
+            centroid_bounds.max.x - centroid_bounds.min.x,
+            centroid_bounds.max.y - centroid_bounds.min.y,
+            centroid_bounds.max.z - centroid_bounds.min.z,
+            0.0,
+        );
+        let axis_of = |aabb: &Aabb| -> f32 {
+            let center = aabb.center();
+            if extent.x >= extent.y && extent.x >= extent.z { center.x }
+            else if extent.y >= extent.z { center.y }
+            else { center.z }
+        };
+        let middle = (start + end) / 2;
+        let chunk_bounds = &self.chunk_bounds;
+        self.order[start..end].select_nth_unstable_by(middle - start, |a, b| {
+            axis_of(&chunk_bounds[*a]).total_cmp(&axis_of(&chunk_bounds[*b]))
+        });
+        
+        let left = self.build_node(start, middle);
+        let right = self.build_node(middle, end);
+        let node = &mut self.nodes[node_index];
+        node.left = left;
+        node.right = right;
+        node.leaf = false;
+        node_index
+    }
+    
+    /// Writes the frustum culling result of every chunk into `is_chunk_culled` and returns how many were culled
+    pub fn cull(&self, frustum: &Frustum, is_chunk_culled: &mut [bool]) -> usize {
+        let mut culled = 0;
+        if self.nodes.is_empty() {
+            return culled;
+        }
+        let mut stack = vec![0usize];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            let containment = frustum.classify_aabb(&node.bounds);
+            match containment {
+                Containment::Outside | Containment::Inside => {
+                    let is_culled = containment == Containment::Outside;
+                    for &chunk in &self.order[node.start..node.end] {
+                        is_chunk_culled[chunk] = is_culled;
+                    }
+                    if is_culled {
+                        culled += node.end - node.start;
+                    }
+                },
+                Containment::Intersecting if node.leaf => {
+                    for &chunk in &self.order[node.start..node.end] {
+                        let is_culled = frustum.classify_aabb(&self.chunk_bounds[chunk]) == Containment::Outside;
+                        is_chunk_culled[chunk] = is_culled;
+                        culled += is_culled as usize;
+                    }
+                },
+                Containment::Intersecting => {
+                    stack.push(node.left);
+                    stack.push(node.right);
+                },
+            }
+        }
+        culled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{Quat, Vec3};
+    
+    fn block(min: (f32, f32, f32), max: (f32, f32, f32)) -> Aabb {
+        Aabb::new(Float4::new(min.0, min.1, min.2, 0.0), Float4::new(max.0, max.1, max.2, 0.0))
+    }
+    
+    /// Looking down +z from the origin with a 90 degree fov, so at a distance of 10 the view is 20 wide
+    fn frustum() -> Frustum {
+        Frustum::from_matrix(&(Mat4::perspective(90f32.to_radians(), 1.0, 0.1, 100.0) * Mat4::IDENTITY))
+    }
+    
+    #[test]
+    fn boxes_in_front_of_the_camera_are_inside() {
+        assert_eq!(frustum().classify_aabb(&block((-1.0, -1.0, 9.0), (1.0, 1.0, 11.0))), Containment::Inside);
+        assert_eq!(frustum().classify_aabb(&block((-5.0, -5.0, 50.0), (5.0, 5.0, 60.0))), Containment::Inside);
+    }
+    
+    #[test]
+    fn boxes_behind_the_camera_or_off_to_the_side_are_outside() {
+        let frustum = frustum();
+        assert_eq!(frustum.classify_aabb(&block((-1.0, -1.0, -11.0), (1.0, 1.0, -9.0))), Containment::Outside);
+        // wider than the view at its depth would be if it were in front, but it's behind
+        assert_eq!(frustum.classify_aabb(&block((-50.0, -50.0, -20.0), (50.0, 50.0, -10.0))), Containment::Outside);
+        assert_eq!(frustum.classify_aabb(&block((20.0, -1.0, 9.0), (22.0, 1.0, 11.0))), Containment::Outside);
+        assert_eq!(frustum.classify_aabb(&block((-1.0, 20.0, 9.0), (1.0, 22.0, 11.0))), Containment::Outside);
+        assert_eq!(frustum.classify_aabb(&block((-1.0, -1.0, 200.0), (1.0, 1.0, 210.0))), Containment::Outside);
+    }
+    
+    #[test]
+    fn boxes_across_a_plane_are_intersecting() {
+        let frustum = frustum();
+        // across the side planes, the near plane (so partly behind the camera) and the far plane
+        assert_eq!(frustum.classify_aabb(&block((9.0, -1.0, 9.0), (11.0, 1.0, 11.0))), Containment::Intersecting);
+        assert_eq!(frustum.classify_aabb(&block((-1.0, -11.0, 9.0), (1.0, -9.0, 11.0))), Containment::Intersecting);
+        assert_eq!(frustum.classify_aabb(&block((-1.0, -1.0, -1.0), (1.0, 1.0, 1.0))), Containment::Intersecting);
+        assert_eq!(frustum.classify_aabb(&block((-1.0, -1.0, 95.0), (1.0, 1.0, 105.0))), Containment::Intersecting);
+    }
+    
+    #[test]
+    fn the_bvh_culls_the_same_chunks_as_testing_each_one() {
+        let mut chunk_bounds = vec![];
+        for x in -16..16 {
+            for y in 0..2 {
+                for z in -16..16 {
+                    let position = Float4::new(x as f32 * 16.0, y as f32 * 16.0, z as f32 * 16.0, 0.0);
+                    chunk_bounds.push(Aabb::from_position_size(position, Float4::new(16.0, 16.0, 16.0, 0.0)));
+                }
+            }
+        }
+        let bvh = ChunkBvh::new(chunk_bounds.clone());
+        for (eye, pitch, yaw) in [(Vec3::new(0.0, 20.0, 0.0), 0.0, 0.0), (Vec3::new(37.0, 5.0, -80.0), 0.4, 2.1), (Vec3::new(-100.0, 60.0, 90.0), -0.9, -0.7), (Vec3::new(0.0, 120.0, 0.0), -1.5, 0.0)] {
+            let view_projection = Mat4::perspective(70f32.to_radians(), 1.6, 0.1, 200.0) * Mat4::view(eye, Quat::from_euler(pitch, yaw, 0.0));
+            let frustum = Frustum::from_matrix(&view_projection);
+            let mut culled = vec![false; chunk_bounds.len()];
+            let culled_count = bvh.cull(&frustum, &mut culled);
+            let expected: Vec<bool> = chunk_bounds.iter().map(|bounds| frustum.classify_aabb(bounds) == Containment::Outside).collect();
+            assert_eq!(culled, expected, "looking from {:?} at pitch {} and yaw {}", eye, pitch, yaw);
+            assert_eq!(culled_count, expected.iter().filter(|culled| **culled).count());
+            assert!(culled_count > 0 && culled_count < culled.len(), "some chunks should be in view and some out of it");
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::camera::Camera;
+use crate::game_loop::{FrameStats, FRAME_STATS_WINDOW};
+use crate::meshing::RemeshStats;
+
+// the debug overlay (toggled with f3) is drawn on the cpu straight into the finished rgb24 pixels, so it doesn't depend on the renderer at all
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+/// Each font pixel is drawn as a SCALE x SCALE block
+const SCALE: usize = 2;
+const ADVANCE: usize = (GLYPH_WIDTH + 1) * SCALE;
+const LINE_HEIGHT: usize = (GLYPH_HEIGHT + 2) * SCALE;
+const PADDING: usize = 6;
+const GRAPH_HEIGHT: usize = 48;
+/// Frame times at or over this fill the whole graph
+const GRAPH_MAX_MS: f32 = 1000.0 / 30.0;
+
+const TEXT_COLOR: [u8; 3] = [235, 235, 235];
+const BACKGROUND_COLOR: [u8; 3] = [0, 0, 0];
+const BACKGROUND_ALPHA: f32 = 0.6;
+
+/// Everything the overlay shows
+pub struct OverlayInfo<'a> {
+    pub frame_stats: &'a FrameStats,
+    pub camera: &'a Camera,
+    pub remesh: &'a RemeshStats,
+    pub looking_at: &'a str,  // the block under the crosshair, with its state
+    pub selected: &'a str,  // what gets placed on a right click
+    pub biome: &'a str,  // the biome the camera's in and its climate
+}
+
+/// An rgb24 image with rows `pitch` bytes apart and row 0 at the top (the layout of the locked sdl texture)
+pub struct PixelTarget<'a> {
+    pixels: &'a mut [u8],
+    pitch: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> PixelTarget<'a> {
+    pub fn new(pixels: &'a mut [u8], pitch: usize, width: usize, height: usize) -> Self {
+        PixelTarget { pixels, pitch, width, height }
+    }
+    
+    /// Blends `color` over a rectangle, clipped to the image
+    fn blend_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: [u8; 3], alpha: f32) {
+        for row in y..(y + height).min(self.height) {
+            for column in x..(x + width).min(self.width) {
+                let index = row * self.pitch + column * 3;
+                for (pixel, channel) in self.pixels[index..index + 3].iter_mut().zip(color) {
+                    let under = *pixel as f32;
+                    *pixel = (under + (channel as f32 - under) * alpha) as u8;
+                }
+            }
+        }
+    }
+    
+    fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: [u8; 3]) {
+        self.blend_rect(x, y, width, height, color, 1.0);
+    }
+    
+    /// Draws a line of text with its top left at (x, y); lowercase letters are drawn as uppercase
+    fn draw_text(&mut self, x: usize, y: usize, text: &str, color: [u8; 3]) {
+        for (i, character) in text.chars().enumerate() {
+            let glyph = glyph(character.to_ascii_uppercase());
+            let glyph_x = x + i * ADVANCE;
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        self.fill_rect(glyph_x + column * SCALE, y + row * SCALE, SCALE, SCALE, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn milliseconds(duration: std::time::Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
+
+/// Draws the overlay into the top left corner of `target`
+pub fn draw_debug_overlay(target: &mut PixelTarget, info: &OverlayInfo) {
+    let stats = info.frame_stats;
+    let remesh = info.remesh;
+    let position = info.camera.position;
+    let lines = [
+        format!(
+            "fps {:.1}  frame {:.2}ms  99th {:.2}ms  max {:.2}ms",
+            stats.fps(),
+            milliseconds(stats.average_frame_time()),
+            milliseconds(stats.percentile_frame_time(0.99)),
+            milliseconds(stats.max_frame_time()),
+        ),
+        format!(
+            "pos {:.1} {:.1} {:.1}  yaw {:.1}  pitch {:.1}",
+            position.x, position.y, position.z,
+            info.camera.yaw().to_degrees(), info.camera.pitch().to_degrees(),
+        ),
+        format!("chunks {}  culled {}  occluded {}", remesh.chunks, remesh.chunks_culled, remesh.chunks_occluded),
+        format!("triangles {}  drawn {}", remesh.triangles, remesh.triangles_drawn),
+        format!("entities {}  culled {}", remesh.entities, remesh.entities_culled),
+        format!("bins {} of {} used  {} full  most {}", remesh.bins_used, remesh.bins, remesh.bins_full, remesh.max_bin_triangles),
+        format!(
+            "lod 0:{} 1:{} 2:{} 3:{} 4:{}",
+            remesh.lod_counts[0], remesh.lod_counts[1], remesh.lod_counts[2], remesh.lod_counts[3], remesh.lod_counts[4],
+        ),
+        format!("looking at {}", info.looking_at),
+        format!("placing {}", info.selected),
+        format!("biome {}", info.biome),
+    ];
+    
+    let text_width = lines.iter().map(|line| line.len()).max().unwrap_or(0) * ADVANCE;
+    let panel_width = text_width.max(FRAME_STATS_WINDOW) + PADDING * 2;
+    let panel_height = lines.len() * LINE_HEIGHT + GRAPH_HEIGHT + PADDING * 3;
+    target.blend_rect(0, 0, panel_width, panel_height, BACKGROUND_COLOR, BACKGROUND_ALPHA);
+    
+    for (i, line) in lines.iter().enumerate() {
+        target.draw_text(PADDING, PADDING + i * LINE_HEIGHT, line, TEXT_COLOR);
+    }
+    
+    // the frame time graph, newest on the right
+    let graph_top = PADDING * 2 + lines.len() * LINE_HEIGHT;
+    let graph_bottom = graph_top + GRAPH_HEIGHT;
+    let frame_count = stats.frame_times().count();
+    let graph_left = PADDING + FRAME_STATS_WINDOW - frame_count;
+    for (i, frame_time) in stats.frame_times().enumerate() {
+        let frame_ms = milliseconds(frame_time);
+        let height = ((frame_ms / GRAPH_MAX_MS).min(1.0) * GRAPH_HEIGHT as f32).max(1.0) as usize;
+        let color = match frame_ms {
+            ms if ms <= 1000.0 / 60.0 => [80, 220, 80],
+            ms if ms <= 1000.0 / 30.0 => [230, 200, 60],
+            _ => [230, 70, 60],
+        };
+        target.fill_rect(graph_left + i, graph_bottom - height, 1, height, color);
+    }
+    // a marker where 60 fps is
+    let sixty_fps_height = ((1000.0 / 60.0) / GRAPH_MAX_MS * GRAPH_HEIGHT as f32) as usize;
+    target.blend_rect(PADDING, graph_bottom - sixty_fps_height, FRAME_STATS_WINDOW, 1, TEXT_COLOR, 0.5);
+}
+
+/// The rows of a 5x7 glyph, top to bottom, with the leftmost pixel in the highest of the low 5 bits
+/// anything without a glyph is drawn as a question mark
+fn glyph(character: char) -> [u8; GLYPH_HEIGHT] {
+    match character {
+        ' ' => [0; GLYPH_HEIGHT],
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        'A' => [0b01110, 0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        ',' => [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '+' => [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000],
+        '=' => [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000],
+        '/' => [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000],
+        '%' => [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011],
+        '(' => [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010],
+        ')' => [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000],
+        '[' => [0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110],
+        ']' => [0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110],
+        _ => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+    }
+}
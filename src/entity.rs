@@ -0,0 +1,456 @@
+use crate::math::{Mat4, Quat, Vec3};
+use crate::physics::{move_and_collide, BoundingBox};
+use crate::shader_handling::{Float2, Float4, Uchar4, Vertex};
+use crate::triangle::Triangle;
+use crate::world::{BlockPosition, World};
+use crate::pathfinding::{find_path, is_standable, Agent};
+use crate::game_loop::TICKS_PER_SECOND;
+
+// anything that isn't part of the chunk grid (items, mobs, projectiles...)
+// entities get simulated on the tick, then every frame their models get placed into the mesh with `Mesh::set_entities`
+
+/// How many extra normals entity faces can snap to, on top of the six chunk faces (the triangle normal index is only 8 bits)
+pub static ENTITY_NORMAL_COUNT: usize = 250;
+static GRAVITY: f32 = 32.0;
+static TERMINAL_VELOCITY: f32 = 60.0;
+/// How much of its horizontal speed something sliding along the ground keeps per tick
+static GROUND_FRICTION: f32 = 0.6;
+
+pub type EntityId = u64;
+
+/// Ran on every tick before the entity moves, for whatever it does on its own
+pub type UpdateHook = fn(&mut Entity, &World, f32);
+
+/// The geometry of an entity around its origin, which sits at the center of the bottom of its box
+/// `normals` are in model space and get indexed by the triangles' normal
+#[derive(Clone, Default)]
+pub struct Model {
+    pub vertices: Vec<Vertex>,
+    pub triangles: Vec<Triangle>,
+    pub normals: Vec<Vec3>,
+}
+
+impl Model {
+    /// A box standing on the origin, with `textures` being the top, bottom and sides
+    pub fn cuboid(size: Vec3, textures: [u16; 3]) -> Self {
+        let half = Vec3::new(size.x * 0.5, size.y, size.z * 0.5);
+        let corner = |x: f32, y: f32, z: f32| Vec3::new(x * half.x, y * half.y, z * half.z);
+        // each face's corners go (0, 0), (1, 0), (0, 1), (1, 1) in uv
+        let faces = [
+            (Vec3::Y, [corner(-1.0, 1.0, -1.0), corner(1.0, 1.0, -1.0), corner(-1.0, 1.0, 1.0), corner(1.0, 1.0, 1.0)], textures[0]),
+            (Vec3::new(0.0, -1.0, 0.0), [corner(-1.0, 0.0, -1.0), corner(1.0, 0.0, -1.0), corner(-1.0, 0.0, 1.0), corner(1.0, 0.0, 1.0)], textures[1]),
+            (Vec3::X, [corner(1.0, 0.0, -1.0), corner(1.0, 0.0, 1.0), corner(1.0, 1.0, -1.0), corner(1.0, 1.0, 1.0)], textures[2]),
+            (Vec3::new(-1.0, 0.0, 0.0), [corner(-1.0, 0.0, 1.0), corner(-1.0, 0.0, -1.0), corner(-1.0, 1.0, 1.0), corner(-1.0, 1.0, -1.0)], textures[2]),
+            (Vec3::Z, [corner(1.0, 0.0, 1.0), corner(-1.0, 0.0, 1.0), corner(1.0, 1.0, 1.0), corner(-1.0, 1.0, 1.0)], textures[2]),
+            (Vec3::new(0.0, 0.0, -1.0), [corner(-1.0, 0.0, -1.0), corner(1.0, 0.0, -1.0), corner(-1.0, 1.0, -1.0), corner(1.0, 1.0, -1.0)], textures[2]),
+        ];
+        let mut model = Model::default();
+        for (normal, corners, texture) in faces {
+            let start = model.vertices.len() as u32;
+            for (corner, uv) in corners.iter().zip([(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)]) {
+                model.vertices.push(Vertex::new(corner.to_float4(0.0), Float2::new(uv.0, uv.1), Uchar4::new(255, 255, 255, 0)));
+            }
+            let normal_index = model.normals.len() as u8;
+            model.normals.push(normal);
+            model.triangles.push(Triangle::new([start, start + 1, start + 3], normal_index, texture));
+            model.triangles.push(Triangle::new([start, start + 2, start + 3], normal_index, texture));
+        }
+        model
+    }
+}
+
+/// Directions spread evenly over the sphere (a fibonacci spiral), which rotated entity faces get snapped to
+pub fn entity_normals() -> Vec<Float4> {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5f32.sqrt());
+    (0..ENTITY_NORMAL_COUNT).map(|i| {
+        let y = 1.0 - (i as f32 + 0.5) / ENTITY_NORMAL_COUNT as f32 * 2.0;
+        let radius = (1.0 - y * y).sqrt();
+        let angle = golden_angle * i as f32;
+        Float4::new(angle.cos() * radius, y, angle.sin() * radius, 0.0)
+    }).collect()
+}
+
+/// A model placed in the world for one frame, handed to `Mesh::set_entities`
+#[derive(Clone)]
+pub struct EntityInstance {
+    pub model: std::sync::Arc<Model>,
+    pub transform: Mat4,  // model to world space
+}
+
+pub struct Entity {
+    pub id: EntityId,  // handed out by `Entities::spawn`
+    pub position: Vec3,  // the center of the bottom of its box
+    pub previous_position: Vec3,  // as of the tick before, for drawing it between ticks
+    pub velocity: Vec3,
+    pub rotation: Quat,
+    pub previous_rotation: Quat,
+    pub width: f32,
+    pub height: f32,
+    pub model: std::sync::Arc<Model>,
+    pub update: Option<UpdateHook>,
+    pub physics: bool,  // falls and collides with blocks, rather than just moving by its velocity
+    pub on_ground: bool,
+    pub collided: bool,  // walked or flew into the side of something on the last tick
+    pub age: u32,  // in ticks
+    pub removed: bool,  // despawned at the end of the tick
+    pub path: Vec<BlockPosition>,  // where it's still walking to, nearest first (see `wander_update`)
+}
+
+impl Entity {
+    pub fn new(position: Vec3, width: f32, height: f32, model: std::sync::Arc<Model>) -> Self {
+        Entity {
+            id: 0,
+            position,
+            previous_position: position,
+            velocity: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            previous_rotation: Quat::IDENTITY,
+            width,
+            height,
+            model,
+            update: None,
+            physics: true,
+            on_ground: false,
+            collided: false,
+            age: 0,
+            removed: false,
+            path: vec![],
+        }
+    }
+    
+    pub fn with_velocity(mut self, velocity: Vec3) -> Self {
+        self.velocity = velocity;
+        self
+    }
+    
+    pub fn with_update(mut self, update: UpdateHook) -> Self {
+        self.update = Some(update);
+        self
+    }
+    
+    pub fn bounds(&self) -> BoundingBox {
+        BoundingBox::from_base(self.position, self.width, self.height)
+    }
+    
+    /// One simulation tick: the update hook, then gravity and collision (if it has physics)
+    pub fn tick(&mut self, world: &World, delta_time: f32) {
+        self.previous_position = self.position;
+        self.previous_rotation = self.rotation;
+        if let Some(update) = self.update {
+            update(self, world, delta_time);
+        }
+        if self.physics {
+            self.velocity.y = (self.velocity.y - GRAVITY * delta_time).max(-TERMINAL_VELOCITY);
+            let result = move_and_collide(world, self.bounds(), self.velocity * delta_time, 0.0);
+            self.position += result.offset;
+            self.on_ground = result.on_ground;
+            self.collided = result.blocked[0] || result.blocked[2];
+            if result.blocked[0] {
+                self.velocity.x = 0.0;
+            }
+            if result.blocked[1] {
+                self.velocity.y = 0.0;
+            }
+            if result.blocked[2] {
+                self.velocity.z = 0.0;
+            }
+            if self.on_ground {
+                self.velocity.x *= GROUND_FRICTION;
+                self.velocity.z *= GROUND_FRICTION;
+            }
+        } else {
+            self.position += self.velocity * delta_time;
+        }
+        self.age += 1;
+    }
+    
+    /// Where to draw it, `alpha` of the way from the last tick to this one
+    pub fn instance(&self, alpha: f32) -> EntityInstance {
+        let position = self.previous_position.lerp(self.position, alpha);
+        let rotation = self.previous_rotation.slerp(self.rotation, alpha);
+        EntityInstance {
+            model: self.model.clone(),
+            transform: Mat4::translation(position) * Mat4::from_quat(rotation),
+        }
+    }
+}
+
+/// Every live entity
+pub struct Entities {
+    entities: Vec<Entity>,
+    next_id: EntityId,
+}
+
+impl Default for Entities {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Entities {
+    /// Ids start at 1, since 0 is what an entity has before it's spawned
+    pub fn new() -> Self {
+        Entities { entities: vec![], next_id: 1 }
+    }
+    
+    pub fn spawn(&mut self, mut entity: Entity) -> EntityId {
+        entity.id = self.next_id;
+        self.next_id += 1;
+        self.entities.push(entity);
+        self.next_id - 1
+    }
+    
+    /// Ticks every entity, then drops the ones that got removed; returns how many were (so whether the drawn ones changed)
+    pub fn tick(&mut self, world: &World, delta_time: f32) -> usize {
+        for entity in &mut self.entities {
+            entity.tick(world, delta_time);
+        }
+        let count = self.entities.len();
+        self.entities.retain(|entity| !entity.removed);
+        count - self.entities.len()
+    }
+    
+    /// Whether anything moved or turned on the last tick (so the drawn positions change between ticks)
+    pub fn moving(&self) -> bool {
+        self.entities.iter().any(|entity| entity.position != entity.previous_position || entity.rotation != entity.previous_rotation)
+    }
+    
+    pub fn instances(&self, alpha: f32) -> Vec<EntityInstance> {
+        self.entities.iter().map(|entity| entity.instance(alpha)).collect()
+    }
+}
+
+/// Dropped items spin in place and despawn after five minutes
+pub fn item_update(entity: &mut Entity, _world: &World, delta_time: f32) {
+    entity.rotation = (Quat::from_axis_angle(Vec3::Y, 1.5 * delta_time) * entity.rotation).normalized();
+    if entity.age > TICKS_PER_SECOND * 300 {
+        entity.removed = true;
+    }
+}
+
+/// Mobs wander to a random spot nearby every few seconds, walking there along a path and hopping up blocks on the way
+pub fn wander_update(entity: &mut Entity, world: &World, _delta_time: f32) {
+    static WANDER_SPEED: f32 = 2.0;
+    static HOP_SPEED: f32 = 9.0;  // the same as the player jumps, which only just clears a block
+    static WANDER_RANGE: i32 = 8;
+    static MAX_SEARCH: usize = 500;
+    // the goal can only be picked from the ground, since paths start where its feet are
+    let feet = BlockPosition::containing(entity.position + Vec3::new(0.0, 0.01, 0.0));
+    if entity.age.is_multiple_of(TICKS_PER_SECOND * 3) && entity.on_ground {
+        let agent = Agent { height: entity.height.ceil() as i32, ..Agent::player_sized() };
+        let column = (feet.x + rand::random_range(-WANDER_RANGE..=WANDER_RANGE), feet.z + rand::random_range(-WANDER_RANGE..=WANDER_RANGE));
+        let goal = (-3..=3).rev()
+            .map(|dy| BlockPosition::new(column.0, feet.y + dy, column.1))
+            .find(|position| is_standable(world, *position, &agent));
+        entity.path.clear();
+        match goal.map(|goal| find_path(world, feet, goal, &agent, MAX_SEARCH)) {
+            Some(Ok(path)) => {
+                log_trace!("Entity {} wandering {} blocks (cost {}, searched {} nodes)", entity.id, path.positions.len() - 1, path.cost, path.explored);
+                entity.path.extend(path.positions.into_iter().skip(1));
+            }
+            Some(Err(reason)) => log_trace!("Entity {} couldn't wander: {}", entity.id, reason),
+            None => {}
+        }
+    }
+    follow_path(entity, feet, WANDER_SPEED, HOP_SPEED);
+}
+
+/// Walks toward the next block of the entity's path (dropping the ones it's reached), or stands still without one
+fn follow_path(entity: &mut Entity, feet: BlockPosition, speed: f32, hop_speed: f32) {
+    let across = |entity: &Entity, to: BlockPosition| Vec3::new(to.x as f32 - entity.position.x, 0.0, to.z as f32 - entity.position.z);
+    while let Some(next) = entity.path.first()
+        && across(entity, *next).length() < 0.2 {
+        entity.path.remove(0);
+    }
+    let Some(next) = entity.path.first().copied() else {
+        entity.velocity.x = 0.0;
+        entity.velocity.z = 0.0;
+        return;
+    };
+    let direction = across(entity, next).normalized();
+    entity.rotation = Quat::from_axis_angle(Vec3::Y, -direction.z.atan2(direction.x));
+    entity.velocity.x = direction.x * speed;
+    entity.velocity.z = direction.z * speed;
+    if entity.on_ground && (next.y > feet.y || entity.collided) {
+        entity.velocity.y = hop_speed;
+    }
+}
+
+/// Projectiles fly until they hit something (or after ten seconds), then disappear
+pub fn projectile_update(entity: &mut Entity, _world: &World, _delta_time: f32) {
+    if entity.collided || entity.on_ground || entity.age > TICKS_PER_SECOND * 10 {
+        entity.removed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block;
+    use crate::world::BlockPosition;
+    
+    const TICK: f32 = 1.0 / TICKS_PER_SECOND as f32;
+    
+    fn cube_entity(position: Vec3) -> Entity {
+        Entity::new(position, 0.5, 0.5, std::sync::Arc::new(Model::cuboid(Vec3::splat(0.5), [0, 0, 0])))
+    }
+    
+    fn assert_vec3(got: Vec3, expected: Vec3) {
+        assert!((got - expected).length() < 1e-4, "expected {:?}, got {:?}", expected, got);
+    }
+    
+    #[test]
+    fn entities_fall_onto_the_ground() {
+        let world = World::test_floor(block::STONE);
+        let mut entity = cube_entity(Vec3::new(4.0, 5.0, 8.0));
+        for _ in 0..60 {
+            entity.tick(&world, TICK);
+        }
+        assert!(entity.on_ground, "not on the ground after falling");
+        assert!((entity.position.y - 0.5).abs() < 0.01, "came to rest at y = {}", entity.position.y);
+        assert_eq!(entity.velocity.y, 0.0);
+        assert_eq!(entity.age, 60);
+    }
+    
+    #[test]
+    fn the_ground_slows_sliding_entities() {
+        let world = World::test_floor(block::STONE);
+        let mut entity = cube_entity(Vec3::new(4.0, 0.5, 8.0)).with_velocity(Vec3::new(4.0, 0.0, 0.0));
+        entity.tick(&world, TICK);
+        assert!(entity.on_ground);
+        assert_vec3(entity.position, Vec3::new(4.0 + 4.0 * TICK, 0.5, 8.0));
+        assert!((entity.velocity.x - 4.0 * GROUND_FRICTION).abs() < 1e-5, "kept {} of its speed", entity.velocity.x);
+        
+        // and don't get slowed in the air
+        let mut flying = cube_entity(Vec3::new(4.0, 8.0, 8.0)).with_velocity(Vec3::new(4.0, 0.0, 0.0));
+        flying.tick(&world, TICK);
+        assert_eq!(flying.velocity.x, 4.0);
+    }
+    
+    #[test]
+    fn entities_without_physics_just_move_by_their_velocity() {
+        let world = World::test_floor(block::STONE);
+        let mut entity = cube_entity(Vec3::new(4.0, 0.0, 8.0)).with_velocity(Vec3::new(1.0, 2.0, -3.0));
+        entity.physics = false;
+        entity.tick(&world, TICK);
+        assert_vec3(entity.position, Vec3::new(4.0 + TICK, 2.0 * TICK, 8.0 - 3.0 * TICK));
+        assert_vec3(entity.previous_position, Vec3::new(4.0, 0.0, 8.0));
+        assert_eq!(entity.velocity, Vec3::new(1.0, 2.0, -3.0), "gravity shouldn't apply");
+    }
+    
+    #[test]
+    fn ids_start_at_one() {
+        for mut entities in [Entities::new(), Entities::default()] {
+            assert_eq!(entities.spawn(cube_entity(Vec3::ZERO)), 1);
+            assert_eq!(entities.spawn(cube_entity(Vec3::ZERO)), 2);
+        }
+    }
+    
+    #[test]
+    fn removed_entities_despawn_at_the_end_of_the_tick() {
+        let world = World::test_floor(block::STONE);
+        let mut entities = Entities::new();
+        entities.spawn(cube_entity(Vec3::new(4.0, 0.5, 8.0)));
+        entities.spawn(cube_entity(Vec3::new(6.0, 0.5, 8.0)).with_update(|entity, _, _| entity.removed = true));
+        assert_eq!(entities.tick(&world, TICK), 1);
+        assert_eq!(entities.instances(1.0).len(), 1);
+        assert_eq!(entities.tick(&world, TICK), 0);
+    }
+    
+    #[test]
+    fn items_despawn_after_five_minutes() {
+        let world = World::test_floor(block::STONE);
+        let mut entities = Entities::new();
+        entities.spawn(cube_entity(Vec3::new(4.0, 0.5, 8.0)).with_update(item_update));
+        let lifetime = TICKS_PER_SECOND * 300;
+        let despawned: usize = (0..=lifetime).map(|_| entities.tick(&world, TICK)).sum();
+        assert_eq!(despawned, 0, "despawned before its five minutes were up");
+        assert_eq!(entities.tick(&world, TICK), 1);
+    }
+    
+    #[test]
+    fn projectiles_despawn_when_they_hit_something_or_time_out() {
+        let mut world = World::test_floor(block::STONE);
+        for y in 1..4 {
+            for z in 0..16 {
+                world.set_block(BlockPosition::new(10, y, z), block::STONE);
+            }
+        }
+        let mut entities = Entities::new();
+        entities.spawn(cube_entity(Vec3::new(4.0, 1.5, 8.0)).with_velocity(Vec3::new(30.0, 0.0, 0.0)).with_update(projectile_update));
+        let ticks = (1..20).find(|_| entities.tick(&world, TICK) == 1);
+        assert!(ticks.is_some(), "a projectile fired at a wall didn't despawn");
+        
+        // with nothing to hit it falls forever, until it times out
+        let world = World::test_floor(block::AIR);
+        let mut entities = Entities::new();
+        entities.spawn(cube_entity(Vec3::new(4.0, 1.5, 8.0)).with_velocity(Vec3::new(0.0, 0.0, 1.0)).with_update(projectile_update));
+        let despawned: usize = (0..=TICKS_PER_SECOND * 10).map(|_| entities.tick(&world, TICK)).sum();
+        assert_eq!(despawned, 0, "despawned before it timed out");
+        assert_eq!(entities.tick(&world, TICK), 1);
+    }
+    
+    #[test]
+    fn only_moving_or_turning_counts_as_moving() {
+        let world = World::test_floor(block::STONE);
+        let mut entities = Entities::new();
+        entities.spawn(cube_entity(Vec3::new(4.0, 0.5, 8.0)));
+        entities.tick(&world, TICK);
+        assert!(!entities.moving(), "an entity resting on the ground counted as moving");
+        
+        entities.spawn(cube_entity(Vec3::new(8.0, 0.5, 8.0)).with_update(|entity, _, delta_time| {
+            entity.rotation = (Quat::from_axis_angle(Vec3::Y, delta_time) * entity.rotation).normalized();
+        }));
+        entities.tick(&world, TICK);
+        assert!(entities.moving(), "a turning entity didn't count as moving");
+    }
+    
+    #[test]
+    fn instances_are_drawn_between_ticks() {
+        let mut entity = cube_entity(Vec3::new(2.0, 1.0, 0.0));
+        entity.previous_position = Vec3::new(0.0, 1.0, 0.0);
+        entity.rotation = Quat::from_axis_angle(Vec3::Y, std::f32::consts::FRAC_PI_2);
+        for (alpha, position, angle) in [(0.0, 0.0, 0.0), (0.5, 1.0, std::f32::consts::FRAC_PI_4), (1.0, 2.0, std::f32::consts::FRAC_PI_2)] {
+            let transform = entity.instance(alpha).transform;
+            assert_vec3(transform.transform_point(Vec3::ZERO), Vec3::new(position, 1.0, 0.0));
+            let expected = Vec3::new(position, 1.0, 0.0) + Quat::from_axis_angle(Vec3::Y, angle).rotate(Vec3::X);
+            assert_vec3(transform.transform_point(Vec3::X), expected);
+        }
+    }
+    
+    #[test]
+    fn mobs_wander_to_somewhere_they_can_stand() {
+        let world = World::test_floor(block::STONE);
+        let mut mob = cube_entity(Vec3::new(4.0, 0.5, 8.0));
+        mob.on_ground = true;
+        let mut wandered = false;
+        for _ in 0..20 {
+            wander_update(&mut mob, &world, TICK);
+            if let Some(goal) = mob.path.last() {
+                assert!(is_standable(&world, *goal, &Agent::player_sized()), "wandering to {:?}, which isn't standable", goal);
+                assert!((goal.x - 4).abs() <= 8 && (goal.z - 8).abs() <= 8, "wandering to {:?}, which is out of range", goal);
+                wandered = true;
+            }
+        }
+        assert!(wandered, "never picked anywhere to wander to");
+    }
+
+    #[test]
+    fn mobs_walk_along_their_path_and_hop_up_blocks() {
+        let mut world = World::test_floor(block::STONE);
+        for x in 6..10 {
+            world.set_block(BlockPosition::new(x, 1, 8), block::STONE);
+        }
+        let mut mob = cube_entity(Vec3::new(3.0, 0.5, 8.0)).with_update(wander_update);
+        let goal = BlockPosition::new(8, 2, 8);
+        mob.path = find_path(&world, BlockPosition::new(3, 1, 8), goal, &Agent::player_sized(), 1000).unwrap().positions;
+        mob.age = 1;  // so it doesn't pick somewhere else for another three seconds
+        mob.tick(&world, TICK);
+        assert_vec3(mob.rotation.rotate(Vec3::X), Vec3::X);
+        for _ in 2..TICKS_PER_SECOND * 3 {
+            mob.tick(&world, TICK);
+        }
+        assert!(mob.path.is_empty(), "still had {:?} to go", mob.path);
+        assert!((mob.position - Vec3::new(8.0, 1.5, 8.0)).length() < 0.25, "ended up at {:?}", mob.position);
+    }
+}
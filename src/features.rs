@@ -0,0 +1,401 @@
+use crate::biome::Climate;
+use crate::block::{self, BlockId};
+use crate::block_state;
+use crate::world::{BlockPosition, World, CHUNK_SIZE};
+use rand::{Rng, SeedableRng};
+
+// the feature stage of world generation: once a chunk's terrain is down, ore veins, boulders, trees and prefab structures get placed on it
+// each chunk's features come from a generator seeded by the world seed and the chunk's coordinates, so a seed always places the same ones
+// features can reach into the chunks around them; writes into chunks that haven't been generated yet are queued up and land right after their terrain
+
+/// An ore, the deepest-first veins it forms, and how many of them each chunk gets
+struct OreVein {
+    block: BlockId,
+    veins: usize,
+    size: usize,  // how many blocks each vein wanders through
+    max_y: i32,  // veins start at or below this
+}
+
+static ORES: [OreVein; 3] = [
+    OreVein { block: block::COAL_ORE, veins: 4, size: 8, max_y: 9 },
+    OreVein { block: block::IRON_ORE, veins: 2, size: 6, max_y: 6 },
+    OreVein { block: block::GOLD_ORE, veins: 1, size: 4, max_y: 3 },
+];
+/// The chance a chunk gets a boulder
+static BOULDER_CHANCE: f64 = 0.15;
+
+/// What a feature's allowed to overwrite
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Replace {
+    /// air and plants (anything that isn't solid and isn't a fluid)
+    Open,
+    /// the same plus leaves, so trunks can grow through another tree's canopy
+    Foliage,
+    /// only stone, for ores
+    Stone,
+    /// everything, for structures (which say where they want air)
+    Any,
+}
+
+impl Replace {
+    fn allows(self, cell: u32) -> bool {
+        let open = !block::is_solid(cell) && block::fluid(cell).is_none();
+        match self {
+            Replace::Open => open,
+            Replace::Foliage => open || block::id(cell) == block::LEAVES,
+            Replace::Stone => block::id(cell) == block::STONE,
+            Replace::Any => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TreeShape {
+    /// a short trunk under a round canopy
+    Oak,
+    /// a taller trunk with a canopy narrowing to a point, for cold places
+    Pine,
+    /// a stump in a ball of leaves, for dry places
+    Bush,
+}
+
+#[derive(serde::Deserialize)]
+struct StructureFile {
+    chance: f64,
+    #[serde(default)]
+    sink: i32,
+    palette: std::collections::HashMap<char, String>,
+    layers: Vec<Vec<String>>,
+}
+
+/// A prefab loaded from a `.json` file by `load_structures`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Structure {
+    pub name: String,
+    pub chance: f64,  // of a chunk getting one
+    pub sink: i32,  // how many layers go below the ground
+    pub blocks: Vec<((i32, i32, i32), u32)>,  // from the structure's lowest corner; spaces in the layers leave whatever was there and aren't listed
+}
+
+/// Loads every `.json` structure in `directory`; ones that fail to load are logged and left out
+/// each file is a palette of characters to blocks (with state, like `cobblestone stairs[facing=north]`) and the layers bottom to top,
+/// each layer being rows along z of characters along x
+pub fn load_structures(directory: &str) -> Result<Vec<Structure>, String> {
+    let entries = std::fs::read_dir(directory).map_err(|e| format!("Failed to read the structure directory '{}': {}", directory, e))?;
+    let mut structures = vec![];
+    for entry in entries {
+        let path = entry.map_err(|e| format!("Failed to read the structure directory '{}': {}", directory, e))?.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        match load_structure(&path) {
+            Ok(structure) => structures.push(structure),
+            Err(error) => log_warn!("Skipping the structure '{}': {}", path.display(), error),
+        }
+    }
+    // sorted so the placement order (and so the world) doesn't depend on the order the directory gets listed in
+    structures.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(structures)
+}
+
+fn load_structure(path: &std::path::Path) -> Result<Structure, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let file: StructureFile = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    if !(0.0..=1.0).contains(&file.chance) {
+        return Err(format!("The chance of a structure has to be between 0 and 1, got {}", file.chance));
+    }
+    let palette = file.palette.iter()
+        .map(|(character, text)| Ok((*character, block_state::parse(text)?)))
+        .collect::<Result<std::collections::HashMap<char, u32>, String>>()?;
+    let mut blocks = vec![];
+    for (y, layer) in file.layers.iter().enumerate() {
+        for (z, row) in layer.iter().enumerate() {
+            for (x, character) in row.chars().enumerate() {
+                if character == ' ' {
+                    continue;
+                }
+                let cell = palette.get(&character).ok_or_else(|| format!("'{}' in layer {} isn't in the palette", character, y))?;
+                blocks.push(((x as i32, y as i32, z as i32), *cell));
+            }
+        }
+    }
+    let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    Ok(Structure { name, chance: file.chance, sink: file.sink, blocks })
+}
+
+/// Writes waiting on the chunk they're in to be generated, by chunk
+type QueuedWrites = std::collections::HashMap<(i32, i32, i32), Vec<(BlockPosition, u32, Replace)>>;
+
+/// Places the features of each chunk as it's generated, holding on to whatever they wrote into chunks that aren't generated yet
+pub struct Features {
+    seed: u64,
+    climate: Climate,
+    structures: Vec<Structure>,
+    queued: QueuedWrites,
+}
+
+impl Features {
+    pub fn new(seed: u64, structures: Vec<Structure>) -> Self {
+        Features { seed, climate: Climate::new(seed), structures, queued: std::collections::HashMap::new() }
+    }
+    
+    /// How many writes are still waiting on their chunks to be generated
+    pub fn queued_writes(&self) -> usize {
+        self.queued.values().map(Vec::len).sum()
+    }
+    
+    /// Sets a block if it's allowed to replace what's there, or queues it up if its chunk hasn't been generated yet
+    pub fn set(&mut self, world: &mut World, position: BlockPosition, cell: u32, replace: Replace) {
+        if !world.is_loaded(position) {
+            self.queued.entry(position.split().0).or_default().push((position, cell, replace));
+            return;
+        }
+        if replace.allows(world.block(position)) {
+            world.set_block_unmarked(position, cell);
+        }
+    }
+    
+    /// Runs the feature stage for a chunk that was just added to `world` with its terrain:
+    /// first whatever its neighbours queued for it, then its own ores, boulders, trees and structures
+    pub fn place(&mut self, world: &mut World, chunk: (i32, i32, i32)) {
+        for (position, cell, replace) in self.queued.remove(&chunk).unwrap_or_default() {
+            self.set(world, position, cell, replace);
+        }
+        let hash = (chunk.0 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ (chunk.1 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F) ^ (chunk.2 as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+        let mut random = rand::rngs::StdRng::seed_from_u64(self.seed ^ hash);
+        let origin = BlockPosition::new(chunk.0 * CHUNK_SIZE, chunk.1 * CHUNK_SIZE, chunk.2 * CHUNK_SIZE);
+        let column = |random: &mut rand::rngs::StdRng| (origin.x + random.random_range(0..CHUNK_SIZE), origin.z + random.random_range(0..CHUNK_SIZE));
+        
+        // ores wander through the stone from somewhere below their depth
+        for ore in &ORES {
+            for _ in 0..ore.veins {
+                let (x, z) = column(&mut random);
+                let mut position = BlockPosition::new(x, origin.y + random.random_range(0..=ore.max_y), z);
+                for _ in 0..ore.size {
+                    self.set(world, position, ore.block, Replace::Stone);
+                    let step = if random.random_bool(0.5) { 1 } else { -1 };
+                    position = match random.random_range(0..3) {
+                        0 => BlockPosition::new(position.x + step, position.y, position.z),
+                        1 => BlockPosition::new(position.x, position.y + step, position.z),
+                        _ => BlockPosition::new(position.x, position.y, position.z + step),
+                    };
+                }
+            }
+        }
+        
+        if random.random_bool(BOULDER_CHANCE) {
+            let (x, z) = column(&mut random);
+            if let Some(ground) = surface(world, x, z, origin.y) {
+                self.place_boulder(world, &mut random, ground);
+            }
+        }
+        
+        // more trees the wetter it is, and their shape depends on the climate
+        let (temperature, humidity) = self.climate.at(origin.x + CHUNK_SIZE / 2, origin.z + CHUNK_SIZE / 2);
+        let trees = (1.0 + humidity * 3.0 + random.random::<f32>()) as usize;
+        for _ in 0..trees {
+            let (x, z) = column(&mut random);
+            let Some(ground) = surface(world, x, z, origin.y) else {
+                continue;
+            };
+            if block::id(world.block(ground)) != block::GRASS {
+                continue;
+            }
+            let shape = if temperature < 0.3 {
+                TreeShape::Pine
+            } else if humidity < 0.3 {
+                TreeShape::Bush
+            } else {
+                TreeShape::Oak
+            };
+            self.place_tree(world, &mut random, ground.above(), shape);
+        }
+        
+        for index in 0..self.structures.len() {
+            if !random.random_bool(self.structures[index].chance) {
+                continue;
+            }
+            let (x, z) = column(&mut random);
+            let Some(ground) = surface(world, x, z, origin.y) else {
+                continue;
+            };
+            let base = BlockPosition::new(ground.x, ground.y + 1 - self.structures[index].sink, ground.z);
+            for block_index in 0..self.structures[index].blocks.len() {
+                let ((x, y, z), cell) = self.structures[index].blocks[block_index];
+                self.set(world, BlockPosition::new(base.x + x, base.y + y, base.z + z), cell, Replace::Any);
+            }
+        }
+    }
+    
+    /// A lumpy ball of stone and cobblestone half sunk into the ground
+    fn place_boulder(&mut self, world: &mut World, random: &mut rand::rngs::StdRng, ground: BlockPosition) {
+        let radius = random.random_range(1..=2);
+        for dx in -radius..=radius {
+            for dy in -radius..=radius {
+                for dz in -radius..=radius {
+                    // squashed a little so it sits rather than balances
+                    let distance = (dx * dx * 2 + dy * dy * 3 + dz * dz * 2) as f32 / 2.0;
+                    if distance > (radius * radius) as f32 + random.random::<f32>() {
+                        continue;
+                    }
+                    let cell = if random.random_bool(0.3) { block::STONE } else { block::COBBLESTONE };
+                    self.set(world, BlockPosition::new(ground.x + dx, ground.y + dy, ground.z + dz), cell, Replace::Open);
+                }
+            }
+        }
+    }
+    
+    /// Grows a tree up from `base` (the block above the ground)
+    fn place_tree(&mut self, world: &mut World, random: &mut rand::rngs::StdRng, base: BlockPosition, shape: TreeShape) {
+        let trunk = match shape {
+            TreeShape::Oak => random.random_range(3..=4),
+            TreeShape::Pine => random.random_range(4..=5),
+            TreeShape::Bush => 1,
+        };
+        let top = base.y + trunk - 1;
+        // the canopy as the radius of leaves at each height, relative to the top of the trunk
+        let canopy: &[(i32, i32)] = match shape {
+            TreeShape::Oak => &[(-1, 2), (0, 2), (1, 1)],
+            TreeShape::Pine => &[(-3, 2), (-2, 1), (-1, 2), (0, 1), (1, 0)],
+            TreeShape::Bush => &[(0, 1), (1, 0)],
+        };
+        for (height, radius) in canopy {
+            for dx in -radius..=*radius {
+                for dz in -radius..=*radius {
+                    // rounding off the corners, sometimes
+                    if *radius > 0 && dx.abs() == *radius && dz.abs() == *radius && random.random_bool(0.7) {
+                        continue;
+                    }
+                    self.set(world, BlockPosition::new(base.x + dx, top + height, base.z + dz), block::LEAVES, Replace::Open);
+                }
+            }
+        }
+        for y in base.y..=top {
+            self.set(world, BlockPosition::new(base.x, y, base.z), block::LOG, Replace::Foliage);
+        }
+    }
+}
+
+/// The highest solid block in a column of the chunk layer starting at `bottom`, if there is one
+fn surface(world: &World, x: i32, z: i32, bottom: i32) -> Option<BlockPosition> {
+    (bottom..bottom + CHUNK_SIZE).rev().map(|y| BlockPosition::new(x, y, z)).find(|position| world.is_solid(*position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::shader_handling::Float4;
+    
+    /// Flat ground for a chunk: stone up to 7, then dirt with grass on top at 10
+    fn terrain_chunk(chunk_x: i32, chunk_z: i32, index: usize) -> Chunk {
+        let mut chunk = Chunk::new(Float4::new(chunk_x as f32 * 16.0, 0.0, chunk_z as f32 * 16.0, 0.0), index);
+        for x in 0..16 {
+            for z in 0..16 {
+                for y in 0..=10 {
+                    chunk.tile_data[x][y][z] = match y {
+                        0..=7 => block::STONE,
+                        8..=9 => block::DIRT,
+                        _ => block::GRASS,
+                    };
+                }
+            }
+        }
+        chunk
+    }
+    
+    /// Generates 4x4 chunks of flat ground with their features, a chunk at a time
+    fn generate(seed: u64, structures: Vec<Structure>) -> (World, Features) {
+        let mut world = World::new(vec![]);
+        let mut features = Features::new(seed, structures);
+        for chunk_x in 0..4 {
+            for chunk_z in 0..4 {
+                world.add_chunk(terrain_chunk(chunk_x, chunk_z, 0));
+                features.place(&mut world, (chunk_x, 0, chunk_z));
+            }
+        }
+        (world, features)
+    }
+    
+    fn tiles(world: &World) -> Vec<[[[u32; 16]; 16]; 16]> {
+        world.chunks().iter().map(|chunk| chunk.tile_data).collect()
+    }
+    
+    #[test]
+    fn features_only_depend_on_the_seed() {
+        let world = tiles(&generate(7, vec![]).0);
+        assert!(world == tiles(&generate(7, vec![]).0), "the same seed placed different features");
+        assert!(world != tiles(&generate(8, vec![]).0), "another seed placed the same features");
+    }
+    
+    #[test]
+    fn ores_stay_in_the_stone_and_trees_stand_on_grass() {
+        let (world, _) = generate(7, vec![]);
+        let mut cells = vec![];
+        for x in 0..64 {
+            for y in 0..16 {
+                for z in 0..64 {
+                    let position = BlockPosition::new(x, y, z);
+                    cells.push((position, world.block(position)));
+                }
+            }
+        }
+        for ore in &ORES {
+            let found: Vec<_> = cells.iter().filter(|(_, cell)| *cell == ore.block).collect();
+            let name = block::properties(ore.block).name;
+            assert!(!found.is_empty(), "no {} anywhere", name);
+            // give or take how far the veins wander
+            assert!(found.iter().all(|(position, _)| position.y <= ore.max_y + ore.size as i32 && position.y <= 7), "{} outside the stone below {}", name, ore.max_y);
+        }
+        let trunks = cells.iter().filter(|(position, cell)| *cell == block::LOG && world.block(position.below()) == block::GRASS).count();
+        let leaves = cells.iter().filter(|(_, cell)| *cell == block::LEAVES).count();
+        assert!(trunks >= 16, "every chunk should have a tree or two, found {} trunks", trunks);
+        assert!(leaves >= trunks * 4, "only {} leaves for {} trees", leaves, trunks);
+    }
+    
+    #[test]
+    fn writes_into_ungenerated_chunks_land_when_they_generate() {
+        let mut world = World::new(vec![terrain_chunk(0, 0, 0)]);
+        let mut features = Features::new(7, vec![]);
+        // into what'll be stone, what'll be air above the ground, and stone that only open space can be written over
+        let buried = BlockPosition::new(20, 3, 3);
+        let above = BlockPosition::new(20, 12, 3);
+        let blocked = BlockPosition::new(21, 3, 3);
+        features.set(&mut world, buried, block::OBSIDIAN, Replace::Stone);
+        features.set(&mut world, above, block::OBSIDIAN, Replace::Open);
+        features.set(&mut world, blocked, block::OBSIDIAN, Replace::Open);
+        assert_eq!(features.queued_writes(), 3);
+        assert_eq!(world.block(buried), block::AIR);
+        
+        world.add_chunk(terrain_chunk(1, 0, 1));
+        features.place(&mut world, (1, 0, 0));
+        // (its own trees can still queue up writes for the chunks past it)
+        assert!(!features.queued.contains_key(&(1, 0, 0)), "writes were left queued for a chunk that's been generated");
+        // they land after the terrain, so what they may replace is checked against it
+        assert_eq!(world.block(buried), block::OBSIDIAN);
+        assert_eq!(world.block(above), block::OBSIDIAN);
+        assert_ne!(world.block(blocked), block::OBSIDIAN, "a write only allowed into open space replaced stone");
+    }
+    
+    #[test]
+    fn structures_load_and_get_placed_across_chunk_borders() {
+        let path = std::env::temp_dir().join(format!("test_structure_{}.json", std::process::id()));
+        let wall = "x".repeat(20);
+        std::fs::write(&path, format!(r#"{{ "chance": 1.0, "palette": {{ "x": "obsidian", "s": "cobblestone stairs[facing=north]" }}, "layers": [["{}", " s"]] }}"#, wall)).unwrap();
+        let structure = load_structure(&path);
+        std::fs::write(&path, r#"{ "chance": 1.0, "palette": { "x": "obsidian" }, "layers": [["xy"]] }"#).unwrap();
+        let missing_character = load_structure(&path);
+        let _ = std::fs::remove_file(&path);
+        assert!(missing_character.is_err(), "a structure using a character missing from its palette shouldn't load");
+        let structure = structure.expect("the structure didn't load");
+        assert_eq!(structure.blocks.len(), 21);
+        assert!(structure.blocks.contains(&((1, 0, 1), block_state::parse("cobblestone stairs[facing=north]").unwrap())));
+        
+        let (world, _) = generate(7, vec![structure]);
+        let spanning = (0..4).any(|chunk_x| {
+            (0..64).any(|z| (0..16).any(|y| world.block(BlockPosition::new(chunk_x * 16 + 15, y, z)) == block::OBSIDIAN
+                && world.block(BlockPosition::new(chunk_x * 16 + 16, y, z)) == block::OBSIDIAN))
+        });
+        assert!(spanning, "a 20 block wide structure should have been placed across a chunk border");
+    }
+}
@@ -0,0 +1,288 @@
+use crate::block::{self, BlockId};
+use crate::block_state::{self, FALLING, LEVEL};
+use crate::block_updates::BlockUpdates;
+use crate::world::{BlockPosition, World};
+
+// cellular fluids: a source block keeps feeding the blocks next to it, each one flowed a level further from it,
+// until they reach the fluid's spread distance; whatever's fed from above falls straight down instead
+// updates are only ran for blocks something changed next to, so still water costs nothing
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fluid {
+    Water,
+    Lava,
+}
+
+impl Fluid {
+    pub fn block(self) -> BlockId {
+        match self {
+            Fluid::Water => block::WATER,
+            Fluid::Lava => block::LAVA,
+        }
+    }
+    
+    /// How many blocks it flows from a source
+    fn spread_distance(self) -> u16 {
+        match self {
+            Fluid::Water => 7,
+            Fluid::Lava => 3,
+        }
+    }
+    
+    /// How many ticks a change takes to carry on to the next block
+    pub fn tick_delay(self) -> u64 {
+        match self {
+            Fluid::Water => 5,
+            Fluid::Lava => 30,
+        }
+    }
+}
+
+/// How many blocks a fluid cell has flowed from its source (0 for sources and falling fluid)
+pub fn level(cell: u32) -> u16 {
+    block_state::get(cell, &LEVEL).unwrap_or(0)
+}
+
+pub fn is_source(cell: u32) -> bool {
+    block::state(cell) == 0
+}
+
+pub fn is_falling(cell: u32) -> bool {
+    block_state::get(cell, &FALLING) == Some(1)
+}
+
+/// How far below the top of its block a fluid's surface sits, in sixteenths
+/// anything with the same fluid on top of it is full, otherwise it gets shallower the further it's flowed
+pub fn surface_drop(cell: u32, fluid_above: bool) -> u32 {
+    if fluid_above {
+        return 0;
+    }
+    let spread = block::fluid(cell).map(Fluid::spread_distance).unwrap_or(1) as u32;
+    2 + level(cell) as u32 * 12 / spread
+}
+
+fn source(fluid: Fluid) -> u32 {
+    block::with_state(fluid.block(), 0)
+}
+
+fn flowing(fluid: Fluid, level: u16) -> u32 {
+    block_state::with(fluid.block(), &LEVEL, level)
+}
+
+fn falling(fluid: Fluid) -> u32 {
+    block_state::with(fluid.block(), &FALLING, 1)
+}
+
+/// Whether a fluid can flow into a cell, washing away whatever was there
+fn is_replaceable(cell: u32) -> bool {
+    !block::is_solid(cell) && block::fluid(cell).is_none()
+}
+
+/// Works out what a fluid block should be given what's around it, then lets it spread; returns how many blocks changed
+pub fn update(updates: &mut BlockUpdates, world: &mut World, position: BlockPosition) -> usize {
+    let cell = world.block(position);
+    let Some(fluid) = block::fluid(cell) else {
+        return 0;
+    };
+    let mut changed = 0;
+    
+    // lava touching water hardens (into obsidian if it was a source)
+    if fluid == Fluid::Lava {
+        let touching_water = position.horizontal_neighbours().into_iter().chain([position.above()])
+            .any(|neighbour| block::fluid(world.block(neighbour)) == Some(Fluid::Water));
+        if touching_water {
+            updates.set(world, position, if is_source(cell) { block::OBSIDIAN } else { block::COBBLESTONE });
+            return 1;
+        }
+    }
+    
+    let updated = if is_source(cell) {
+        cell
+    } else if block::fluid(world.block(position.above())) == Some(fluid) {
+        falling(fluid)
+    } else {
+        // fed by whichever neighbour is closest to a source
+        let mut sources = 0;
+        let mut closest: Option<u16> = None;
+        for neighbour in position.horizontal_neighbours() {
+            let neighbour = world.block(neighbour);
+            if block::fluid(neighbour) != Some(fluid) {
+                continue;
+            }
+            sources += is_source(neighbour) as usize;
+            let neighbour_level = if is_falling(neighbour) { 0 } else { level(neighbour) };
+            closest = Some(closest.map_or(neighbour_level, |closest| closest.min(neighbour_level)));
+        }
+        let below_cell = world.block(position.below());
+        // water between two sources becomes one, as long as it's sitting on something
+        if fluid == Fluid::Water && sources >= 2 && (block::is_solid(below_cell) || below_cell == source(fluid)) {
+            source(fluid)
+        } else {
+            match closest {
+                Some(closest) if closest < fluid.spread_distance() => flowing(fluid, closest + 1),
+                _ => block::AIR,  // nothing's feeding it anymore
+            }
+        }
+    };
+    if updated != cell {
+        updates.set(world, position, updated);
+        changed += 1;
+        if updated == block::AIR {
+            return changed;
+        }
+    }
+    
+    // falling takes priority over spreading out, unless there's nothing loaded below to fall into
+    let down = position.below();
+    let below_cell = world.block(down);
+    if world.is_loaded(down) && is_replaceable(below_cell) {
+        updates.set(world, down, falling(fluid));
+        return changed + 1;
+    }
+    match block::fluid(below_cell) {
+        Some(Fluid::Water) if fluid == Fluid::Lava => {
+            updates.set(world, down, block::STONE);
+            return changed + 1;
+        },
+        Some(below_fluid) if below_fluid == fluid => {
+            if !is_source(below_cell) && !is_falling(below_cell) {
+                updates.set(world, down, falling(fluid));
+                changed += 1;
+            }
+            return changed;
+        },
+        _ => {},
+    }
+    
+    let next_level = if is_falling(updated) { 1 } else { level(updated) + 1 };
+    if next_level > fluid.spread_distance() {
+        return changed;
+    }
+    for neighbour in position.horizontal_neighbours() {
+        let neighbour_cell = world.block(neighbour);
+        let flows_in = world.is_loaded(neighbour) && is_replaceable(neighbour_cell) || (
+            block::fluid(neighbour_cell) == Some(fluid) && !is_source(neighbour_cell) && !is_falling(neighbour_cell) && level(neighbour_cell) > next_level
+        );
+        if flows_in {
+            updates.set(world, neighbour, flowing(fluid, next_level));
+            changed += 1;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Runs ticks until nothing's left to update, returning how many it took
+    fn settle(updates: &mut BlockUpdates, world: &mut World) -> usize {
+        for tick in 0..5000 {
+            if updates.pending_updates() == 0 {
+                return tick;
+            }
+            updates.tick(world);
+        }
+        panic!("fluids were still updating after 5000 ticks ({} pending)", updates.pending_updates());
+    }
+    
+    fn pour(updates: &mut BlockUpdates, world: &mut World, position: BlockPosition, cell: u32) {
+        world.set_block(position, cell);
+        updates.block_changed(world, position);
+    }
+    
+    #[test]
+    fn sources_spread_in_a_diamond_into_the_next_chunk() {
+        let mut world = World::test_floor(block::STONE);
+        let mut updates = BlockUpdates::new(0);
+        pour(&mut updates, &mut world, BlockPosition::new(14, 1, 8), source(Fluid::Water));
+        settle(&mut updates, &mut world);
+        let edge = world.block(BlockPosition::new(21, 1, 8));
+        assert_eq!(block::fluid(edge), Some(Fluid::Water), "water should reach 7 blocks from its source");
+        assert_eq!(level(edge), 7);
+        assert_eq!(world.block(BlockPosition::new(22, 1, 8)), block::AIR, "water spread further than 7 blocks");
+        assert_eq!(level(world.block(BlockPosition::new(17, 1, 11))), 6);
+        assert_eq!(world.count(block::WATER), 113);
+        let mutated: Vec<bool> = world.chunks().iter().map(|chunk| chunk.mutated).collect();
+        assert_eq!(mutated, [false, true, true, false], "only the two chunks the water spread through should be mutated");
+    }
+    
+    #[test]
+    fn fluids_on_the_bottom_of_the_world_spread_sideways() {
+        // nothing's loaded below y = 0, which reads back as air but can't be fallen into
+        let mut world = World::test_floor(block::AIR);
+        let mut updates = BlockUpdates::new(0);
+        pour(&mut updates, &mut world, BlockPosition::new(8, 0, 8), source(Fluid::Water));
+        assert_eq!(update(&mut updates, &mut world, BlockPosition::new(8, 0, 8)), 4, "should only count the 4 blocks it spread into");
+        settle(&mut updates, &mut world);
+        assert_eq!(level(world.block(BlockPosition::new(15, 0, 8))), 7, "didn't spread along the bottom layer");
+        assert_eq!(world.count(block::WATER), 113);
+    }
+    
+    #[test]
+    fn removing_the_source_drains_what_it_fed() {
+        let mut world = World::test_floor(block::STONE);
+        let mut updates = BlockUpdates::new(0);
+        let source_position = BlockPosition::new(14, 1, 8);
+        pour(&mut updates, &mut world, source_position, source(Fluid::Water));
+        settle(&mut updates, &mut world);
+        pour(&mut updates, &mut world, source_position, block::AIR);
+        settle(&mut updates, &mut world);
+        assert_eq!(world.count(block::WATER), 0);
+    }
+    
+    #[test]
+    fn poured_fluids_fall_then_spread() {
+        let mut world = World::test_floor(block::STONE);
+        let mut updates = BlockUpdates::new(0);
+        pour(&mut updates, &mut world, BlockPosition::new(8, 5, 8), source(Fluid::Water));
+        settle(&mut updates, &mut world);
+        for y in 1..5 {
+            assert!(is_falling(world.block(BlockPosition::new(8, y, 8))), "the column at y = {} isn't falling", y);
+        }
+        assert_eq!(level(world.block(BlockPosition::new(15, 1, 8))), 7, "didn't spread from where it landed");
+        assert_eq!(world.block(BlockPosition::new(9, 4, 8)), block::AIR, "spread sideways while falling");
+    }
+    
+    #[test]
+    fn water_between_two_sources_becomes_a_source() {
+        let mut world = World::test_floor(block::STONE);
+        let mut updates = BlockUpdates::new(0);
+        pour(&mut updates, &mut world, BlockPosition::new(4, 1, 8), source(Fluid::Water));
+        pour(&mut updates, &mut world, BlockPosition::new(6, 1, 8), source(Fluid::Water));
+        settle(&mut updates, &mut world);
+        assert_eq!(world.block(BlockPosition::new(5, 1, 8)), source(Fluid::Water));
+    }
+    
+    #[test]
+    fn lava_sources_touching_water_turn_to_obsidian() {
+        let mut world = World::test_floor(block::STONE);
+        let mut updates = BlockUpdates::new(0);
+        pour(&mut updates, &mut world, BlockPosition::new(10, 1, 8), source(Fluid::Lava));
+        pour(&mut updates, &mut world, BlockPosition::new(13, 1, 8), source(Fluid::Water));
+        settle(&mut updates, &mut world);
+        assert_eq!(world.block(BlockPosition::new(10, 1, 8)), block::OBSIDIAN);
+    }
+    
+    #[test]
+    fn flowing_lava_touching_water_turns_to_cobblestone() {
+        let mut world = World::test_floor(block::STONE);
+        let mut updates = BlockUpdates::new(0);
+        pour(&mut updates, &mut world, BlockPosition::new(30, 1, 8), source(Fluid::Lava));
+        settle(&mut updates, &mut world);
+        pour(&mut updates, &mut world, BlockPosition::new(35, 1, 8), source(Fluid::Water));
+        settle(&mut updates, &mut world);
+        assert!(world.count(block::COBBLESTONE) > 0);
+    }
+    
+    #[test]
+    fn lava_falling_onto_water_turns_it_to_stone() {
+        let mut world = World::test_floor(block::STONE);
+        let mut updates = BlockUpdates::new(0);
+        pour(&mut updates, &mut world, BlockPosition::new(40, 1, 8), source(Fluid::Water));
+        settle(&mut updates, &mut world);
+        pour(&mut updates, &mut world, BlockPosition::new(40, 4, 8), source(Fluid::Lava));
+        settle(&mut updates, &mut world);
+        assert_eq!(world.block(BlockPosition::new(40, 1, 8)), block::STONE);
+    }
+}
@@ -0,0 +1,194 @@
+// the simulation (movement, physics, block updates, the day cycle...) runs on a fixed tick that doesn't care how fast frames are drawn
+// rendering runs as often as it can (or as often as the cap/vsync lets it) and blends between the last two ticks
+
+/// How often the simulation ticks
+pub static TICKS_PER_SECOND: u32 = 20;
+/// If rendering falls this many ticks behind, the rest are dropped rather than spiraling trying to catch up
+static MAX_TICKS_PER_FRAME: u32 = 10;
+/// How many frames the rolling stats are over
+pub static FRAME_STATS_WINDOW: usize = 240;
+
+/// How the main loop paces itself, set from the command line (`--fps-cap <fps>`, `--vsync`)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LoopSettings {
+    pub frame_cap: Option<u32>,
+    pub vsync: bool,
+}
+
+impl LoopSettings {
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut settings = LoopSettings::default();
+        if let Some(flag_index) = args.iter().position(|arg| arg == "--fps-cap") {
+            let cap = args.get(flag_index + 1).ok_or("--fps-cap needs a frame rate")?;
+            let cap = cap.parse::<u32>().map_err(|e| format!("Invalid frame rate cap '{}': {}", cap, e))?;
+            // a cap of 0 is the same as leaving it uncapped
+            settings.frame_cap = (cap > 0).then_some(cap);
+        }
+        settings.vsync = args.iter().any(|arg| arg == "--vsync");
+        Ok(settings)
+    }
+    
+    /// The shortest a frame is allowed to take, if there's a cap
+    pub fn min_frame_time(&self) -> Option<std::time::Duration> {
+        self.frame_cap.map(|cap| std::time::Duration::from_secs_f64(1.0 / cap as f64))
+    }
+}
+
+/// Turns real time into a whole number of simulation ticks, carrying the leftover over to the next frame
+pub struct FixedTimestep {
+    tick_length: std::time::Duration,
+    accumulator: std::time::Duration,
+    last_update: std::time::Instant,
+    tick: u64,
+}
+
+impl FixedTimestep {
+    pub fn new(ticks_per_second: u32) -> Self {
+        FixedTimestep {
+            tick_length: std::time::Duration::from_secs_f64(1.0 / ticks_per_second as f64),
+            accumulator: std::time::Duration::ZERO,
+            last_update: std::time::Instant::now(),
+            tick: 0,
+        }
+    }
+    
+    /// The length of a tick in seconds, which is the delta time the simulation should use
+    pub fn tick_seconds(&self) -> f32 {
+        self.tick_length.as_secs_f32()
+    }
+    
+    /// How many ticks have run since the start
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+    
+    /// Adds the time since the last call and returns how many ticks should be run now
+    pub fn advance(&mut self, now: std::time::Instant) -> u32 {
+        self.accumulator += now.duration_since(self.last_update);
+        self.last_update = now;
+        let mut ticks = 0;
+        while self.accumulator >= self.tick_length {
+            self.accumulator -= self.tick_length;
+            ticks += 1;
+        }
+        if ticks > MAX_TICKS_PER_FRAME {
+            // after a stall (a breakpoint, dragging the window...) the simulation just loses the time instead of running hundreds of ticks
+            ticks = MAX_TICKS_PER_FRAME;
+            self.accumulator = std::time::Duration::ZERO;
+        }
+        self.tick += ticks as u64;
+        ticks
+    }
+    
+    /// How far (0..1) into the next tick the current time is, for blending between the previous and current simulation state
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator.as_secs_f64() / self.tick_length.as_secs_f64()) as f32
+    }
+}
+
+/// Sleeps out the rest of a frame so it takes at least `min_frame_time`
+/// (sleeping is coarse on most platforms, so the last bit is spun instead)
+pub fn wait_for_frame_end(frame_start: std::time::Instant, min_frame_time: std::time::Duration) {
+    const SPIN_TIME: std::time::Duration = std::time::Duration::from_millis(1);
+    let frame_end = frame_start + min_frame_time;
+    let now = std::time::Instant::now();
+    if frame_end > now + SPIN_TIME {
+        std::thread::sleep(frame_end - now - SPIN_TIME);
+    }
+    while std::time::Instant::now() < frame_end {
+        std::hint::spin_loop();
+    }
+}
+
+/// The timings of a single frame
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameTimings {
+    pub frame: std::time::Duration,
+    pub upload: std::time::Duration,     // writing the per-frame buffers
+    pub execute: std::time::Duration,    // the compute shader itself
+    pub read_back: std::time::Duration,  // copying the pixels out and picking up new geometry
+    pub ticks: u32,
+}
+
+/// Frame timings over the last `FRAME_STATS_WINDOW` frames
+pub struct FrameStats {
+    frames: std::collections::VecDeque<FrameTimings>,
+    last_report: std::time::Instant,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        FrameStats {
+            frames: std::collections::VecDeque::with_capacity(FRAME_STATS_WINDOW),
+            last_report: std::time::Instant::now(),
+        }
+    }
+    
+    pub fn record(&mut self, timings: FrameTimings) {
+        if self.frames.len() == FRAME_STATS_WINDOW {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(timings);
+    }
+    
+    fn average(&self, stage: impl Fn(&FrameTimings) -> std::time::Duration) -> std::time::Duration {
+        if self.frames.is_empty() {
+            return std::time::Duration::ZERO;
+        }
+        self.frames.iter().map(stage).sum::<std::time::Duration>() / self.frames.len() as u32
+    }
+    
+    pub fn average_frame_time(&self) -> std::time::Duration {
+        self.average(|timings| timings.frame)
+    }
+    
+    pub fn fps(&self) -> f32 {
+        let average = self.average_frame_time().as_secs_f32();
+        if average > 0.0 { 1.0 / average } else { 0.0 }
+    }
+    
+    /// The frame time that `fraction` of the frames were at or under (0.99 gives the time the slowest 1% are over)
+    pub fn percentile_frame_time(&self, fraction: f32) -> std::time::Duration {
+        let mut frame_times: Vec<std::time::Duration> = self.frames.iter().map(|timings| timings.frame).collect();
+        if frame_times.is_empty() {
+            return std::time::Duration::ZERO;
+        }
+        frame_times.sort_unstable();
+        let index = ((frame_times.len() - 1) as f32 * fraction.clamp(0.0, 1.0)).round() as usize;
+        frame_times[index]
+    }
+    
+    /// The recorded frame times, oldest first
+    pub fn frame_times(&self) -> impl Iterator<Item = std::time::Duration> + '_ {
+        self.frames.iter().map(|timings| timings.frame)
+    }
+    
+    pub fn max_frame_time(&self) -> std::time::Duration {
+        self.frames.iter().map(|timings| timings.frame).max().unwrap_or_default()
+    }
+    
+    /// A one line summary, returned at most once every `interval` so it can be printed without flooding the terminal
+    pub fn report(&mut self, interval: std::time::Duration) -> Option<String> {
+        if self.last_report.elapsed() < interval || self.frames.is_empty() {
+            return None;
+        }
+        self.last_report = std::time::Instant::now();
+        Some(format!(
+            "FPS: {:.1}      Frame: {:.2?} avg, {:.2?} 99th, {:.2?} max      Upload: {:.2?}      Execute: {:.2?}      Read Back: {:.2?}      Ticks/Frame: {:.2}",
+            self.fps(),
+            self.average_frame_time(),
+            self.percentile_frame_time(0.99),
+            self.max_frame_time(),
+            self.average(|timings| timings.upload),
+            self.average(|timings| timings.execute),
+            self.average(|timings| timings.read_back),
+            self.frames.iter().map(|timings| timings.ticks).sum::<u32>() as f32 / self.frames.len() as f32,
+        ))
+    }
+}
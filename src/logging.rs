@@ -0,0 +1,169 @@
+// leveled logging plus timed spans, which can also be recorded and dumped as a chrome trace (open it in chrome://tracing or perfetto)
+// the level and tracing are set once from the command line in main (`--log-level <level>`, `--trace <path>`)
+
+/// How much gets logged, from least to most
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+    Trace = 4,
+}
+
+impl Level {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(format!("Unknown log level '{}' (expected error, warn, info, debug or trace)", name)),
+        }
+    }
+    
+    fn label(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN ",
+            Level::Info => "INFO ",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+static MAX_LEVEL: std::sync::atomic::AtomicU8 = std::sync::atomic::AtomicU8::new(Level::Info as u8);
+static TRACING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+/// Past this many recorded spans the rest get dropped, so a long session can't eat all the memory
+static MAX_TRACE_EVENTS: usize = 2_000_000;
+/// Everything is timed from here (chrome traces want microseconds since some fixed point)
+static START: std::sync::LazyLock<std::time::Instant> = std::sync::LazyLock::new(std::time::Instant::now);
+static TRACE_EVENTS: parking_lot::Mutex<Vec<TraceEvent>> = parking_lot::Mutex::new(vec![]);
+
+static NEXT_THREAD_ID: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(1);
+thread_local! {
+    // small sequential ids read better in the trace viewer than the os ones
+    static THREAD_ID: u32 = NEXT_THREAD_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+}
+
+pub fn set_level(level: Level) {
+    MAX_LEVEL.store(level as u8, std::sync::atomic::Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= MAX_LEVEL.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Starts recording every span so it can be written out with `write_chrome_trace`
+pub fn start_tracing() {
+    std::sync::LazyLock::force(&START);
+    TRACING.store(true, std::sync::atomic::Ordering::Relaxed);
+}
+
+fn tracing() -> bool {
+    TRACING.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Used by the `log_*!` macros; call those instead
+pub fn write(level: Level, target: &str, message: std::fmt::Arguments) {
+    // the module path without the crate name, which is the same for everything
+    let target = target.split_once("::").map_or(target, |(_, rest)| rest);
+    eprintln!("[{:>10.3}s {} {}] {}", START.elapsed().as_secs_f64(), level.label(), target, message);
+}
+
+#[macro_export]
+macro_rules! log_at {
+    ($level:expr, $($arg:tt)*) => {
+        if $crate::logging::enabled($level) {
+            $crate::logging::write($level, module_path!(), format_args!($($arg)*));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! log_error { ($($arg:tt)*) => { $crate::log_at!($crate::logging::Level::Error, $($arg)*) }; }
+#[macro_export]
+macro_rules! log_warn { ($($arg:tt)*) => { $crate::log_at!($crate::logging::Level::Warn, $($arg)*) }; }
+#[macro_export]
+macro_rules! log_info { ($($arg:tt)*) => { $crate::log_at!($crate::logging::Level::Info, $($arg)*) }; }
+#[macro_export]
+macro_rules! log_debug { ($($arg:tt)*) => { $crate::log_at!($crate::logging::Level::Debug, $($arg)*) }; }
+#[macro_export]
+macro_rules! log_trace { ($($arg:tt)*) => { $crate::log_at!($crate::logging::Level::Trace, $($arg)*) }; }
+
+/// A complete ("X") event in the chrome trace format
+#[derive(serde::Serialize)]
+struct TraceEvent {
+    name: &'static str,
+    cat: &'static str,
+    ph: &'static str,
+    ts: f64,   // microseconds
+    dur: f64,
+    pid: u32,
+    tid: u32,
+}
+
+/// Times everything until it's dropped; logged at trace level and recorded if tracing is on
+#[must_use = "a span ends as soon as it's dropped"]
+pub struct Span {
+    category: &'static str,
+    name: &'static str,
+    start: std::time::Instant,
+}
+
+pub fn span(category: &'static str, name: &'static str) -> Span {
+    Span { category, name, start: std::time::Instant::now() }
+}
+
+impl Span {
+    /// How long the span has been open so far
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.start.elapsed()
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let (trace_logged, traced) = (enabled(Level::Trace), tracing());
+        if !trace_logged && !traced {
+            return;
+        }
+        let start = self.start;
+        let duration = start.elapsed();
+        if trace_logged {
+            write(Level::Trace, self.category, format_args!("{} took {:?}", self.name, duration));
+        }
+        if traced {
+            let mut events = TRACE_EVENTS.lock();
+            if events.len() < MAX_TRACE_EVENTS {
+                events.push(TraceEvent {
+                    name: self.name,
+                    cat: self.category,
+                    ph: "X",
+                    ts: start.saturating_duration_since(*START).as_secs_f64() * 1_000_000.0,
+                    dur: duration.as_secs_f64() * 1_000_000.0,
+                    pid: 1,
+                    tid: THREAD_ID.with(|id| *id),
+                });
+            }
+        }
+    }
+}
+
+/// Writes every span recorded since `start_tracing` to `path` as chrome trace json
+pub fn write_chrome_trace(path: &str) -> Result<(), String> {
+    let events = TRACE_EVENTS.lock();
+    if events.len() >= MAX_TRACE_EVENTS {
+        log_warn!("The trace hit its limit of {} spans, so the end of the session is missing", MAX_TRACE_EVENTS);
+    }
+    let trace = serde_json::json!({
+        "traceEvents": &*events,
+        "displayTimeUnit": "ms",
+    });
+    let file = std::fs::File::create(path).map_err(|e| format!("Failed to create trace file '{}': {}", path, e))?;
+    serde_json::to_writer(std::io::BufWriter::new(file), &trace).map_err(|e| format!("Failed to write trace file '{}': {}", path, e))?;
+    log_info!("Wrote {} spans to {}", events.len(), path);
+    Ok(())
+}
@@ -1,6 +1,32 @@
+#[macro_use]
+mod logging;
 mod shader_handling;
 mod meshing;
 mod chunk;
+mod culling;
+mod occlusion;
+mod math;
+mod camera;
+mod mesh_handoff;
+mod memory;
+mod triangle;
+#[cfg(test)]
+mod raster;
+mod texture_atlas;
+mod game_loop;
+mod debug_overlay;
+mod block;
+mod world;
+mod physics;
+mod player;
+mod entity;
+mod pathfinding;
+mod fluid;
+mod block_updates;
+mod block_shape;
+mod block_state;
+mod biome;
+mod features;
 
 use metal::Device;
 use sdl2::render::{TextureAccess, TextureCreator};
@@ -8,9 +34,16 @@ use sdl2::pixels::PixelFormatEnum;
 use sdl2::video::WindowContext;
 use sdl2::rect::Rect;
 use shader_handling::{ShaderHandler, Shader};
-use crate::meshing::{rotate, Mesh, MeshDoubleBuffer};
-use crate::chunk::{generate_cube, Chunk};
+use crate::meshing::{Mesh, MeshFrame, MeshParts};
+use crate::math::Vec3;
+use crate::camera::{Camera, CameraInput};
+use crate::chunk::Chunk;
 use crate::shader_handling::{Float4, Float4x4, Pipeline, Uchar4, Uint4, Vertex};
+use crate::texture_atlas::TextureAtlas;
+use crate::world::{BlockPosition, World};
+use crate::player::Player;
+use crate::entity::{Entities, Entity, EntityInstance, Model};
+use crate::game_loop::{FixedTimestep, FrameStats, FrameTimings, LoopSettings, TICKS_PER_SECOND};
 
 /// The starting width of the application window
 static WINDOW_START_WIDTH: u32 = 1200;
@@ -32,14 +65,191 @@ static MAX_VERTICES: u64 = 7_500_000_u64;   // HOW?????? how did it even handle
 static MAX_TRIANGLES: u64 = 5_000_000_u64;
 static MAX_TEXTURES: u64 = 1024_u64;
 
-static TILE_TEXTURE_WIDTH: u64 = 16u64;
-static TILE_TEXTURE_HEIGHT: u64 = 16u64;
-
 static FORCED_REMESH_DELAY: u64 = 1u64;
+/// Anything generated below this gets flooded with water
+static SEA_LEVEL: usize = 11;
+/// How often the rolling frame stats get printed
+static FRAME_STATS_REPORT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// How far away blocks can be broken and placed
+static BLOCK_REACH: f32 = 6.0;
+/// What the number keys pick to place, in order (see `hotbar_slot`)
+static PLACEABLE_BLOCKS: [block::BlockId; 10] = [
+    block::STONE, block::COBBLESTONE, block::LOG, block::STONE_SLAB, block::COBBLESTONE_STAIRS,
+    block::FENCE, block::TABLE, block::SAND, block::WHEAT, block::FIRE,
+];
+
+/// Which of `PLACEABLE_BLOCKS` a number key picks: 1 to 9 and then 0, the same order as along the keyboard
+fn hotbar_slot(keycode: sdl2::keyboard::Keycode) -> Option<usize> {
+    match keycode.into_i32() - sdl2::keyboard::Keycode::Num1.into_i32() {
+        slot @ 0..=8 => Some(slot as usize),
+        _ => (keycode == sdl2::keyboard::Keycode::Num0).then_some(9),
+    }
+}
 
 static CELL_SIZE: u32 = 4;  // seems like a good size for performance; 16 was much slower; lower size = more cpu work, but faster gpu, higher size = less cpu work, but slower gpu
 
+#[cfg(feature = "alloc-stats")]
+#[global_allocator]
+static ALLOCATOR: memory::TrackingAllocator = memory::TrackingAllocator;
+
+/// The normals every face indexes into (the low 8 bits of a triangle's w)
+/// the six chunk faces, then the directions rotated entity faces get snapped to
+fn face_normals() -> Vec<Float4> {
+    let mut normals = vec![
+        Float4::new( 0.0,  1.0,  0.0, 0.0),
+        Float4::new( 0.0, -1.0,  0.0, 0.0),
+        Float4::new( 1.0,  0.0,  0.0, 0.0),
+        Float4::new(-1.0,  0.0,  0.0, 0.0),
+        Float4::new( 0.0,  0.0,  1.0, 0.0),
+        Float4::new( 0.0,  0.0, -1.0, 0.0),
+    ];
+    normals.extend(entity::entity_normals());
+    normals
+}
+
+/// The same seed always generates the same world;
+/// each chunk gets its terrain and then its features (trees, ores, boulders and `structures`, see `features::Features`)
+fn generate_world(seed: u64, structures: &[features::Structure]) -> World {
+    use rand::{Rng, SeedableRng};
+    let mut random = rand::rngs::StdRng::seed_from_u64(seed);
+    let climate = biome::Climate::new(seed);
+    let mut features = features::Features::new(seed, structures.to_vec());
+    let mut world = World::new(vec![]);
+    for chunk_x in 0..64 {
+        for chunk_z in 0..64 {
+            let mut chunk = Chunk::new(Float4::new(chunk_x as f32 * 16.0, 0.0, chunk_z as f32 * 16.0, 0.0), 0);
+            chunk.mutated = true;
+            chunk.climate = climate.chunk_corners(chunk_x * 16, chunk_z * 16);
+            for x in 0..16 {
+                for z in 0..16 {
+                    let height = random.random_range(10..14);
+                    for y in 0..height {
+                        // grass on top of a few layers of dirt (or sand along the shore and gravel further out under the sea), then stone
+                        chunk.tile_data[x][y][z] = match height - y {
+                            1 if height < SEA_LEVEL => block::GRAVEL,
+                            1 if height == SEA_LEVEL => block::SAND,
+                            1 => block::GRASS,
+                            2..=4 => block::DIRT,
+                            _ => block::STONE,
+                        };
+                    }
+                    for y in height..SEA_LEVEL {
+                        chunk.tile_data[x][y][z] = block::WATER;
+                    }
+                    if height > SEA_LEVEL && random.random_bool(0.1) {
+                        chunk.tile_data[x][height][z] = block::TALL_GRASS;
+                    }
+                }
+            }
+            world.add_chunk(chunk);
+            features.place(&mut world, (chunk_x, 0, chunk_z));
+        }
+    }
+    // anything still queued reaches past the edge of the world (or above the chunk layer), and just doesn't get placed
+    log_debug!("Dropped {} feature blocks outside the generated chunks", features.queued_writes());
+    world
+}
+
+/// Meshes every chunk into `mesh` (which should be empty), picking each chunk's lod from its distance to the camera
+fn build_world_mesh(mesh: &mut Mesh, world: &parking_lot::RwLock<World>, camera_position: Float4) {
+    let _span = logging::span("meshing", "build_world_mesh");
+    let chunk_count = world.read().chunks().len();
+    for chunk_index in 0..chunk_count {
+        // locked a chunk at a time rather than for the whole rebuild, so the simulation never waits on more than one chunk's remesh
+        let mut world = world.write();
+        let chunk = &mut world.chunks_mut()[chunk_index];
+        chunk.chunk_index = mesh.chunk_ref().len();
+        mesh.add_chunk(
+            chunk.position,
+            Float4::new(16.0, 16.0, 16.0, 0.0),
+        );
+        let distance = camera_position.distance(&Float4::new(
+            chunk.position.x + 8.0,
+            chunk.position.y + 8.0,
+            chunk.position.z + 8.0,
+            0.0,
+        ));
+        chunk.remesh_chunk(mesh, distance as usize / 4, match distance {
+            0.0..=32.0  => 0,
+            32.0..=64.0 => 1,
+            64.0..=128.0 => 2,
+            128.0..=256.0 => 3,
+            _ => 4,
+        });
+    }
+}
+
+/// Rebuilds the world mesh (and a frame from it) over and over without opening a window,
+/// printing how long each rebuild took and how much memory it needed (ran with `--bench-rebuild <count>`);
+/// the heap numbers are the live bytes and high-water mark of the heap (not the process's resident memory) and need `--features alloc-stats`
+fn run_rebuild_benchmark(rebuilds: usize) -> Result<(), String> {
+    let camera = Camera::new(Vec3::new(0.0, 2.0, -2.0), 60.0, 0.1, 9999.0);
+    let window_size = (WINDOW_START_WIDTH, WINDOW_START_HEIGHT);
+    let world = parking_lot::RwLock::new(generate_world(0, &[]));
+    let mut mesh = Mesh::new(MeshParts { normals: face_normals(), ..Default::default() });
+    let mut frame = MeshFrame::default();
+    println!("World generated, heap in use: {}", memory::format_heap_bytes(memory::allocated()));
+    for rebuild in 0..rebuilds {
+        memory::reset_peak();
+        let start = std::time::Instant::now();
+        mesh.reset();
+        build_world_mesh(&mut mesh, &world, camera.position.to_float4(0.0));
+        let built = start.elapsed();
+        mesh.check_remesh(&mut frame, window_size, &camera, false);
+        println!(
+            "Rebuild {}: built in {:?}, total {:?}      Heap High-Water Mark: {}      Heap After: {}      Mesh: {}      Frame: {}",
+            rebuild, built, start.elapsed(),
+            memory::format_heap_bytes(memory::peak_allocated()),
+            memory::format_heap_bytes(memory::allocated()),
+            memory::format_bytes(mesh.memory_usage()),
+            memory::format_bytes(frame.memory_usage()),
+        );
+    }
+    Ok(())
+}
+
+/// Writes out the chrome trace if one was asked for with `--trace <path>`
+fn finish_tracing(trace_path: &Option<String>) -> Result<(), String> {
+    match trace_path {
+        Some(path) => logging::write_chrome_trace(path),
+        None => Ok(()),
+    }
+}
+
 pub fn main() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(flag_index) = args.iter().position(|arg| arg == "--log-level") {
+        let level = args.get(flag_index + 1).ok_or("--log-level needs a level (error, warn, info, debug or trace)")?;
+        logging::set_level(logging::Level::parse(level)?);
+    }
+    let trace_path = match args.iter().position(|arg| arg == "--trace") {
+        Some(flag_index) => Some(args.get(flag_index + 1).ok_or("--trace needs a path to write the trace to")?.clone()),
+        None => None,
+    };
+    if trace_path.is_some() {
+        logging::start_tracing();
+    }
+    
+    if let Some(flag_index) = args.iter().position(|arg| arg == "--bench-rebuild") {
+        let rebuilds = match args.get(flag_index + 1) {
+            Some(count) => count.parse::<usize>().map_err(|e| format!("Invalid rebuild count '{}': {}", count, e))?,
+            None => 10,
+        };
+        run_rebuild_benchmark(rebuilds)?;
+        return finish_tracing(&trace_path);
+    }
+    let loop_settings = LoopSettings::from_args(&args)?;
+    // the world generation and block updates only depend on the seed (and what the player does)
+    let seed = match args.iter().position(|arg| arg == "--seed") {
+        Some(flag_index) => {
+            let seed = args.get(flag_index + 1).ok_or("--seed needs a number")?;
+            seed.parse::<u64>().map_err(|e| format!("Invalid seed '{}': {}", seed, e))?
+        },
+        None => rand::random(),
+    };
+    log_info!("World seed: {}", seed);
+    
     // Initialize SDL2
     let sdl = sdl2::init()?;
     let video = sdl.video()?;
@@ -57,8 +267,11 @@ pub fn main() -> Result<(), String> {
     
     // --- Create an SDL2 surface and texture ---
     let (_device_width, _device_height) = (video.desktop_display_mode(0)?.w, video.desktop_display_mode(0)?.h);
-    let mut window_surface = window
-        .into_canvas()
+    let mut canvas_builder = window.into_canvas();
+    if loop_settings.vsync {
+        canvas_builder = canvas_builder.present_vsync();
+    }
+    let mut window_surface = canvas_builder
         .build()
         .map_err(|e| e.to_string())?;
     
@@ -83,273 +296,387 @@ pub fn main() -> Result<(), String> {
         size_of::<Float4>() as u64 * MAX_TRIANGLES,
         size_of::<Uint4 >() as u64 * MAX_TRIANGLES,
         size_of::<u32   >() as u64 * ((MAXIMUM_WINDOW_WIDTH / CELL_SIZE as u64) * (MAXIMUM_WINDOW_HEIGHT / CELL_SIZE as u64) * 64),
-        size_of::<Uchar4 >() as u64 * MAX_TEXTURES * (TILE_TEXTURE_WIDTH * TILE_TEXTURE_HEIGHT),
+        size_of::<Uchar4 >() as u64 * MAX_TEXTURES * texture_atlas::TEXELS_PER_TEXTURE as u64,  // every texture along with its mips
         size_of::<f32   >() as u64 * (MAXIMUM_WINDOW_HEIGHT * MAXIMUM_WINDOW_WIDTH),
         size_of::<u8    >() as u64 * (MAXIMUM_WINDOW_HEIGHT * MAXIMUM_WINDOW_WIDTH),
     ], "ComputeShader")?;
     let mut shader_handler = ShaderHandler::new(device, shader);
     
     // todo! actually load textures
+    let mut atlas = TextureAtlas::new();
     let mut texture = vec![];
+    // grass and foliage are grayscale wherever the biome tints them (see `texture_atlas::TINTED_ALPHA`)
+    let tinted = |shade: u8| Uchar4::new(shade, shade, shade, texture_atlas::TINTED_ALPHA);
     for i in 0..=255 {
         if i / 16 > 4 {
             texture.push(Uchar4::new(150, 75, 10, 0));
         } else {
-            texture.push(Uchar4::new(75, 225, 75, 0));
+            texture.push(tinted(220));
         }
     }
-    for _ in 0..=255 {
-        texture.push(Uchar4::new(75, 225, 75, 0));
+    atlas.add_texture(&texture)?;
+    let mut grass = vec![];
+    for i in 0..256 {
+        grass.push(tinted(if (i * 13 + i / 16 * 7) % 5 == 0 { 195 } else { 225 }));
+    }
+    atlas.add_texture(&grass)?;
+    atlas.add_texture(&[Uchar4::new(150, 75, 10, 0); 256])?;
+    atlas.add_texture(&[Uchar4::new(125, 125, 125, 0); 256])?;  // stone
+    let mut cobblestone = vec![];
+    for i in 0..256 {
+        let shade = if (i / 16 / 4 + i % 16 / 4) % 2 == 0 { 110 } else { 140 };
+        cobblestone.push(Uchar4::new(shade, shade, shade, 0));
+    }
+    atlas.add_texture(&cobblestone)?;
+    atlas.add_texture(&[Uchar4::new(30, 15, 45, 0); 256])?;  // obsidian
+    // water, lava and fire are strips of frames stacked top to bottom, swapped in as the game clock goes (see `TextureAtlas::animate`)
+    let mut water = vec![];
+    for frame in 0..8 {
+        for i in 0..256 {
+            let ripple = (i % 16 + i / 16 + frame * 2) % 16 < 2;
+            water.push(if ripple { Uchar4::new(70, 120, 235, 0) } else { Uchar4::new(40, 90, 220, 0) });
+        }
+    }
+    atlas.add_animated_texture(&water, &[3; 8])?;
+    let mut lava = vec![];
+    for frame in 0..6 {
+        for i in 0..256 {
+            let bubble = (i % 16 * 3 + i / 16 * 5 + frame) % 7 == 0;
+            lava.push(if bubble { Uchar4::new(255, 190, 60, 0) } else { Uchar4::new(230, 110, 20, 0) });
+        }
+    }
+    atlas.add_animated_texture(&lava, &[8, 6, 6, 8, 6, 6])?;
+    atlas.add_texture(&[Uchar4::new(220, 205, 150, 0); 256])?;  // sand
+    let mut gravel = vec![];
+    for i in 0..256 {
+        let shade = if (i * 37 + i / 16 * 11) % 5 < 2 { 95 } else { 135 };
+        gravel.push(Uchar4::new(shade, shade - 5, shade - 10, 0));
+    }
+    atlas.add_texture(&gravel)?;
+    let mut log = vec![];
+    for i in 0..256 {
+        let shade = if i % 16 % 4 == 0 { 60 } else { 90 };
+        log.push(Uchar4::new(shade + 15, shade, shade / 2, 0));
+    }
+    atlas.add_texture(&log)?;
+    // leaves and wheat have holes cut out of them (texels with an alpha of 128 or more aren't drawn)
+    let mut leaves = vec![];
+    for i in 0..256 {
+        let hole = (i * 7 + i / 16 * 3) % 6 == 0;
+        leaves.push(if hole { Uchar4::new(0, 0, 0, 255) } else { tinted(170) });
+    }
+    atlas.add_texture(&leaves)?;
+    let mut wheat = vec![];
+    for i in 0..256 {
+        let stalk = i % 16 % 3 == 1;
+        wheat.push(Uchar4::new(200, 180, 70, if stalk { 0 } else { 255 }));
     }
-    for _ in 0..=255 {
-        texture.push(Uchar4::new(150, 75, 10, 0));
+    atlas.add_texture(&wheat)?;
+    let mut tall_grass = vec![];
+    for i in 0..256 {
+        let blade = (i % 16 + i / 16 / 3) % 4 == 0;
+        tall_grass.push(if blade { tinted(210) } else { Uchar4::new(0, 0, 0, 255) });
     }
-    shader_handler.get_shader().update_buffer_slice(9, &texture)?;
+    atlas.add_texture(&tall_grass)?;
+    // the rings on the cut ends of logs
+    let mut log_end = vec![];
+    for i in 0..256 {
+        let (dx, dy) = ((i % 16) as f32 - 7.5, (i / 16) as f32 - 7.5);
+        let shade = if ((dx * dx + dy * dy).sqrt() as u32).is_multiple_of(3) { 110 } else { 150 };
+        log_end.push(Uchar4::new(shade + 20, shade, shade / 2, 0));
+    }
+    atlas.add_texture(&log_end)?;
+    // flames of a different height in each column, flickering between frames, with the rest cut out
+    let mut fire = vec![];
+    for frame in 0..4 {
+        for i in 0..256 {
+            let height = (i % 16 * 7 + frame * 5) % 9 + 5;
+            let above_flame = 16 - i / 16 > height;
+            fire.push(Uchar4::new(255, 120 + (i / 16 * 8) as u8, 30, if above_flame { 255 } else { 0 }));
+        }
+    }
+    atlas.add_animated_texture(&fire, &[2, 3, 2, 3])?;
+    // coal, iron and gold ore are stone with specks of their color
+    for speck in [Uchar4::new(30, 30, 30, 0), Uchar4::new(200, 150, 115, 0), Uchar4::new(245, 205, 50, 0)] {
+        let ore: Vec<Uchar4> = (0..256)
+            .map(|i| if (i * 11 + i / 16 * 5) % 9 < 2 { speck } else { Uchar4::new(125, 125, 125, 0) })
+            .collect();
+        atlas.add_texture(&ore)?;
+    }
+    if atlas.texture_count() > MAX_TEXTURES as usize {
+        return Err(format!("Too many block textures ({}); the texture buffer only has room for {}", atlas.texture_count(), MAX_TEXTURES));
+    }
+    shader_handler.get_shader().update_buffer_slice(9, atlas.texels())?;
     
-    let mut camera_position = Float4::new(0.0, 2.0, -2.0, 0.0);
-    let mut camera_rotation = Float4::new(0.0, 0.0, 0.0, 0.0);
-    let normals: Vec<Float4> = vec![
-        Float4::new( 0.0,  1.0,  0.0, 0.0),
-        Float4::new( 0.0, -1.0,  0.0, 0.0),
-        Float4::new( 1.0,  0.0,  0.0, 0.0),
-        Float4::new(-1.0,  0.0,  0.0, 0.0),
-        Float4::new( 0.0,  0.0,  1.0, 0.0),
-        Float4::new( 0.0,  0.0, -1.0, 0.0),
-    ];
+    let mut camera = Camera::new(Vec3::new(0.0, 2.0, -2.0), 60.0, 0.1, 9999.0);
+    // the mouse is captured for mouse-look until escape is pressed
+    sdl.mouse().set_relative_mouse_mode(true);
+    let mut mouse_captured = true;
+    let normals = face_normals();
     
     let depth_buffer = vec![f32::MAX; const { (MAXIMUM_WINDOW_WIDTH * MAXIMUM_WINDOW_HEIGHT) as usize }];
     
-    let mesh = Mesh::new(
-        true,
-        vec![],
-        vec![],
-        vec![],
-        vec![],
-        vec![Vertex::default(); MAX_VERTICES as usize],
-        vec![],
-        normals.clone(),
-        vec![0u32; 64 * (MAXIMUM_WINDOW_WIDTH / CELL_SIZE as u64) as usize * (MAXIMUM_WINDOW_HEIGHT / CELL_SIZE as u64) as usize],
-        vec![],
-        vec![],
-        vec![],
-    );
-    let mesh = std::sync::Arc::new(MeshDoubleBuffer {
-        front: std::sync::Arc::new(parking_lot::RwLock::new(mesh.clone())),
-        back: std::sync::Arc::new(parking_lot::RwLock::new(mesh)),
-        current_front: std::sync::Arc::new(parking_lot::RwLock::new(true)),
-        swapping: std::sync::Arc::new(parking_lot::RwLock::new(false)),
-    });
-    let mut chunks = vec![];
-    for chunk_x in 0..64 {
-        for chunk_z in 0..64 {
-            let mut chunk = Chunk::new(Float4::new(chunk_x as f32 * 16.0, 0.0, chunk_z as f32 * 16.0, 0.0), 0);
-            chunk.mutated = true;
-            for x in 0..16 {
-                for z in 0..16 {
-                    for y in 0..rand::random_range(10..14) {
-                        chunk.tile_data[x][y][z] = 1;  // setting some tiles to be solid
-                    }
-                }
-            }
-            chunks.push(chunk);
+    // the remesh worker builds frames into one slot while the renderer draws from another (see mesh_handoff)
+    let (mut frame_writer, mut frame_reader) = mesh_handoff::triple_buffer(MeshFrame::default(), MeshFrame::default(), MeshFrame::default());
+    match block_shape::load_models("models") {
+        Ok(count) => log_info!("Loaded {} block models", count),
+        Err(error) => log_warn!("{} (blocks using models will be drawn as cubes)", error),
+    }
+    let structures = match features::load_structures("structures") {
+        Ok(structures) => {
+            log_info!("Loaded {} structures", structures.len());
+            structures
+        }
+        Err(error) => {
+            log_warn!("{} (the world will be generated without structures)", error);
+            vec![]
+        }
+    };
+    let world = std::sync::Arc::new(parking_lot::RwLock::new(generate_world(seed, &structures)));
+    // todo! place these from actual gameplay rather than a test row near the start
+    {
+        let mut world = world.write();
+        let ripe_wheat = block_state::with(block::WHEAT, &block_state::AGE, block_updates::CROP_STAGES - 1);
+        let row = [
+            "stone slab", "stone slab[half=top]", "stone slab", "cobblestone stairs", "cobblestone stairs[facing=west,half=top]",
+            "fence", "fence", "fence", "table", "log[axis=x]", "log", "tall grass", "fire",
+        ].map(|text| block_state::parse(text).unwrap_or(block::AIR));
+        let row = row.into_iter().chain([ripe_wheat, block::WHEAT]);
+        for (i, cell) in row.enumerate() {
+            let column = 2 + i as i32;
+            let Some(ground) = (0..15).rev().find(|y| world.is_solid(BlockPosition::new(column, *y, 4))) else {
+                continue;
+            };
+            world.set_block(BlockPosition::new(column, ground + 1, 4), cell);
         }
     }
     
-    let processing_mutation = std::sync::Arc::new(parking_lot::RwLock::new(false));
-    let waiting_for_chunk_update = std::sync::Arc::new(parking_lot::RwLock::new(false));
+    let window_size_sync = std::sync::Arc::new(parking_lot::RwLock::new((WINDOW_START_WIDTH, WINDOW_START_HEIGHT)));
+    let camera_sync = std::sync::Arc::new(parking_lot::RwLock::new(camera));
+    // where every entity should be drawn as of the newest frame, placed into the mesh by the remesh worker
+    let entity_instances_sync = std::sync::Arc::new(parking_lot::RwLock::new(Vec::<EntityInstance>::new()));
     
-    let processing_mutation_clone = processing_mutation.clone();
-    let waiting_for_chunk_update_clone = waiting_for_chunk_update.clone();
-    let thread_mesh = mesh.clone();
-    let (mesh_update_sender, mesh_update_receiver) = crossbeam::channel::unbounded::<((u32, u32), Float4, Float4)>();
-    let (mesh_complete_sender, mesh_complete_receiver) = crossbeam::channel::unbounded::<()>();
+    // rebuilt meshes go straight to the remesh worker, which owns whichever one is active
+    // and the one it replaces gets sent back so the next rebuild can reuse its allocations
+    let (new_mesh_sender, new_mesh_receiver) = crossbeam::channel::bounded::<Mesh>(1);
+    let (recycled_mesh_sender, recycled_mesh_receiver) = crossbeam::channel::bounded::<Mesh>(1);
+    let (mesh_update_sender, mesh_update_receiver) = crossbeam::channel::unbounded::<((u32, u32), Camera)>();
+    let entity_instances_sync_clone = entity_instances_sync.clone();
     let _mesh_handle = std::thread::spawn(move || {
-        let mut priority_cycle = 0;
+        let mut mesh: Option<Mesh> = None;
+        let mut last_request: Option<((u32, u32), Camera)> = None;
         loop {
-            if *waiting_for_chunk_update_clone.read() { continue; }
-            let (window_size, cam_rot, cam_pos) = match mesh_update_receiver.recv() {
-                Ok(v) => v,
-                Err(_) => break,
-            };  // blocking wait for a remesh signal
-            *processing_mutation_clone.write() = true;
-            thread_mesh.current().write().check_remesh(window_size, cam_pos, cam_rot, match priority_cycle % 4 {
-                0 => 0,
-                1 => 0,
-                2 => 0,
-                3 => 0,
-                _ => 0,
-            }, match priority_cycle % 4 {
-                0 => 6*0 + usize::MAX,
-                1 => 25*0 + usize::MAX,
-                2 => 55*0 + usize::MAX,
-                3 => 128*0 + usize::MAX,
-                _ => usize::MAX,
-            }, false);
-            mesh_complete_sender.send(()).unwrap();
-            priority_cycle += 1;
+            // blocking until there's either new geometry or a new view to draw it from
+            crossbeam::channel::select! {
+                recv(new_mesh_receiver) -> new_mesh => match new_mesh {
+                    Ok(new_mesh) => if let Some(old_mesh) = mesh.replace(new_mesh) {
+                        let _ = recycled_mesh_sender.try_send(old_mesh);  // if one's already waiting this one just gets dropped
+                    },
+                    Err(_) => break,
+                },
+                recv(mesh_update_receiver) -> request => match request {
+                    Ok(request) => last_request = Some(request),
+                    Err(_) => break,
+                },
+            }
+            // only the newest view matters if several requests queued up while remeshing
+            while let Ok(request) = mesh_update_receiver.try_recv() {
+                last_request = Some(request);
+            }
+            if let (Some(mesh), Some((window_size, camera))) = (&mut mesh, &last_request) {
+                mesh.set_entities(&entity_instances_sync_clone.read());
+                mesh.check_remesh(frame_writer.back_mut(), *window_size, camera, false);
+                frame_writer.publish();
+            }
         }
     });
     
-    let window_size_sync = std::sync::Arc::new(parking_lot::RwLock::new((WINDOW_START_WIDTH, WINDOW_START_HEIGHT)));
-    let camera_position_sync = std::sync::Arc::new(parking_lot::RwLock::new(camera_position.clone()));
-    let camera_rotation_sync = std::sync::Arc::new(parking_lot::RwLock::new(camera_rotation.clone()));
-    
-    let processing_mutation_clone = processing_mutation.clone();
-    let rebuild_mesh = mesh.clone();
     let (mesh_build_sender, mesh_build_receiver) = crossbeam::channel::unbounded::<()>();
     let window_size_sync_clone = window_size_sync.clone();
-    let camera_position_sync_clone = camera_position_sync.clone();
-    let camera_rotation_sync_clone = camera_rotation_sync.clone();
-    let waiting_for_chunk_update_clone = waiting_for_chunk_update.clone();
-    let chunks = std::sync::Arc::new(parking_lot::RwLock::new(chunks));
-    let chunks_clone = chunks.clone();
+    let camera_sync_clone = camera_sync.clone();
+    let mesh_update_sender_clone = mesh_update_sender.clone();
+    let world_clone = world.clone();
     let _mesh_rebuild_handle = std::thread::spawn(move || {
         loop {
             // either on timeout or on signal, remesh all chunks
             let _ = mesh_build_receiver.recv_timeout(std::time::Duration::from_secs(FORCED_REMESH_DELAY));
-            let write_lock = rebuild_mesh.back();
-            let mut write_lock = write_lock.write();
-            *write_lock = Mesh::new(
-                true,
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-                vec![Vertex::default(); MAX_VERTICES as usize],
-                vec![],
-                normals.clone(),
-                vec![0u32; 64 * (MAXIMUM_WINDOW_WIDTH / CELL_SIZE as u64) as usize * (MAXIMUM_WINDOW_HEIGHT / CELL_SIZE as u64) as usize],
-                vec![],
-                vec![],
-                vec![],
-            );
-            let cam_pos = *camera_position_sync_clone.read();
-            for chunk in &mut *chunks_clone.write() {
-                chunk.chunk_index = write_lock.chunk_ref().len();
-                write_lock.add_chunk(
-                    chunk.position,
-                    Float4::new(16.0, 16.0, 16.0, 0.0),
-                );
-                let distance = cam_pos.distance(&Float4::new(
-                    chunk.position.x + 8.0,
-                    chunk.position.y + 8.0,
-                    chunk.position.z + 8.0,
-                    0.0,
-                ));
-                chunk.remesh_chunk(&mut write_lock, distance as usize / 4, match distance {
-                    0.0..=32.0  => 0,
-                    32.0..=64.0 => 1,
-                    64.0..=128.0 => 2,
-                    128.0..=256.0 => 3,
-                    _ => 4,
-                });
-            }
-            write_lock.mutated(true);
-            write_lock.check_remesh(*window_size_sync_clone.read(), *camera_position_sync_clone.read(), *camera_rotation_sync_clone.read(), 0, usize::MAX, true);
-            rebuild_mesh.swap();
-            while *processing_mutation_clone.read() {
-                std::thread::sleep(std::time::Duration::from_micros(500));
+            while mesh_build_receiver.try_recv().is_ok() {}  // one rebuild covers every change signalled so far
+            let mut mesh = match recycled_mesh_receiver.try_recv() {
+                Ok(mut mesh) => {
+                    mesh.reset();
+                    mesh
+                },
+                Err(_) => Mesh::new(MeshParts { normals: normals.clone(), ..Default::default() }),
+            };
+            let cam_pos = camera_sync_clone.read().position.to_float4(0.0);
+            let rebuild_span = logging::span("meshing", "rebuild");
+            build_world_mesh(&mut mesh, &world_clone, cam_pos);
+            log_debug!("Rebuilt the world mesh in {:?} ({} triangles)", rebuild_span.elapsed(), mesh.indices_ref().len());
+            drop(rebuild_span);
+            if new_mesh_sender.send(mesh).is_err() {
+                break;
             }
-            drop(write_lock);
-            *waiting_for_chunk_update_clone.write() = true;
-            println!("\n\n\nRemeshed\n\n\n");
+            // making sure the new geometry gets drawn even if the camera is sitting still
+            let _ = mesh_update_sender_clone.send((*window_size_sync_clone.read(), *camera_sync_clone.read()));
             //break;
         }
     });
     mesh_build_sender.send(()).unwrap();
     
     // --- Main loop ---
+    let mut view_dirty = true;  // set whenever the camera or window changes so a new frame gets requested
+    let mut timestep = FixedTimestep::new(TICKS_PER_SECOND);
+    let mut player = Player::new(camera.position - Vec3::new(0.0, player::EYE_HEIGHT, 0.0));
+    let mut previous_camera_position = camera.position;  // where the camera was as of the tick before, for interpolating between ticks
+    // todo! spawn these from actual gameplay rather than a few test ones around the start
+    let mob_model = std::sync::Arc::new(Model::cuboid(Vec3::new(0.8, 0.9, 0.8), [1, 2, 2]));
+    let item_model = std::sync::Arc::new(Model::cuboid(Vec3::splat(0.25), [0, 0, 0]));
+    let projectile_model = std::sync::Arc::new(Model::cuboid(Vec3::splat(0.2), [2, 2, 2]));
+    let mut entities = Entities::new();
+    let mut block_updates = block_updates::BlockUpdates::new(seed);
+    let climate = biome::Climate::new(seed);  // the same one the world was generated with, for showing which biome the camera's in
+    for i in 0..4 {
+        entities.spawn(Entity::new(Vec3::new(6.0 + i as f32 * 3.0, 16.0, 10.0), 0.8, 0.9, mob_model.clone()).with_update(entity::wander_update));
+        entities.spawn(Entity::new(Vec3::new(7.0 + i as f32 * 3.0, 16.0, 6.0), 0.25, 0.25, item_model.clone()).with_update(entity::item_update));
+    }
+    let mut frame_stats = FrameStats::new();
+    let mut show_debug_overlay = false;
+    let mut selected_block = PLACEABLE_BLOCKS[0];
+    let mut last_frame = std::time::Instant::now();
     'running: loop {
         let frame_start = std::time::Instant::now();
+        let delta_time = frame_start.duration_since(last_frame).as_secs_f32().min(0.25);  // clamped so a stall doesn't fling the camera
+        last_frame = frame_start;
+        let mut timings = FrameTimings::default();
+        let _frame_span = logging::span("render", "frame");
+        
+        let mut camera_input = CameraInput::default();
         for event in event_pump.poll_iter() {
             match event {
                 sdl2::event::Event::Quit { .. } => break 'running,
-                sdl2::event::Event::KeyDown { keycode, .. } => {
-                    if let Some(key) = keycode {
-                        let mut movement = Float4::new(0.0, 0.0, 0.0, 0.0);
-                        let mut blend = Float4::new(1.0, 1.0, 1.0, 0.0);
-                        match key {
-                            sdl2::keyboard::Keycode::A => {
-                                movement.x += 4.0;
-                                blend.y = 0.0;
-                                mesh.current().write().mutated(true);
-                            },
-                            sdl2::keyboard::Keycode::D => {
-                                movement.x -= 4.0;
-                                blend.y = 0.0;
-                                mesh.current().write().mutated(true);
-                            },
-                            sdl2::keyboard::Keycode::W => {
-                                movement.z += 4.0;
-                                blend.y = 0.0;
-                                mesh.current().write().mutated(true);
-                            },
-                            sdl2::keyboard::Keycode::S => {
-                                movement.z -= 4.0;
-                                blend.y = 0.0;
-                                mesh.current().write().mutated(true);
-                            },
-                            sdl2::keyboard::Keycode::Space => {
-                                movement.y += 4.0;
-                                blend.x = 0.0;
-                                blend.z = 0.0;
-                                mesh.current().write().mutated(true);
-                            },
-                            sdl2::keyboard::Keycode::LSHIFT => {
-                                blend.x = 0.0;
-                                blend.z = 0.0;
-                                movement.y -= 4.0;
-                                mesh.current().write().mutated(true);
-                            },
-                            sdl2::keyboard::Keycode::Left => {
-                                camera_rotation.y -= 0.1;
-                                mesh.current().write().mutated(true);
-                            },
-                            sdl2::keyboard::Keycode::Right => {
-                                camera_rotation.y += 0.1;
-                                mesh.current().write().mutated(true);
-                            },
-                            sdl2::keyboard::Keycode::Up => {
-                                camera_rotation.x += 0.025;
-                                mesh.current().write().mutated(true);
-                            },
-                            sdl2::keyboard::Keycode::Down => {
-                                camera_rotation.x -= 0.025;
-                                mesh.current().write().mutated(true);
-                            },
-                            /*sdl2::keyboard::Keycode::Equals => {
-                                let current = *chunk_lod_res_temp.read();
-                                if current < 4 {
-                                    *chunk_lod_res_temp.write() = current + 1;
-                                    mesh_build_sender.send(()).unwrap();
-                                }
-                            },
-                            sdl2::keyboard::Keycode::MINUS => {
-                                let current = *chunk_lod_res_temp.read();
-                                if current > 0 {
-                                    *chunk_lod_res_temp.write() = current - 1;
-                                    mesh_build_sender.send(()).unwrap();
-                                }
-                            },*/
-                            _ => {}
-                        }
-                        let mut offset = rotate(movement, &camera_rotation.negate());
-                        offset.x = movement.x * (1.0 - blend.x) + offset.x * blend.x;
-                        offset.y = movement.y * (1.0 - blend.y) + offset.y * blend.y;
-                        offset.z = movement.z * (1.0 - blend.z) + offset.z * blend.z;
-                        camera_position.x += offset.x;
-                        camera_position.y += offset.y;
-                        camera_position.z += offset.z;
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::Escape), .. } => {
+                    mouse_captured = !mouse_captured;
+                    sdl.mouse().set_relative_mouse_mode(mouse_captured);
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::P), repeat: false, .. } => {
+                    camera.toggle_orthographic();
+                    view_dirty = true;
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::I), repeat: false, .. } => {
+                    camera.toggle_isometric();
+                    view_dirty = true;
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::N), repeat: false, .. } => {
+                    player.toggle_noclip(&mut camera);
+                    log_info!("Movement mode: {:?}", player.mode);
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::F), repeat: false, .. } => {
+                    // throwing a projectile from the eye along wherever the camera is looking
+                    let start = camera.position + camera.forward() * 0.5 - Vec3::new(0.0, 0.1, 0.0);
+                    entities.spawn(Entity::new(start, 0.2, 0.2, projectile_model.clone()).with_velocity(camera.forward() * 30.0).with_update(entity::projectile_update));
+                    view_dirty = true;
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(keycode @ (sdl2::keyboard::Keycode::J | sdl2::keyboard::Keycode::K | sdl2::keyboard::Keycode::G)), repeat: false, .. } => {
+                    // pouring a fluid source (or dropping sand) a few blocks in front of the camera
+                    let target = BlockPosition::containing(camera.position + camera.forward() * 3.0);
+                    let placed = match keycode {
+                        sdl2::keyboard::Keycode::J => fluid::Fluid::Water.block(),
+                        sdl2::keyboard::Keycode::K => fluid::Fluid::Lava.block(),
+                        _ => block::SAND,
+                    };
+                    let mut world = world.write();
+                    if world.block(target) == block::AIR && world.set_block(target, placed) {
+                        block_updates.block_changed(&world, target);
+                        mesh_build_sender.send(()).unwrap();
                     }
-                }
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(keycode), repeat: false, .. } if let Some(slot) = hotbar_slot(keycode) => {
+                    // the number keys pick from the blocks that can be placed
+                    selected_block = PLACEABLE_BLOCKS[slot];
+                    log_info!("Placing {}", block::properties(selected_block).name);
+                },
+                sdl2::event::Event::MouseButtonDown { mouse_btn: button @ (sdl2::mouse::MouseButton::Left | sdl2::mouse::MouseButton::Right), .. } if mouse_captured => {
+                    // breaking whatever's under the crosshair, or placing the selected block against the side of it that was clicked
+                    let mut world = world.write();
+                    let Some(hit) = world.raycast(camera.position, camera.forward(), BLOCK_REACH) else {
+                        continue;
+                    };
+                    let (target, cell) = if button == sdl2::mouse::MouseButton::Left {
+                        (hit.position, block::AIR)
+                    } else {
+                        let height = hit.point.y - (hit.position.y as f32 - 0.5);
+                        (hit.position.beside(hit.face), block_state::placed(selected_block, hit.face, height, camera.forward()))
+                    };
+                    let replaceable = button == sdl2::mouse::MouseButton::Left || world.block(target) == block::AIR || block::fluid(world.block(target)).is_some();
+                    // nothing solid gets placed where the player's standing
+                    let blocked = block::is_solid(cell) && physics::BoundingBox::of_block(target).overlaps(player.bounds());
+                    if replaceable && !blocked && world.set_block(target, cell) {
+                        block_updates.block_changed(&world, target);
+                        mesh_build_sender.send(()).unwrap();
+                    }
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::F3), repeat: false, .. } => {
+                    show_debug_overlay = !show_debug_overlay;
+                },
+                sdl2::event::Event::MouseWheel { y, .. } => {
+                    camera_input.zoom += y as f32 * camera.controls.zoom_speed * 0.1;
+                },
+                sdl2::event::Event::MouseMotion { xrel, yrel, .. } if mouse_captured => {
+                    camera_input.yaw += xrel as f32 * camera.controls.mouse_sensitivity;
+                    camera_input.pitch -= yrel as f32 * camera.controls.mouse_sensitivity;
+                },
                 _ => {}
             }
         }
         
+        // movement is driven by the held keys rather than key repeat events so it's smooth and frame rate independent
+        let keyboard = event_pump.keyboard_state();
+        let axis = |positive: sdl2::keyboard::Scancode, negative: sdl2::keyboard::Scancode| -> f32 {
+            keyboard.is_scancode_pressed(positive) as i32 as f32 - keyboard.is_scancode_pressed(negative) as i32 as f32
+        };
+        camera_input.forward = axis(sdl2::keyboard::Scancode::W, sdl2::keyboard::Scancode::S);
+        camera_input.left = axis(sdl2::keyboard::Scancode::A, sdl2::keyboard::Scancode::D);
+        camera_input.up = axis(sdl2::keyboard::Scancode::Space, sdl2::keyboard::Scancode::LShift);
+        camera_input.sprint = keyboard.is_scancode_pressed(sdl2::keyboard::Scancode::LCtrl);
+        camera_input.yaw += axis(sdl2::keyboard::Scancode::Right, sdl2::keyboard::Scancode::Left) * camera.controls.turn_speed * delta_time;
+        camera_input.pitch += axis(sdl2::keyboard::Scancode::Up, sdl2::keyboard::Scancode::Down) * camera.controls.turn_speed * delta_time;
+        if camera.look(&camera_input, delta_time) {
+            view_dirty = true;
+        }
+        
+        // running however many simulation ticks fit in the time that's passed
+        timings.ticks = timestep.advance(frame_start);
+        let mut chunks_changed = 0;
+        let mut entities_despawned = 0;
+        for _ in 0..timings.ticks {
+            previous_camera_position = camera.position;
+            let mut world = world.write();
+            player.step(&world, &camera_input, &mut camera, timestep.tick_seconds());
+            entities_despawned += entities.tick(&world, timestep.tick_seconds());
+            chunks_changed += block_updates.tick(&mut world);
+        }
+        // only the animated textures that moved on a frame get uploaded again, rather than the whole atlas
+        for index in atlas.animate(timestep.tick()) {
+            shader_handler.get_shader().update_buffer_range(9, index as usize * texture_atlas::TEXELS_PER_TEXTURE, atlas.texture_texels(index))?;
+        }
+        // only the chunks the changes were in get remeshed (see `Chunk::mutated`), the rest reuse their cached geometry,
+        // but the world mesh is still put back together and uploaded as a whole
+        if chunks_changed > 0 {
+            mesh_build_sender.send(()).unwrap();
+        }
+        // while moving the drawn position changes every frame, even between ticks
+        if previous_camera_position != camera.position || entities.moving() || entities_despawned > 0 {
+            view_dirty = true;
+        }
+        let render_camera = camera.interpolated(previous_camera_position, timestep.alpha());
+        if view_dirty {
+            *entity_instances_sync.write() = entities.instances(timestep.alpha());
+        }
+        
         // checking the surface texture's size
         let window_size = window_surface.output_size()?;
         if surface_texture_size != window_size {
-            mesh.current().write().mutated(true);
+            view_dirty = true;
             surface_texture = texture_creator
                 .create_texture(PixelFormatEnum::RGB24, TextureAccess::Streaming, window_size.0, window_size.1)
                 .map_err(|e| e.to_string())?;
@@ -361,8 +688,7 @@ pub fn main() -> Result<(), String> {
         }
         
         *window_size_sync.write() = window_size;
-        *camera_position_sync.write() = camera_position;
-        *camera_rotation_sync.write() = camera_rotation;
+        *camera_sync.write() = render_camera;
         
         // !====! Do Rendering Here! !====!
         
@@ -370,7 +696,8 @@ pub fn main() -> Result<(), String> {
         // creating a pixel buffer to pass around to reduce draw calls as the cpu is faster than repeatedly waiting for the gpu to return data
         // the gpu is fast, but data moves between the gpu and cpu slowly
         let _buffer_result = surface_texture.with_lock(None, |pixels, pitch| {
-            let start = std::time::Instant::now();
+            let mut start = std::time::Instant::now();
+            let upload_span = logging::span("render", "upload");
             pixels.fill(0);  // clearing the pixel buffer
             shader_handler.get_shader().update_buffer(0, pitch as u32  ).unwrap();
             shader_handler.get_shader().update_buffer(1, window_size.0 ).unwrap();
@@ -391,20 +718,25 @@ pub fn main() -> Result<(), String> {
                 metal::NSUInteger::from(1_u64),
             );
             
-            let execution_start = start.elapsed();
+            timings.upload = start.elapsed();
+            drop(upload_span);
+            start = std::time::Instant::now();
+            let execute_span = logging::span("render", "execute");
             
             shader_handler.get_shader().execute(grid_size, thread_group_size, Some(|| {
                 // runs while rendering is happening
                 
-                // checking if the mesh was mutated, and if so, sending a remesh signal for the background thread
-                if let Some(mesh) = mesh.current().try_read() {
-                    if mesh.was_mutated() {
-                        mesh_update_sender.send((window_size, camera_rotation.clone(), camera_position.clone())).unwrap();
-                    }
+                // if the view changed, sending a remesh signal for the background thread
+                if view_dirty {
+                    view_dirty = false;
+                    mesh_update_sender.send((window_size, render_camera)).unwrap();
                 }
             }));
             
-            let execution_end = start.elapsed() - execution_start;
+            timings.execute = start.elapsed();
+            drop(execute_span);
+            start = std::time::Instant::now();
+            let _read_back_span = logging::span("render", "read_back");
             
             let contents: *mut &[u8] = shader_handler.get_shader().get_buffer_contents(11);
             if contents.is_null() { panic!("Null pointer when unwrapping shader pixel result for triangle rendering."); }
@@ -414,30 +746,55 @@ pub fn main() -> Result<(), String> {
                 }
             );
             
-            // checking if the background thread finished remeshing (try_recv so it doesn't block if it's still processing)
-            if mesh_complete_receiver.try_recv().is_ok() || *waiting_for_chunk_update.read() {
-                if !*processing_mutation.read() && *waiting_for_chunk_update.read() { mesh.update(); }
-                *waiting_for_chunk_update.write() = false;
-                *processing_mutation.write() = false;
-                mesh.current().read().update_shader_buffers(&mut shader_handler, window_size, camera_rotation.clone());
-                *processing_mutation.write() = false;
+            // picking up the newest finished frame, if the background thread published one (never blocks)
+            if frame_reader.acquire() {
+                frame_reader.front().update_shader_buffers(&mut shader_handler);
             }
             
-            let total_end = start.elapsed() - execution_end - execution_start;
-            println!("Buffer Upload Time: {:?}, Execution Time: {:?}, Read In Time: {:?}", execution_start, execution_end, total_end);
+            if show_debug_overlay {
+                let _overlay_span = logging::span("render", "debug_overlay");
+                let looking_at = {
+                    let world = world.read();
+                    match world.raycast(render_camera.position, render_camera.forward(), BLOCK_REACH) {
+                        Some(hit) => format!("{} at {} {} {}", block_state::describe(world.block(hit.position)), hit.position.x, hit.position.y, hit.position.z),
+                        None => "nothing".to_string(),
+                    }
+                };
+                let (temperature, humidity) = climate.at(render_camera.position.x.round() as i32, render_camera.position.z.round() as i32);
+                let biome = format!("{}  temperature {:.2}  humidity {:.2}", biome::name(temperature, humidity), temperature, humidity);
+                let mut target = debug_overlay::PixelTarget::new(pixels, pitch, window_size.0 as usize, window_size.1 as usize);
+                debug_overlay::draw_debug_overlay(&mut target, &debug_overlay::OverlayInfo {
+                    frame_stats: &frame_stats,
+                    camera: &render_camera,
+                    remesh: &frame_reader.front().stats,
+                    looking_at: &looking_at,
+                    selected: block::properties(selected_block).name,
+                    biome: &biome,
+                });
+            }
+            
+            timings.read_back = start.elapsed();
         })?;
         
         // !====! No Rendering Beyond Here !====!
         
         // clearing and drawing the texture
+        let present_span = logging::span("render", "present");
         window_surface.clear();
         window_surface.copy(&surface_texture, None, Rect::new(0, 0, window_size.0, window_size.1))?;
 
         // flushing the screen and stuff
         window_surface.present();
+        drop(present_span);
         
-        let frame_time = frame_start.elapsed();
-        println!("Frame Time: {:?}\nFPS: {}", frame_time, 1.0 / frame_time.as_secs_f32());
+        if let Some(min_frame_time) = loop_settings.min_frame_time() {
+            game_loop::wait_for_frame_end(frame_start, min_frame_time);
+        }
+        timings.frame = frame_start.elapsed();
+        frame_stats.record(timings);
+        if let Some(report) = frame_stats.report(FRAME_STATS_REPORT_INTERVAL) {
+            log_info!("{}", report);
+        }
     }
-    Ok(())
+    finish_tracing(&trace_path)
 }
@@ -0,0 +1,620 @@
+use crate::shader_handling::Float4;
+
+// the cpu side linear algebra
+// the gpu facing types (Float4, Vertex, etc.) stay as plain #[repr(C)] structs in shader_handling
+// and get converted at the edges
+
+/// A 3 component vector for positions, directions and normals
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec3 {
+    pub const ZERO: Vec3 = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    pub const X: Vec3 = Vec3 { x: 1.0, y: 0.0, z: 0.0 };
+    pub const Y: Vec3 = Vec3 { x: 0.0, y: 1.0, z: 0.0 };
+    pub const Z: Vec3 = Vec3 { x: 0.0, y: 0.0, z: 1.0 };
+    
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Vec3 { x, y, z }
+    }
+    
+    pub fn splat(value: f32) -> Self {
+        Vec3 { x: value, y: value, z: value }
+    }
+    
+    pub fn dot(self, other: Vec3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+    
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        Vec3 {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
+    
+    pub fn length_squared(self) -> f32 {
+        self.dot(self)
+    }
+    
+    pub fn length(self) -> f32 {
+        self.length_squared().sqrt()
+    }
+    
+    pub fn distance(self, other: Vec3) -> f32 {
+        (self - other).length()
+    }
+    
+    /// Returns the zero vector rather than NaNs for a zero length input
+    pub fn normalized(self) -> Vec3 {
+        let length = self.length();
+        if length == 0.0 {
+            Vec3::ZERO
+        } else {
+            self / length
+        }
+    }
+    
+    pub fn min(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x.min(other.x), self.y.min(other.y), self.z.min(other.z))
+    }
+    
+    pub fn max(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x.max(other.x), self.y.max(other.y), self.z.max(other.z))
+    }
+    
+    pub fn lerp(self, other: Vec3, t: f32) -> Vec3 {
+        self + (other - self) * t
+    }
+    
+    pub fn extend(self, w: f32) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, w)
+    }
+    
+    pub fn to_float4(self, w: f32) -> Float4 {
+        Float4::new(self.x, self.y, self.z, w)
+    }
+}
+
+impl From<Float4> for Vec3 {
+    fn from(value: Float4) -> Self {
+        Vec3::new(value.x, value.y, value.z)
+    }
+}
+
+/// A 4 component vector, mostly for homogeneous coordinates
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vec4 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Vec4 {
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Vec4 { x, y, z, w }
+    }
+    
+    pub fn dot(self, other: Vec4) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+    
+    pub fn xyz(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+    
+    pub fn to_float4(self) -> Float4 {
+        Float4::new(self.x, self.y, self.z, self.w)
+    }
+}
+
+impl From<Float4> for Vec4 {
+    fn from(value: Float4) -> Self {
+        Vec4::new(value.x, value.y, value.z, value.w)
+    }
+}
+
+macro_rules! impl_vector_ops {
+    ($name:ident { $($field:ident),+ }) => {
+        impl std::ops::Add for $name {
+            type Output = $name;
+            fn add(self, other: $name) -> $name { $name { $($field: self.$field + other.$field),+ } }
+        }
+        impl std::ops::Sub for $name {
+            type Output = $name;
+            fn sub(self, other: $name) -> $name { $name { $($field: self.$field - other.$field),+ } }
+        }
+        impl std::ops::Mul<f32> for $name {
+            type Output = $name;
+            fn mul(self, scalar: f32) -> $name { $name { $($field: self.$field * scalar),+ } }
+        }
+        impl std::ops::Mul<$name> for f32 {
+            type Output = $name;
+            fn mul(self, vector: $name) -> $name { $name { $($field: self * vector.$field),+ } }
+        }
+        impl std::ops::Div<f32> for $name {
+            type Output = $name;
+            fn div(self, scalar: f32) -> $name { $name { $($field: self.$field / scalar),+ } }
+        }
+        impl std::ops::Neg for $name {
+            type Output = $name;
+            fn neg(self) -> $name { $name { $($field: -self.$field),+ } }
+        }
+        impl std::ops::AddAssign for $name {
+            fn add_assign(&mut self, other: $name) { $(self.$field += other.$field;)+ }
+        }
+        impl std::ops::SubAssign for $name {
+            fn sub_assign(&mut self, other: $name) { $(self.$field -= other.$field;)+ }
+        }
+        impl std::ops::MulAssign<f32> for $name {
+            fn mul_assign(&mut self, scalar: f32) { $(self.$field *= scalar;)+ }
+        }
+    };
+}
+
+impl_vector_ops!(Vec3 { x, y, z });
+impl_vector_ops!(Vec4 { x, y, z, w });
+
+/// A rotation stored as a unit quaternion (x, y, z imaginary, w real)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::IDENTITY
+    }
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat { x: 0.0, y: 0.0, z: 0.0, w: 1.0 };
+    
+    /// A rotation of `angle` radians around `axis` (which doesn't need to be normalized)
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Self {
+        let axis = axis.normalized();
+        let (sin, cos) = (angle * 0.5).sin_cos();
+        Quat { x: axis.x * sin, y: axis.y * sin, z: axis.z * sin, w: cos }
+    }
+    
+    /// The same rotation the old euler camera used: Rx * Ry * Rz (z is applied to the vector first)
+    pub fn from_euler(x: f32, y: f32, z: f32) -> Self {
+        Quat::from_axis_angle(Vec3::X, x) * Quat::from_axis_angle(Vec3::Y, y) * Quat::from_axis_angle(Vec3::Z, z)
+    }
+    
+    pub fn conjugate(self) -> Quat {
+        Quat { x: -self.x, y: -self.y, z: -self.z, w: self.w }
+    }
+    
+    pub fn length(self) -> f32 {
+        (self.x * self.x + self.y * self.y + self.z * self.z + self.w * self.w).sqrt()
+    }
+    
+    pub fn normalized(self) -> Quat {
+        let length = self.length();
+        if length == 0.0 {
+            return Quat::IDENTITY;
+        }
+        Quat { x: self.x / length, y: self.y / length, z: self.z / length, w: self.w / length }
+    }
+    
+    /// Rotates a vector (v' = q v q*), expanded to avoid building the intermediate quaternions
+    pub fn rotate(self, vector: Vec3) -> Vec3 {
+        let q = Vec3::new(self.x, self.y, self.z);
+        let t = q.cross(vector) * 2.0;
+        vector + t * self.w + q.cross(t)
+    }
+    
+    /// Spherical interpolation along the shortest arc
+    pub fn slerp(self, other: Quat, t: f32) -> Quat {
+        let mut cos_theta = self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w;
+        let other = if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            Quat { x: -other.x, y: -other.y, z: -other.z, w: -other.w }
+        } else {
+            other
+        };
+        // nearly parallel rotations fall back to a normalized lerp to avoid dividing by ~0
+        let (a, b) = if cos_theta > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (((1.0 - t) * theta).sin() / sin_theta, (t * theta).sin() / sin_theta)
+        };
+        Quat {
+            x: self.x * a + other.x * b,
+            y: self.y * a + other.y * b,
+            z: self.z * a + other.z * b,
+            w: self.w * a + other.w * b,
+        }.normalized()
+    }
+}
+
+impl std::ops::Mul for Quat {
+    type Output = Quat;
+    /// Hamilton product; `a * b` applies `b` first, then `a`
+    fn mul(self, other: Quat) -> Quat {
+        Quat {
+            x: self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            y: self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            z: self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+            w: self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+        }
+    }
+}
+
+/// A row major 4x4 matrix used with column vectors (`m * v`), the same layout the old Mat4x4 had
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::IDENTITY
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]
+    };
+    
+    pub fn translation(offset: Vec3) -> Mat4 {
+        Mat4 {
+            m: [
+                [1.0, 0.0, 0.0, offset.x],
+                [0.0, 1.0, 0.0, offset.y],
+                [0.0, 0.0, 1.0, offset.z],
+                [0.0, 0.0, 0.0, 1.0     ],
+            ]
+        }
+    }
+    
+    #[cfg(test)]
+    pub fn scale(scale: Vec3) -> Mat4 {
+        Mat4 {
+            m: [
+                [scale.x, 0.0,     0.0,     0.0],
+                [0.0,     scale.y, 0.0,     0.0],
+                [0.0,     0.0,     scale.z, 0.0],
+                [0.0,     0.0,     0.0,     1.0],
+            ]
+        }
+    }
+    
+    pub fn from_quat(rotation: Quat) -> Mat4 {
+        let Quat { x, y, z, w } = rotation;
+        let (xx, yy, zz) = (x * x, y * y, z * z);
+        let (xy, xz, yz) = (x * y, x * z, y * z);
+        let (wx, wy, wz) = (w * x, w * y, w * z);
+        Mat4 {
+            m: [
+                [1.0 - 2.0 * (yy + zz), 2.0 * (xy - wz),       2.0 * (xz + wy),       0.0],
+                [2.0 * (xy + wz),       1.0 - 2.0 * (xx + zz), 2.0 * (yz - wx),       0.0],
+                [2.0 * (xz - wy),       2.0 * (yz + wx),       1.0 - 2.0 * (xx + yy), 0.0],
+                [0.0,                   0.0,                   0.0,                   1.0],
+            ]
+        }
+    }
+    
+    /// The camera's view transform: rotation * (p - eye)
+    pub fn view(eye: Vec3, rotation: Quat) -> Mat4 {
+        Mat4::from_quat(rotation) * Mat4::translation(-eye)
+    }
+    
+    /// A view matrix looking from `eye` towards `target`
+    /// view space keeps the renderer's convention of +z forward and +y up
+    #[cfg(test)]
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Mat4 {
+        let forward = (target - eye).normalized();
+        let right = up.cross(forward).normalized();
+        let up = forward.cross(right);
+        Mat4 {
+            m: [
+                [right.x,   right.y,   right.z,   -right.dot(eye)  ],
+                [up.x,      up.y,      up.z,      -up.dot(eye)     ],
+                [forward.x, forward.y, forward.z, -forward.dot(eye)],
+                [0.0,       0.0,       0.0,       1.0              ],
+            ]
+        }
+    }
+    
+    /// Perspective projection for a +z forward view space
+    /// x and y are negated so the ndc (and so the screen mapping) matches what the old -z matrix produced,
+    /// while w stays positive for everything in front of the camera so clip space tests work as usual
+    /// depth maps near..far to -1..1
+    pub fn perspective(fov_y_radians: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
+        let f = 1.0 / (fov_y_radians * 0.5).tan();
+        Mat4 {
+            m: [
+                [-f / aspect, 0.0, 0.0,                         0.0                             ],
+                [0.0,         -f,  0.0,                         0.0                             ],
+                [0.0,         0.0, (far + near) / (far - near), -(2.0 * far * near) / (far - near)],
+                [0.0,         0.0, 1.0,                         0.0                             ],
+            ]
+        }
+    }
+    
+    /// Orthographic projection for the same +z forward view space, with the same x/y flip as `perspective`
+    /// so switching between the two doesn't mirror the screen; w is always 1
+    pub fn orthographic(half_width: f32, half_height: f32, near: f32, far: f32) -> Mat4 {
+        Mat4 {
+            m: [
+                [-1.0 / half_width, 0.0,                0.0,                 0.0                           ],
+                [0.0,               -1.0 / half_height, 0.0,                 0.0                           ],
+                [0.0,               0.0,                2.0 / (far - near),  -(far + near) / (far - near)  ],
+                [0.0,               0.0,                0.0,                 1.0                           ],
+            ]
+        }
+    }
+    
+    #[cfg(test)]
+    pub fn transpose(&self) -> Mat4 {
+        let mut result = Mat4 { m: [[0.0; 4]; 4] };
+        for row in 0..4 {
+            for column in 0..4 {
+                result.m[row][column] = self.m[column][row];
+            }
+        }
+        result
+    }
+    
+    pub fn row(&self, row: usize) -> Vec4 {
+        Vec4::new(self.m[row][0], self.m[row][1], self.m[row][2], self.m[row][3])
+    }
+    
+    pub fn mul_vec4(&self, v: Vec4) -> Vec4 {
+        let m = &self.m;
+        Vec4 {
+            x: m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z + m[0][3] * v.w,
+            y: m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z + m[1][3] * v.w,
+            z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z + m[2][3] * v.w,
+            w: m[3][0] * v.x + m[3][1] * v.y + m[3][2] * v.z + m[3][3] * v.w,
+        }
+    }
+    
+    /// Transforms a point (w = 1) ignoring the projective row
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.mul_vec4(point.extend(1.0)).xyz()
+    }
+    
+    /// Transforms a direction (w = 0) so translation doesn't apply
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        self.mul_vec4(vector.extend(0.0)).xyz()
+    }
+    
+    /// The general inverse through 2x2 sub determinants; None for singular matrices
+    #[cfg(test)]
+    pub fn inverse(&self) -> Option<Mat4> {
+        let m = &self.m;
+        let s0 = m[0][0] * m[1][1] - m[1][0] * m[0][1];
+        let s1 = m[0][0] * m[1][2] - m[1][0] * m[0][2];
+        let s2 = m[0][0] * m[1][3] - m[1][0] * m[0][3];
+        let s3 = m[0][1] * m[1][2] - m[1][1] * m[0][2];
+        let s4 = m[0][1] * m[1][3] - m[1][1] * m[0][3];
+        let s5 = m[0][2] * m[1][3] - m[1][2] * m[0][3];
+        let c5 = m[2][2] * m[3][3] - m[3][2] * m[2][3];
+        let c4 = m[2][1] * m[3][3] - m[3][1] * m[2][3];
+        let c3 = m[2][1] * m[3][2] - m[3][1] * m[2][2];
+        let c2 = m[2][0] * m[3][3] - m[3][0] * m[2][3];
+        let c1 = m[2][0] * m[3][2] - m[3][0] * m[2][2];
+        let c0 = m[2][0] * m[3][1] - m[3][0] * m[2][1];
+        
+        let determinant = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if determinant.abs() <= f32::EPSILON {
+            return None;
+        }
+        let inv = 1.0 / determinant;
+        Some(Mat4 {
+            m: [
+                [
+                    ( m[1][1] * c5 - m[1][2] * c4 + m[1][3] * c3) * inv,
+                    (-m[0][1] * c5 + m[0][2] * c4 - m[0][3] * c3) * inv,
+                    ( m[3][1] * s5 - m[3][2] * s4 + m[3][3] * s3) * inv,
+                    (-m[2][1] * s5 + m[2][2] * s4 - m[2][3] * s3) * inv,
+                ],
+                [
+                    (-m[1][0] * c5 + m[1][2] * c2 - m[1][3] * c1) * inv,
+                    ( m[0][0] * c5 - m[0][2] * c2 + m[0][3] * c1) * inv,
+                    (-m[3][0] * s5 + m[3][2] * s2 - m[3][3] * s1) * inv,
+                    ( m[2][0] * s5 - m[2][2] * s2 + m[2][3] * s1) * inv,
+                ],
+                [
+                    ( m[1][0] * c4 - m[1][1] * c2 + m[1][3] * c0) * inv,
+                    (-m[0][0] * c4 + m[0][1] * c2 - m[0][3] * c0) * inv,
+                    ( m[3][0] * s4 - m[3][1] * s2 + m[3][3] * s0) * inv,
+                    (-m[2][0] * s4 + m[2][1] * s2 - m[2][3] * s0) * inv,
+                ],
+                [
+                    (-m[1][0] * c3 + m[1][1] * c1 - m[1][2] * c0) * inv,
+                    ( m[0][0] * c3 - m[0][1] * c1 + m[0][2] * c0) * inv,
+                    (-m[3][0] * s3 + m[3][1] * s1 - m[3][2] * s0) * inv,
+                    ( m[2][0] * s3 - m[2][1] * s1 + m[2][2] * s0) * inv,
+                ],
+            ]
+        })
+    }
+}
+
+impl std::ops::Mul for Mat4 {
+    type Output = Mat4;
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut result = Mat4 { m: [[0.0; 4]; 4] };
+        for row in 0..4 {
+            for column in 0..4 {
+                result.m[row][column] =
+                    self.m[row][0] * other.m[0][column] +
+                    self.m[row][1] * other.m[1][column] +
+                    self.m[row][2] * other.m[2][column] +
+                    self.m[row][3] * other.m[3][column];
+            }
+        }
+        result
+    }
+}
+
+impl std::ops::Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+    fn mul(self, v: Vec4) -> Vec4 {
+        self.mul_vec4(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const EPSILON: f32 = 1e-5;
+    
+    fn assert_vec3(actual: Vec3, expected: Vec3) {
+        assert!((actual - expected).length() < EPSILON, "expected {:?}, got {:?}", expected, actual);
+    }
+    
+    fn assert_mat4(actual: Mat4, expected: Mat4) {
+        for row in 0..4 {
+            for column in 0..4 {
+                // products of whole matrices (like a projection out to 500) round off more than single vectors do
+                assert!((actual.m[row][column] - expected.m[row][column]).abs() < 1e-4, "expected {:?}, got {:?}", expected, actual);
+            }
+        }
+    }
+    
+    /// The same rotation, up to the sign of the whole quaternion
+    fn assert_quat(actual: Quat, expected: Quat) {
+        let dot = actual.x * expected.x + actual.y * expected.y + actual.z * expected.z + actual.w * expected.w;
+        assert!((dot.abs() - 1.0).abs() < EPSILON, "expected {:?}, got {:?}", expected, actual);
+    }
+    
+    /// Just the screen position of a projected point
+    fn flatten(point: Vec3) -> Vec3 {
+        Vec3::new(point.x, point.y, 0.0)
+    }
+    
+    fn rotation_x(angle: f32) -> Mat4 {
+        let (sin, cos) = angle.sin_cos();
+        Mat4 { m: [[1.0, 0.0, 0.0, 0.0], [0.0, cos, -sin, 0.0], [0.0, sin, cos, 0.0], [0.0, 0.0, 0.0, 1.0]] }
+    }
+    
+    fn rotation_y(angle: f32) -> Mat4 {
+        let (sin, cos) = angle.sin_cos();
+        Mat4 { m: [[cos, 0.0, sin, 0.0], [0.0, 1.0, 0.0, 0.0], [-sin, 0.0, cos, 0.0], [0.0, 0.0, 0.0, 1.0]] }
+    }
+    
+    fn rotation_z(angle: f32) -> Mat4 {
+        let (sin, cos) = angle.sin_cos();
+        Mat4 { m: [[cos, -sin, 0.0, 0.0], [sin, cos, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]] }
+    }
+    
+    #[test]
+    fn quaternions_rotate_like_the_matching_matrices() {
+        let angle = 0.7;
+        assert_mat4(Mat4::from_quat(Quat::from_axis_angle(Vec3::X, angle)), rotation_x(angle));
+        assert_mat4(Mat4::from_quat(Quat::from_axis_angle(Vec3::Y, angle)), rotation_y(angle));
+        assert_mat4(Mat4::from_quat(Quat::from_axis_angle(Vec3::Z, angle)), rotation_z(angle));
+        // a quarter turn around y takes +x to -z
+        assert_vec3(Quat::from_axis_angle(Vec3::Y, std::f32::consts::FRAC_PI_2).rotate(Vec3::X), -Vec3::Z);
+        
+        let (x, y, z) = (0.3, -1.1, 2.4);
+        let euler = Quat::from_euler(x, y, z);
+        assert_mat4(Mat4::from_quat(euler), rotation_x(x) * rotation_y(y) * rotation_z(z));
+        for vector in [Vec3::X, Vec3::Y, Vec3::Z, Vec3::new(1.5, -2.0, 0.25)] {
+            assert_vec3(euler.rotate(vector), Mat4::from_quat(euler).transform_vector(vector));
+        }
+    }
+    
+    #[test]
+    fn a_matrix_times_its_inverse_is_the_identity() {
+        let transform = Mat4::translation(Vec3::new(3.0, -7.0, 12.5)) * Mat4::from_quat(Quat::from_euler(0.4, 1.2, -0.3)) * Mat4::scale(Vec3::new(2.0, 0.5, 3.0));
+        let projection = Mat4::perspective(1.2, 1.6, 0.1, 500.0);
+        for matrix in [transform, projection, projection * transform, Mat4::orthographic(8.0, 5.0, 0.1, 100.0)] {
+            let inverse = matrix.inverse().expect("should be invertible");
+            assert_mat4(matrix * inverse, Mat4::IDENTITY);
+            assert_mat4(inverse * matrix, Mat4::IDENTITY);
+        }
+        assert_eq!(Mat4::scale(Vec3::new(1.0, 0.0, 1.0)).inverse(), None);
+        assert_mat4(transform.transpose().transpose(), transform);
+    }
+    
+    #[test]
+    fn look_at_lines_the_view_up_with_the_target() {
+        // looking down -z turns the view around, so +x ends up on the left
+        let (eye, target) = (Vec3::new(1.0, 2.0, 3.0), Vec3::new(1.0, 2.0, -7.0));
+        let view = Mat4::look_at(eye, target, Vec3::Y);
+        assert_vec3(view.row(0).xyz(), -Vec3::X);
+        assert_vec3(view.row(1).xyz(), Vec3::Y);
+        assert_vec3(view.row(2).xyz(), -Vec3::Z);
+        assert_vec3(view.transform_point(eye), Vec3::ZERO);
+        assert_vec3(view.transform_point(target), Vec3::new(0.0, 0.0, 10.0));
+        assert_vec3(view.transform_point(eye + Vec3::X), Vec3::new(-1.0, 0.0, 0.0));
+        
+        // from off to the side and above, the basis stays orthonormal and the target lands straight ahead
+        let (eye, target) = (Vec3::new(-4.0, 9.0, 2.0), Vec3::new(6.0, 1.0, -3.0));
+        let view = Mat4::look_at(eye, target, Vec3::Y);
+        let (right, up, forward) = (view.row(0).xyz(), view.row(1).xyz(), view.row(2).xyz());
+        for (a, b) in [(right, up), (up, forward), (forward, right)] {
+            assert!(a.dot(b).abs() < EPSILON);
+        }
+        assert_vec3(right.cross(up), forward);
+        assert!(up.y > 0.0, "up should still point up");
+        assert_vec3(view.transform_point(target), Vec3::new(0.0, 0.0, eye.distance(target)));
+    }
+    
+    #[test]
+    fn perspective_maps_near_and_far_to_the_ends_of_the_depth_range() {
+        let (near, far) = (0.1, 250.0);
+        let projection = Mat4::perspective(90f32.to_radians(), 2.0, near, far);
+        let ndc = |point: Vec3| {
+            let clip = projection.mul_vec4(point.extend(1.0));
+            assert!(clip.w > 0.0, "w should be positive in front of the camera");
+            clip.xyz() / clip.w
+        };
+        assert!((ndc(Vec3::new(0.0, 0.0, near)).z + 1.0).abs() < EPSILON);
+        assert!((ndc(Vec3::new(0.0, 0.0, far)).z - 1.0).abs() < 1e-4);
+        // the edges of a 90 degree view at a distance of 10 are 10 up and 20 across (the aspect's 2), and x and y come out negated
+        assert_vec3(flatten(ndc(Vec3::new(20.0, 10.0, 10.0))), Vec3::new(-1.0, -1.0, 0.0));
+        assert_vec3(flatten(ndc(Vec3::new(-20.0, -10.0, 10.0))), Vec3::new(1.0, 1.0, 0.0));
+    }
+    
+    #[test]
+    fn orthographic_maps_near_and_far_to_the_ends_of_the_depth_range() {
+        let (near, far) = (0.5, 100.0);
+        let projection = Mat4::orthographic(8.0, 4.5, near, far);
+        let clip = |point: Vec3| projection.mul_vec4(point.extend(1.0));
+        assert_eq!(clip(Vec3::new(3.0, 1.0, 40.0)).w, 1.0);
+        assert!((clip(Vec3::new(0.0, 0.0, near)).z + 1.0).abs() < EPSILON);
+        assert!((clip(Vec3::new(0.0, 0.0, far)).z - 1.0).abs() < EPSILON);
+        // flipped the same way as the perspective projection
+        assert_vec3(flatten(clip(Vec3::new(8.0, 4.5, 10.0)).xyz()), Vec3::new(-1.0, -1.0, 0.0));
+    }
+    
+    #[test]
+    fn slerp_goes_from_one_rotation_to_the_other_along_the_shortest_arc() {
+        let (from, to) = (Quat::from_axis_angle(Vec3::Y, 0.2), Quat::from_axis_angle(Vec3::Y, 1.4));
+        assert_quat(from.slerp(to, 0.0), from);
+        assert_quat(from.slerp(to, 1.0), to);
+        assert_quat(from.slerp(to, 0.5), Quat::from_axis_angle(Vec3::Y, 0.8));
+        assert_quat(from.slerp(to, 0.25), Quat::from_axis_angle(Vec3::Y, 0.5));
+        // the same rotation as `to` with the signs flipped still takes the short way round
+        let flipped = Quat { x: -to.x, y: -to.y, z: -to.z, w: -to.w };
+        assert_quat(from.slerp(flipped, 0.5), Quat::from_axis_angle(Vec3::Y, 0.8));
+        // nearly the same rotation falls back to the normalized lerp without producing NaNs
+        let nearly = Quat::from_axis_angle(Vec3::Y, 0.2001);
+        assert!((from.slerp(nearly, 0.5).length() - 1.0).abs() < EPSILON);
+    }
+}
@@ -0,0 +1,95 @@
+// keeping track of how much the heap is using, so rebuilds can report how much memory they actually needed
+// the counting costs a few atomics on every allocation, so it's only installed when built with `--features alloc-stats`
+static ALLOCATED: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+static PEAK_ALLOCATED: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// Wraps the system allocator, counting the live bytes and the high-water mark
+/// installed with `#[global_allocator]` in main
+#[cfg(feature = "alloc-stats")]
+pub struct TrackingAllocator;
+
+#[cfg(feature = "alloc-stats")]
+impl TrackingAllocator {
+    fn track_alloc(size: usize) {
+        let current = ALLOCATED.fetch_add(size, std::sync::atomic::Ordering::Relaxed) + size;
+        PEAK_ALLOCATED.fetch_max(current, std::sync::atomic::Ordering::Relaxed);
+    }
+    
+    fn track_dealloc(size: usize) {
+        ALLOCATED.fetch_sub(size, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "alloc-stats")]
+unsafe impl std::alloc::GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+        let ptr = unsafe { std::alloc::System.alloc(layout) };
+        if !ptr.is_null() {
+            Self::track_alloc(layout.size());
+        }
+        ptr
+    }
+    
+    unsafe fn alloc_zeroed(&self, layout: std::alloc::Layout) -> *mut u8 {
+        let ptr = unsafe { std::alloc::System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            Self::track_alloc(layout.size());
+        }
+        ptr
+    }
+    
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+        unsafe { std::alloc::System.dealloc(ptr, layout) };
+        Self::track_dealloc(layout.size());
+    }
+    
+    unsafe fn realloc(&self, ptr: *mut u8, layout: std::alloc::Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { std::alloc::System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            // the old block is only gone if the realloc worked
+            Self::track_dealloc(layout.size());
+            Self::track_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// The bytes currently allocated on the heap, or `None` without the tracking allocator
+pub fn allocated() -> Option<usize> {
+    cfg!(feature = "alloc-stats").then(|| ALLOCATED.load(std::sync::atomic::Ordering::Relaxed))
+}
+
+/// The heap's high-water mark: the most bytes that were allocated at once since the last `reset_peak`
+/// (only what went through the allocator, so not the process's resident memory); `None` without the tracking allocator
+pub fn peak_allocated() -> Option<usize> {
+    cfg!(feature = "alloc-stats").then(|| PEAK_ALLOCATED.load(std::sync::atomic::Ordering::Relaxed))
+}
+
+/// Starts a new measurement window for `peak_allocated`
+pub fn reset_peak() {
+    PEAK_ALLOCATED.store(ALLOCATED.load(std::sync::atomic::Ordering::Relaxed), std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Like `format_bytes`, for the heap counters that only exist with the tracking allocator
+pub fn format_heap_bytes(bytes: Option<usize>) -> String {
+    match bytes {
+        Some(bytes) => format_bytes(bytes),
+        None => "n/a (build with --features alloc-stats)".to_string(),
+    }
+}
+
+/// The capacity of a vector in bytes (which is what it's actually holding onto, rather than its length)
+pub fn vec_bytes<T>(vec: &Vec<T>) -> usize {
+    vec.capacity() * size_of::<T>()
+}
+
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", value, UNITS[unit])
+}
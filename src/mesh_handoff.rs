@@ -0,0 +1,122 @@
+// Handing finished frames from the remesh worker to the render loop
+//
+// There are three slots and each one is always owned by exactly one party:
+//
+//   back   - owned by the writer (remesh worker); the frame currently being transformed and binned
+//   middle - owned by the handoff itself; the most recently finished frame
+//   front  - owned by the reader (render loop); the frame being uploaded / drawn
+//
+// State machine (the only two transitions, each a single swap under a short lock):
+//
+//   publish (writer):  back <-> middle, fresh = true
+//       the finished frame becomes the newest one; the writer keeps going on whatever was in the middle
+//   acquire (reader):  if fresh { front <-> middle, fresh = false }
+//       the reader picks up the newest frame, or keeps its current one if nothing new was published
+//
+// Neither side ever waits on the other to finish a frame: the writer can publish as often as it likes
+// (older unread frames just get overwritten) and the reader never sees a slot that's being written,
+// so there are no torn frames. The lock only guards the pointer swaps, so nothing spins or blocks for long.
+
+struct Middle<T> {
+    slot: T,
+    fresh: bool,  // true when the middle slot holds a frame the reader hasn't picked up yet
+}
+
+/// The writing end of the handoff; owned by whatever produces frames
+pub struct FrameWriter<T> {
+    back: T,
+    shared: std::sync::Arc<parking_lot::Mutex<Middle<T>>>,
+}
+
+/// The reading end of the handoff; owned by whatever consumes frames
+pub struct FrameReader<T> {
+    front: T,
+    shared: std::sync::Arc<parking_lot::Mutex<Middle<T>>>,
+}
+
+/// Creates a triple buffered handoff from three (usually empty) slots
+pub fn triple_buffer<T>(back: T, middle: T, front: T) -> (FrameWriter<T>, FrameReader<T>) {
+    let shared = std::sync::Arc::new(parking_lot::Mutex::new(Middle { slot: middle, fresh: false }));
+    (
+        FrameWriter { back, shared: shared.clone() },
+        FrameReader { front, shared },
+    )
+}
+
+impl<T> FrameWriter<T> {
+    /// The slot to build the next frame into; its contents are whatever frame was last swapped out,
+    /// so anything that isn't fully rewritten each frame has to be reset by the caller
+    pub fn back_mut(&mut self) -> &mut T {
+        &mut self.back
+    }
+    
+    /// Makes the back slot the newest finished frame
+    pub fn publish(&mut self) {
+        let mut middle = self.shared.lock();
+        std::mem::swap(&mut self.back, &mut middle.slot);
+        middle.fresh = true;
+    }
+}
+
+impl<T> FrameReader<T> {
+    /// Swaps in the newest published frame if there is one; returns true when the front slot changed
+    pub fn acquire(&mut self) -> bool {
+        let mut middle = self.shared.lock();
+        if !middle.fresh {
+            return false;
+        }
+        std::mem::swap(&mut self.front, &mut middle.slot);
+        middle.fresh = false;
+        true
+    }
+    
+    /// The frame currently owned by the reader
+    pub fn front(&self) -> &T {
+        &self.front
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn frames_are_never_torn_or_out_of_order() {
+        const FRAMES: u64 = 20_000;
+        const FRAME_SIZE: usize = 4096;
+        // every element of a frame is stamped with its sequence number, so a frame the reader could see half written would show two stamps
+        let (mut writer, mut reader) = triple_buffer(vec![0u64; FRAME_SIZE], vec![0u64; FRAME_SIZE], vec![0u64; FRAME_SIZE]);
+        let writer_thread = std::thread::spawn(move || {
+            for sequence in 1..=FRAMES {
+                writer.back_mut().fill(sequence);
+                writer.publish();
+            }
+        });
+        let reader_thread = std::thread::spawn(move || {
+            let (mut last, mut acquired) = (0, 0);
+            while last < FRAMES {
+                if !reader.acquire() {
+                    std::thread::yield_now();
+                    continue;
+                }
+                let stamp = reader.front()[0];
+                assert!(reader.front().iter().all(|element| *element == stamp), "frame {} was torn", stamp);
+                assert!(stamp > last, "frame {} was acquired after frame {}", stamp, last);
+                last = stamp;
+                acquired += 1;
+            }
+            acquired
+        });
+        
+        // joined through a channel so a deadlock fails the test instead of hanging it
+        let (done_sender, done_receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let written = writer_thread.join();
+            let _ = done_sender.send((written.is_ok(), reader_thread.join()));
+        });
+        let (written, read) = done_receiver.recv_timeout(std::time::Duration::from_secs(30)).expect("the handoff deadlocked");
+        assert!(written, "the writer panicked");
+        let acquired = read.unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        assert!(acquired > 0 && acquired <= FRAMES);
+    }
+}
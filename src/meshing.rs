@@ -1,81 +1,75 @@
 use crate::{CELL_SIZE, MAXIMUM_WINDOW_HEIGHT, MAXIMUM_WINDOW_WIDTH, WINDOW_START_HEIGHT, WINDOW_START_WIDTH};
-use crate::shader_handling::{Float2, Float4, Pipeline, ShaderHandler, Uint4, Vertex};
+use crate::shader_handling::{Float2, Float4, Pipeline, ShaderHandler, Uchar4, Uint4, Vertex};
+use crate::culling::{Aabb, ChunkBvh, Containment, Frustum};
+use crate::math::{Mat4, Vec3, Vec4};
+use crate::camera::{Camera, ViewBounds};
+use crate::occlusion::{ChunkConnectivity, OcclusionCuller};
+use crate::chunk::ChunkVertex;
+use crate::triangle::{Triangle, TriangleFlags};
+use crate::memory::vec_bytes;
+use crate::entity::EntityInstance;
 
-pub struct MeshDoubleBuffer {
-    pub front: std::sync::Arc<parking_lot::RwLock<Mesh>>,
-    pub back: std::sync::Arc<parking_lot::RwLock<Mesh>>,
-    pub current_front: std::sync::Arc<parking_lot::RwLock<bool>>,
-    pub swapping: std::sync::Arc<parking_lot::RwLock<bool>>,
-}
+/// Handed out to every mesh (and bumped whenever its triangles change) so frames know when their copies are stale
+static NEXT_GEOMETRY_EPOCH: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
 
-impl MeshDoubleBuffer {
-    pub fn current(&self) -> std::sync::Arc<parking_lot::RwLock<Mesh>> {
-        if *self.current_front.read() {
-            self.front.clone()
-        } else {
-            self.back.clone()
-        }
-    }
-    
-    pub fn back(&self) -> std::sync::Arc<parking_lot::RwLock<Mesh>> {
-        if *self.current_front.read() {
-            self.back.clone()
-        } else {
-            self.front.clone()
-        }
-    }
-    
-    pub fn swap(&self) {
-        *self.swapping.write() = true;
-    }
-    
-    pub fn update(&self) -> bool {
-        if !*self.swapping.read() {
-            return false;
-        }
-        *self.swapping.write() = false;
-        let mut front = self.current_front.write();
-        *front = !*front;
-        true
-    }
+fn next_geometry_epoch() -> u64 {
+    NEXT_GEOMETRY_EPOCH.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
 }
 
-#[derive(Clone, Copy, Debug)]
-pub struct Mat4x4 {
-    pub m: [[f32; 4]; 4],
+/// Everything the renderer needs to draw one frame, produced by `Mesh::check_remesh`
+/// these get cycled through `mesh_handoff`, so the buffers are reused rather than reallocated each frame
+#[derive(Clone, Default)]
+pub struct MeshFrame {
+    pub geometry_epoch: u64,  // which mesh geometry the indices and normals were copied from (0 = none yet)
+    pub entity_epoch: u64,  // same, for the entity triangles after them
+    pub window_size: (u32, u32),
+    pub camera_vector: Float4,
+    pub vertices: Vec<Vertex>,  // screen space
+    pub normals: Vec<Float4>,
+    pub indices: Vec<Uint4>,  // packed with `Triangle::encode`
+    pub binned_indices: Vec<u32>,
+    pub stats: RemeshStats,
 }
 
-impl Mat4x4 {
-    pub fn mul_vec4(&self, v: Float4) -> Float4 {
-        let m = &self.m;
-        Float4 {
-            x: m[0][0]*v.x + m[0][1]*v.y + m[0][2]*v.z + m[0][3]*v.w,
-            y: m[1][0]*v.x + m[1][1]*v.y + m[1][2]*v.z + m[1][3]*v.w,
-            z: m[2][0]*v.x + m[2][1]*v.y + m[2][2]*v.z + m[2][3]*v.w,
-            w: m[3][0]*v.x + m[3][1]*v.y + m[3][2]*v.z + m[3][3]*v.w,
-        }
-    }
+/// What the remesh pass that produced a frame saw, for the debug overlay
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RemeshStats {
+    pub chunks: usize,
+    pub chunks_culled: usize,    // outside the frustum
+    pub chunks_occluded: usize,  // inside it but hidden behind terrain
+    pub triangles: usize,
+    pub triangles_drawn: usize,  // that survived culling and made it into the bins
+    pub entities: usize,
+    pub entities_culled: usize,  // outside the frustum or in an occluded chunk
+    pub bins: usize,
+    pub bins_used: usize,
+    pub bins_full: usize,        // had more triangles than slots, so some were dropped
+    pub max_bin_triangles: u32,
+    pub lod_counts: [usize; 5],  // chunks meshed at each lod (0 is full detail)
 }
-
-pub fn perspective(fov_radians: f32, aspect: f32, near: f32, far: f32) -> Mat4x4 {
-    let f = 1.0 / (fov_radians * 0.5).tan();
     
-    Mat4x4 {
-        m: [
-            [f / aspect, 0.0, 0.0,                          0.0],
-            [0.0,        f,   0.0,                          0.0],
-            [0.0,        0.0, (far+near)/(near-far), (2.0*far*near)/(near-far)],
-            [0.0,        0.0, -1.0,                         0.0],
-        ]
+impl MeshFrame {
+    /// The bytes held by the frame's buffers (including spare capacity)
+    pub fn memory_usage(&self) -> usize {
+        vec_bytes(&self.vertices) + vec_bytes(&self.normals) + vec_bytes(&self.indices) + vec_bytes(&self.binned_indices)
+    }
+    
+    pub fn update_shader_buffers(&self, shader_handler: &mut ShaderHandler) {
+        //shader_handler.get_shader().update_buffer(3,  camera_position                 ).unwrap();
+        shader_handler.get_shader().update_buffer(4,  self.camera_vector              ).unwrap();
+        shader_handler.get_shader().update_buffer_slice(5, self.vertices.as_slice()   ).unwrap();
+        shader_handler.get_shader().update_buffer_slice(6, self.normals.as_slice()    ).unwrap();
+        shader_handler.get_shader().update_buffer_slice(7, self.indices.as_slice()    ).unwrap();
+        shader_handler.get_shader().update_buffer_slice(8, self.binned_indices.as_slice()).unwrap();
     }
 }
 
 pub fn transform_vertex(
     pos: Float4,
-    proj: Mat4x4,
+    proj: &Mat4,
 ) -> Float4 {
     // clip space
-    let clip = proj.mul_vec4(pos);
+    let clip = proj.mul_vec4(Vec4::new(pos.x, pos.y, pos.z, 1.0));
     
     // perspective divide → NDC
     let inv_w = 1.0 / clip.w;
@@ -92,82 +86,11 @@ pub fn ndc_to_screen(ndc: Float4, width: f32, height: f32) -> Float4 {
     let x = (ndc.x * 0.5 + 0.5) * width;
     let y = (1.0 - (ndc.y * 0.5 + 0.5)) * height; // flip Y for screen coords
     let z = ndc.z * 0.5 + 0.5;                   // depth 0..1
+    // w is kept as the 1/w from `transform_vertex` so attributes can be interpolated perspective correctly
     
     Float4::new(x, y, z, ndc.w)
 }
 
-pub struct Float3x3 {
-    table: [f32; 9],
-}
-
-impl Float3x3 {
-    pub fn new(
-        m00: f32, m01: f32, m02: f32,
-        m10: f32, m11: f32, m12: f32,
-        m20: f32, m21: f32, m22: f32,
-    ) -> Self {
-        Float3x3 {
-            table: [
-                m00, m01, m02,
-                m10, m11, m12,
-                m20, m21, m22,
-            ],
-        }
-    }
-    
-    pub fn mult_3x3(&self, other: &Float3x3) -> Float3x3 {
-        Float3x3::new(
-            (self.table[0] * other.table[0] + self.table[1] * other.table[3] + self.table[2] * other.table[6]),
-            (self.table[0] * other.table[1] + self.table[1] * other.table[4] + self.table[2] * other.table[7]),
-            (self.table[0] * other.table[2] + self.table[1] * other.table[5] + self.table[2] * other.table[8]),
-            
-            (self.table[3] * other.table[0] + self.table[4] * other.table[3] + self.table[5] * other.table[6]),
-            (self.table[3] * other.table[1] + self.table[4] * other.table[4] + self.table[5] * other.table[7]),
-            (self.table[3] * other.table[2] + self.table[4] * other.table[5] + self.table[5] * other.table[8]),
-            
-            (self.table[6] * other.table[0] + self.table[7] * other.table[3] + self.table[8] * other.table[6]),
-            (self.table[6] * other.table[1] + self.table[7] * other.table[4] + self.table[8] * other.table[7]),
-            (self.table[6] * other.table[2] + self.table[7] * other.table[5] + self.table[8] * other.table[8]),
-        )
-    }
-    
-    pub fn mult_3x1(&self, vec: &Float4) -> Float4 {
-        Float4 {
-            x: self.table[0] * vec.x + self.table[1] * vec.y + self.table[2] * vec.z,
-            y: self.table[3] * vec.x + self.table[4] * vec.y + self.table[5] * vec.z,
-            z: self.table[6] * vec.x + self.table[7] * vec.y + self.table[8] * vec.z,
-            w: 0.0,
-        }
-    }
-}
-
-pub fn rotate(vertex: Float4, rotation: &Float4) -> Float4 {
-    let sin_x = f32::sin(rotation.x);
-    let sin_y = f32::sin(rotation.y);
-    let sin_z = f32::sin(rotation.z);
-    let cos_x = f32::cos(rotation.x);
-    let cos_y = f32::cos(rotation.y);
-    let cos_z = f32::cos(rotation.z);
-    // rotation matrices
-    let rx = Float3x3::new(
-        1.0, 0.0   ,  0.0   ,
-        0.0, cos_x , -sin_x ,
-        0.0, sin_x ,  cos_x
-    );
-    let ry = Float3x3::new(
-        cos_y , 0.0, sin_y,
-        0.0   , 1.0, 0.0  ,
-        -sin_y , 0.0, cos_y
-    );
-    let rz = Float3x3::new(
-        cos_z , -sin_z , 0.0,
-        sin_z ,  cos_z , 0.0,
-        0.0   ,  0.0   , 1.0
-    );
-    let r = rx.mult_3x3(&ry).mult_3x3(&rz);
-    r.mult_3x1(&vertex)
-}
-
 fn inside(v: &Vertex, near: f32) -> bool {
     v.position.z <= -near
 }
@@ -184,12 +107,12 @@ fn intersect(a: &Vertex, b: &Vertex, near: f32) -> Vertex {
             z: plane_z,
             w: 0.0,
         },
-        uv: Float4 {
+        uv: Float2 {
             x: a.uv.x + (b.uv.x - a.uv.x) * t,
             y: a.uv.y + (b.uv.y - a.uv.y) * t,
-            ..Default::default()
         },
-        light: Float4::new(1.0, 1.0, 1.0, 0.0)
+        light: Uchar4::new(255, 255, 255, 0),
+        tint: a.tint,
     }
 }
 
@@ -210,18 +133,18 @@ pub fn clip_triangle_near_plane_inplace(
     inside_count != 3
 }
 
-fn is_triangle_culled_inline(tri_center: Float4, radius: f32, tan_half_fov_y: f32, tan_half_fov_x: f32) -> bool {
+fn is_triangle_culled_inline(tri_center: Float4, radius: f32, bounds: &ViewBounds) -> bool {
     let x = tri_center.x;
     let y = tri_center.y;
     let z = tri_center.z;
-    let z_term = z * tan_half_fov_x;
+    let z_term = z * bounds.slope_x + bounds.offset_x;
     if x < -z_term - radius {
         return true;
     }
     if x > z_term + radius {
         return true;
     }
-    let z_term = z * tan_half_fov_y;
+    let z_term = z * bounds.slope_y + bounds.offset_y;
     if y < -z_term - radius {
         return true;
     }
@@ -232,6 +155,47 @@ fn is_triangle_culled_inline(tri_center: Float4, radius: f32, tan_half_fov_y: f3
     false
 }
 
+/// Why a triangle isn't getting drawn (if it isn't)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TriangleCull {
+    Visible,
+    BackFacing,
+    OutsideView,
+    NearPlane,
+}
+
+/// Culls a triangle whose vertices have already been moved into view space
+fn cull_triangle(index: &Triangle, vertices: &[Vertex], normals: &[Float4], view_matrix: &Mat4, camera: &Camera, view_bounds: &ViewBounds) -> TriangleCull {
+    let p0 = &vertices[index.indices[0] as usize].position;
+    let p1 = &vertices[index.indices[1] as usize].position;
+    let p2 = &vertices[index.indices[2] as usize].position;
+    let tri_center = Float4::new(
+        (p0.x + p1.x + p2.x) * const { 1.0 / 3.0 },
+        (p0.y + p1.y + p2.y) * const { 1.0 / 3.0 },
+        (p0.z + p1.z + p2.z) * const { 1.0 / 3.0 },
+        0.0
+    );
+    if !index.flags.contains(TriangleFlags::DOUBLE_SIDED) {
+        let view_vector = camera.view_vector(Vec3::from(tri_center)).to_float4(0.0);
+        let normal_view = view_matrix.transform_vector(Vec3::from(normals[index.normal as usize])).normalized().to_float4(0.0);
+        if view_vector.dot(&normal_view) < 0.0 {
+            return TriangleCull::BackFacing;
+        }
+    }
+    let rad = (
+        (tri_center.x - p0.x) * (tri_center.x - p0.x) +
+        (tri_center.y - p0.y) * (tri_center.y - p0.y) +
+        (tri_center.z - p0.z) * (tri_center.z - p0.z)
+    ).sqrt();
+    if is_triangle_culled_inline(tri_center, rad, view_bounds) {
+        return TriangleCull::OutsideView;
+    }
+    if clip_triangle_near_plane_inplace(index.indices, 0.01, vertices) {
+        return TriangleCull::NearPlane;
+    }
+    TriangleCull::Visible
+}
+
 struct UnsafePtrWrapper<T> { ptr: T }
 impl<T> UnsafePtrWrapper<T> {
     fn new(ptr: T) -> Self {
@@ -244,47 +208,79 @@ impl<T> UnsafePtrWrapper<T> {
 unsafe impl<T> Send for UnsafePtrWrapper<T> {}
 unsafe impl<T> Sync for UnsafePtrWrapper<T> {}
 
-#[derive(Clone)]
+/// What a mesh starts out holding (see `Mesh::new`); anything left out starts empty
+#[derive(Default)]
+pub struct MeshParts {
+    pub vertices_original: Vec<ChunkVertex>,
+    pub vertex_ownership: Vec<usize>,
+    pub index_chunks: Vec<usize>,
+    pub chunks: Vec<(Float4, Float4)>,
+    pub indices: Vec<Triangle>,
+    pub normals: Vec<Float4>,
+    pub dead: Vec<bool>,
+    pub is_chunk_culled: Vec<bool>,
+    pub vert_chunk_index: Vec<usize>,
+}
+
 pub struct Mesh {
-    mutated: bool,
-    vertices_original: Vec<Vertex>,
+    geometry_epoch: u64,  // changes whenever the triangles do
+    vertices_original: Vec<ChunkVertex>,  // packed and relative to the chunk they belong to (see vert_chunk_index)
     vertex_ownership: Vec<usize>,
-    vertices: Vec<Vertex>,
-    indices: Vec<Uint4>,
+    indices: Vec<Triangle>,
     index_chunks: Vec<usize>,
     chunks: Vec<(Float4, Float4)>,  // position, size
     normals: Vec<Float4>,
-    binned_indices: Vec<u32>,
     dead: Vec<bool>,
     is_chunk_culled: Vec<bool>,
     vert_chunk_index: Vec<usize>,
+    chunk_bvh: ChunkBvh,  // rebuilt whenever the chunk count changes
+    chunk_connectivity: Vec<ChunkConnectivity>,
+    chunk_lods: Vec<u8>,  // which lod each chunk was meshed at
+    occlusion_culler: OcclusionCuller,  // same as the bvh
+    // the entities placed by `set_entities`, which go after all of the chunk geometry in the frame
+    entity_epoch: u64,  // changes whenever the entity triangles do (not when they only move)
+    entity_vertices: Vec<Vertex>,  // world space
+    entity_vertex_owner: Vec<usize>,
+    entity_triangles: Vec<Triangle>,  // already offset past the chunk vertices
+    entity_triangle_owner: Vec<usize>,
+    entity_bounds: Vec<Aabb>,
+    entity_culled: Vec<bool>,
+    entity_dead: Vec<bool>,
 }
 //let mut dead = vec![false; self.indices.len()];
 impl Mesh {
-    pub fn mutated(&mut self, value: bool) {
-        self.mutated = value;
-    }
-    
-    pub fn indices_ref(&self) -> &Vec<Uint4> {
+    pub fn indices_ref(&self) -> &Vec<Triangle> {
         &self.indices
     }
 
-    pub fn vertices_original_ref(&self) -> &Vec<Vertex> {
+    pub fn vertices_original_ref(&self) -> &Vec<ChunkVertex> {
         &self.vertices_original
     }
 
     pub fn add_chunk(&mut self, position: Float4, size: Float4) {
         self.chunks.push((position, size));
         self.is_chunk_culled.push(false);
+        self.chunk_connectivity.push(ChunkConnectivity::fully_open());
+        self.chunk_lods.push(0);
+    }
+    
+    pub fn set_chunk_connectivity(&mut self, chunk_index: usize, connectivity: ChunkConnectivity) {
+        self.chunk_connectivity[chunk_index] = connectivity;
     }
     
-    pub fn push_index(&mut self, index: Uint4, chunk_index: usize) {
+    pub fn set_chunk_lod(&mut self, chunk_index: usize, lod: usize) {
+        self.chunk_lods[chunk_index] = lod as u8;
+    }
+    
+    pub fn push_index(&mut self, index: Triangle, chunk_index: usize) {
+        self.geometry_epoch = next_geometry_epoch();
         self.indices.push(index);
         self.dead.push(false);
         self.index_chunks.push(chunk_index);
     }
     
-    pub fn append_indices(&mut self, indices: &mut Vec<Uint4>, chunk_index: usize) {
+    pub fn append_indices(&mut self, indices: &mut Vec<Triangle>, chunk_index: usize) {
+        self.geometry_epoch = next_geometry_epoch();
         for _ in 0..indices.len() {
             self.dead.push(false);
             self.index_chunks.push(chunk_index);
@@ -292,14 +288,14 @@ impl Mesh {
         self.indices.extend_from_slice(&indices);
     }
     
-    pub fn push_vertex(&mut self, vertex: Vertex, owner: usize, chunk_index: usize) {
+    pub fn push_vertex(&mut self, vertex: ChunkVertex, owner: usize, chunk_index: usize) {
         self.vertices_original.push(vertex);
         //self.vertices.push(vertex);
         self.vertex_ownership.push(owner);
         self.vert_chunk_index.push(chunk_index)
     }
     
-    pub fn append_vertices(&mut self, vertices: &mut Vec<Vertex>, owner: usize, chunk_index: usize) {
+    pub fn append_vertices(&mut self, vertices: &mut Vec<ChunkVertex>, owner: usize, chunk_index: usize) {
         for _ in 0..vertices.len() {
             self.vertex_ownership.push(owner);
             self.vert_chunk_index.push(chunk_index)
@@ -308,70 +304,167 @@ impl Mesh {
         //self.vertices.append(vertices);
     }
     
+    /// Empties the mesh so it can be rebuilt, keeping every allocation so the next build doesn't have to grow them again
+    /// the normals are the shared face normals rather than geometry, so they're kept
+    pub fn reset(&mut self) {
+        self.geometry_epoch = next_geometry_epoch();
+        self.vertices_original.clear();
+        self.vertex_ownership.clear();
+        self.indices.clear();
+        self.index_chunks.clear();
+        self.chunks.clear();
+        self.dead.clear();
+        self.is_chunk_culled.clear();
+        self.vert_chunk_index.clear();
+        self.chunk_connectivity.clear();
+        self.chunk_lods.clear();
+        // the same chunk count doesn't mean the same chunks anymore
+        self.chunk_bvh = ChunkBvh::default();
+        self.occlusion_culler = OcclusionCuller::default();
+        self.entity_epoch = next_geometry_epoch();
+        self.entity_vertices.clear();
+        self.entity_vertex_owner.clear();
+        self.entity_triangles.clear();
+        self.entity_triangle_owner.clear();
+        self.entity_bounds.clear();
+    }
+    
+    /// Replaces the entities with these, placed after the chunk geometry (so they have to be set again after a rebuild)
+    /// their faces get snapped to the closest of the mesh's normals, so rotated ones still get lit and culled about right
+    pub fn set_entities(&mut self, instances: &[EntityInstance]) {
+        let chunk_vertex_count = self.vertices_original.len();
+        self.entity_vertices.clear();
+        self.entity_vertex_owner.clear();
+        self.entity_triangle_owner.clear();
+        self.entity_bounds.clear();
+        let mut triangles = Vec::with_capacity(self.entity_triangles.len());
+        for (entity_index, instance) in instances.iter().enumerate() {
+            let start = (chunk_vertex_count + self.entity_vertices.len()) as u32;
+            let mut bounds = Aabb::empty();
+            for vertex in &instance.model.vertices {
+                let position = instance.transform.transform_point(Vec3::from(vertex.position)).to_float4(0.0);
+                bounds = bounds.union(&Aabb::new(position, position));
+                self.entity_vertices.push(Vertex { position, ..*vertex });
+                self.entity_vertex_owner.push(entity_index);
+            }
+            for triangle in &instance.model.triangles {
+                let normal = instance.transform.transform_vector(instance.model.normals[triangle.normal as usize]).normalized().to_float4(0.0);
+                let closest = self.normals.iter().enumerate()
+                    .max_by(|(_, a), (_, b)| a.dot(&normal).total_cmp(&b.dot(&normal)))
+                    .map(|(index, _)| index as u8)
+                    .unwrap_or(0);
+                triangles.push(Triangle { normal: closest, ..triangle.offset_indices(start) });
+                self.entity_triangle_owner.push(entity_index);
+            }
+            self.entity_bounds.push(bounds);
+        }
+        // only moving doesn't change the triangles, so the frames can keep their copies
+        if triangles != self.entity_triangles {
+            self.entity_triangles = triangles;
+            self.entity_epoch = next_geometry_epoch();
+        }
+    }
+    
+    /// The bytes held by the mesh's buffers (including spare capacity)
+    pub fn memory_usage(&self) -> usize {
+        vec_bytes(&self.vertices_original) + vec_bytes(&self.vertex_ownership) + vec_bytes(&self.indices) +
+            vec_bytes(&self.index_chunks) + vec_bytes(&self.chunks) + vec_bytes(&self.normals) + vec_bytes(&self.dead) +
+            vec_bytes(&self.is_chunk_culled) + vec_bytes(&self.vert_chunk_index) + vec_bytes(&self.chunk_connectivity) + vec_bytes(&self.chunk_lods) +
+            vec_bytes(&self.entity_vertices) + vec_bytes(&self.entity_vertex_owner) + vec_bytes(&self.entity_triangles) +
+            vec_bytes(&self.entity_triangle_owner) + vec_bytes(&self.entity_bounds) + vec_bytes(&self.entity_culled) + vec_bytes(&self.entity_dead)
+    }
+    
     pub fn chunk_ref(&self) -> &Vec<(Float4, Float4)> {
         &self.chunks
     }
 
-    pub fn new(mutated: bool, vertices_original: Vec<Vertex>, vertex_ownership: Vec<usize>, index_chunks: Vec<usize>, chunks: Vec<(Float4, Float4)>, vertices: Vec<Vertex>, indices: Vec<Uint4>, normals: Vec<Float4>, binned_indices: Vec<u32>, dead: Vec<bool>, is_chunk_culled: Vec<bool>, vert_chunk_index: Vec<usize>) -> Self {
+    pub fn new(parts: MeshParts) -> Self {
+        let MeshParts { vertices_original, vertex_ownership, index_chunks, chunks, indices, normals, dead, is_chunk_culled, vert_chunk_index } = parts;
         Mesh {
-            mutated,
+            geometry_epoch: next_geometry_epoch(),
             vertices_original,
             vertex_ownership,
             index_chunks,
             chunks,
-            vertices,
             indices,
             normals,
-            binned_indices,
             dead,
             is_chunk_culled,
             vert_chunk_index,
+            chunk_bvh: ChunkBvh::default(),
+            chunk_connectivity: vec![],
+            chunk_lods: vec![],
+            occlusion_culler: OcclusionCuller::default(),
+            entity_epoch: next_geometry_epoch(),
+            entity_vertices: vec![],
+            entity_vertex_owner: vec![],
+            entity_triangles: vec![],
+            entity_triangle_owner: vec![],
+            entity_bounds: vec![],
+            entity_culled: vec![],
+            entity_dead: vec![],
         }
     }
     
-    pub fn was_mutated(&self) -> bool {
-        self.mutated
-    }
-    
-    pub fn check_remesh(&mut self, /*shader_handler: &mut ShaderHandler,*/ window_size: (u32, u32), camera_position: Float4, camera_rotation: Float4, meshing_priority_min: usize, meshing_priority_max: usize, print_debug: bool) {
-        if !self.mutated { return; }
-        self.mutated = false;
-        
+    /// Transforms, culls and bins the mesh for the given view, writing the results into `frame`
+    /// the frame's buffers are resized to fit rather than assumed to be from this mesh (or this window size)
+    /// `print_debug` logs the pass's summary at info level rather than debug
+    pub fn check_remesh(&mut self, frame: &mut MeshFrame, window_size: (u32, u32), camera: &Camera, print_debug: bool) {
+        let _span = crate::logging::span("remesh", "check_remesh");
         let start = std::time::Instant::now();
         
         // going through all chunks and finding which ones should be culled
-        let fov_y = 60_f32.to_radians();
+        let cull_span = crate::logging::span("remesh", "cull_chunks");
         let aspect = window_size.0 as f32 / window_size.1 as f32;
-        let tan_half_fov_y = (fov_y * 0.5).tan();
-        let tan_half_fov_x = tan_half_fov_y * aspect;
+        let view_bounds = camera.view_bounds(aspect);
         
-        let mut culled_chunks = 0;
+        // the chunks only ever get appended during a rebuild, so a differing count means the hierarchy is stale
+        if self.chunk_bvh.chunk_count() != self.chunks.len() {
+            self.chunk_bvh = ChunkBvh::new(self.chunks.iter().map(|(position, size)| Aabb::from_position_size(*position, *size)).collect());
+        }
         
-        for chunk_index in 0..self.chunks.len() {
-            self.is_chunk_culled[chunk_index] = {
-                // checking if the chunk is outside the view frustum
-                let position = &self.chunks[chunk_index];
-                let center = rotate(Float4::new(
-                    position.0.x + position.1.x * 0.5 - camera_position.x,
-                    position.0.y + position.1.y * 0.5 - camera_position.y,
-                    position.0.z + position.1.z * 0.5 - camera_position.z,
-                    0.0,
-                ), &camera_rotation);
-                let rad = (
-                    position.1.x * position.1.x +
-                    position.1.y * position.1.y +
-                    position.1.z * position.1.z
-                ).sqrt();// * 1.5;
-                is_triangle_culled_inline(center, rad, tan_half_fov_y, tan_half_fov_x)
-            };
-            if self.is_chunk_culled[chunk_index] {
-                culled_chunks += 1;
-            }
+        // every transform for this pass comes from these, rather than rebuilding rotations per vertex
+        let view_matrix = camera.view_matrix();
+        let projection_matrix = camera.projection_matrix(aspect);
+        let view_projection = projection_matrix * view_matrix;
+        
+        let frustum = Frustum::from_matrix(&view_projection);
+        let culled_chunks = self.chunk_bvh.cull(&frustum, &mut self.is_chunk_culled);
+        
+        // hiding the chunks which are inside the frustum but behind solid terrain (ran after the frustum so it can skip culled chunks)
+        if self.occlusion_culler.chunk_count() != self.chunks.len() {
+            self.occlusion_culler = OcclusionCuller::new(&self.chunks);
+        }
+        // an entity is hidden along with the chunk it's in, but only if that chunk gets occluded (rather than just being off screen, which the entity might not be)
+        let entity_chunks: Vec<Option<usize>> = self.entity_bounds.iter().map(|bounds| {
+            self.occlusion_culler.chunk_at(bounds.center()).filter(|chunk| !self.is_chunk_culled[*chunk])
+        }).collect();
+        let occluded_chunks = self.occlusion_culler.cull(camera.eye_position().to_float4(0.0), &self.chunk_connectivity, &mut self.is_chunk_culled);
+        self.entity_culled.clear();
+        for (bounds, chunk) in self.entity_bounds.iter().zip(&entity_chunks) {
+            let occluded = chunk.is_some_and(|chunk| self.is_chunk_culled[chunk]);
+            self.entity_culled.push(occluded || frustum.classify_aabb(bounds) == Containment::Outside);
         }
+        drop(cull_span);
         
-        // replacing vertices with the transformations of the original vertices
-        let projection_matrix = perspective(60_f32.to_radians(), window_size.0 as f32 / window_size.1 as f32, 0.1, 9999.0);
+        // the triangles only need copying into the frame when they've changed since it last held them
+        let geometry_changed = frame.geometry_epoch != self.geometry_epoch;
+        if geometry_changed {
+            frame.indices.clear();
+            frame.indices.extend(self.indices.iter().map(Triangle::encode));
+            frame.normals.clone_from(&self.normals);
+            frame.geometry_epoch = self.geometry_epoch;
+        }
+        if geometry_changed || frame.entity_epoch != self.entity_epoch {
+            frame.indices.truncate(self.indices.len());
+            frame.indices.extend(self.entity_triangles.iter().map(Triangle::encode));
+            frame.entity_epoch = self.entity_epoch;
+        }
+        frame.vertices.resize(self.vertices_original.len() + self.entity_vertices.len(), Vertex::default());
+        frame.window_size = window_size;
+        frame.camera_vector = camera.forward().to_float4(0.0);
         
+        // replacing vertices with the transformations of the original vertices
         // creating slices which DO not overlap to ensure thread safety
         const THREAD_COUNT: usize = 8;  // seems to be a good number for the best speed, but idk
         let length = self.vertices_original.len();
@@ -388,44 +481,39 @@ impl Mesh {
             ));
         }
         
+        let transform_span = crate::logging::span("remesh", "transform");
         let mut thread_handles = vec![];
         // these pointers are safe as the data lives for the length of this class, but the pointers are used purely for part of the function call
         // the pointers are only used in either immutable state where they all get cleaned up after use, or mutable state where the slices ensure no overlap
         let vert_orig_ptr = std::sync::Arc::new(UnsafePtrWrapper::new(self.vertices_original.as_ptr()));
-        let vert_mut_ptr  = std::sync::Arc::new(UnsafePtrWrapper::new(self.vertices.as_mut_ptr()));
-        let ownership_ptr  = std::sync::Arc::new(UnsafePtrWrapper::new(self.vertex_ownership.as_ptr()));
+        let vert_mut_ptr  = std::sync::Arc::new(UnsafePtrWrapper::new(frame.vertices.as_mut_ptr()));
         let vert_chunk_index_ptr  = std::sync::Arc::new(UnsafePtrWrapper::new(self.vert_chunk_index.as_ptr()));
         let culled_chunks_len = self.is_chunk_culled.len();
         let is_chunk_culled_ptr = std::sync::Arc::new(UnsafePtrWrapper::new(self.is_chunk_culled.as_ptr()));
+        let chunks_ptr = std::sync::Arc::new(UnsafePtrWrapper::new(self.chunks.as_ptr()));
         for (start, end) in &slices {
             let (start, end) = (*start, *end);
             let vert_orig_ptr = vert_orig_ptr.clone();
             let vert_mut_ptr  = vert_mut_ptr.clone();
-            let ownership_ptr = ownership_ptr.clone();
             let vert_chunk_index_ptr = vert_chunk_index_ptr.clone();
             let is_chunk_culled_ptr = is_chunk_culled_ptr.clone();
-            
-            let camera_position = camera_position.clone();
-            let camera_rotation = camera_rotation.clone();
+            let chunks_ptr = chunks_ptr.clone();
             
             let thread = std::thread::spawn(move || unsafe {
                 let len = end - start;
                 let vertices_original = std::slice::from_raw_parts(vert_orig_ptr.unwrap().add(start), len);
                 let vertices = std::slice::from_raw_parts_mut(vert_mut_ptr.unwrap().add(start), len);
-                let ownership = std::slice::from_raw_parts(ownership_ptr.unwrap().add(start), len);
                 let vert_chunk_index = std::slice::from_raw_parts(vert_chunk_index_ptr.unwrap().add(start), len);
                 let is_chunk_culled = std::slice::from_raw_parts(is_chunk_culled_ptr.unwrap(), culled_chunks_len);
+                let chunks = std::slice::from_raw_parts(chunks_ptr.unwrap(), culled_chunks_len);
                 for (i, vertex) in vertices_original.iter().enumerate() {
-                    if is_chunk_culled[vert_chunk_index[i]] || ownership[i] < meshing_priority_min || ownership[i] > meshing_priority_max {
-                        continue;  // using the old results (hopefully they're ok, sometimes a complete remesh will be necessary though)
+                    if is_chunk_culled[vert_chunk_index[i]] {
+                        continue;  // nothing alive references these, so whatever was left in the frame doesn't matter
                     }
-                    let vert = rotate(Float4::new(
-                        vertex.position.x - camera_position.x,
-                        vertex.position.y - camera_position.y,
-                        vertex.position.z - camera_position.z,
-                        0.0,
-                    ), &camera_rotation);
-                    vertices[i] = Vertex::new(vert, Float2::new(vertex.uv.x, vertex.uv.y), vertex.light);
+                    // the packed vertices get expanded here, so the full sized ones only ever exist in the frame
+                    let mut vert = vertex.unpack(chunks[vert_chunk_index[i]].0);
+                    vert.position = view_matrix.transform_point(Vec3::from(vert.position)).to_float4(0.0);
+                    vertices[i] = vert;
                 }
             });
             thread_handles.push(thread);
@@ -434,6 +522,9 @@ impl Mesh {
             handle.join().unwrap();
         }
         
+        drop(transform_span);
+        
+        let triangles_span = crate::logging::span("remesh", "cull_triangles");
         let culled = std::sync::Arc::new(parking_lot::RwLock::new(0));
         let length = self.indices.len();
         let mut slices_tri = vec![];
@@ -451,10 +542,9 @@ impl Mesh {
         
         let dead_ptr = std::sync::Arc::new(UnsafePtrWrapper::new(self.dead.as_mut_ptr()));
         let indices_ptr = std::sync::Arc::new(UnsafePtrWrapper::new(self.indices.as_ptr()));
-        let vertices_ptr = std::sync::Arc::new(UnsafePtrWrapper::new(self.vertices.as_ptr()));
+        let vertices_ptr = std::sync::Arc::new(UnsafePtrWrapper::new(frame.vertices.as_ptr()));
         let normals_ptr = std::sync::Arc::new(UnsafePtrWrapper::new(self.normals.as_ptr()));
         let chunk_owner_ptr = std::sync::Arc::new(UnsafePtrWrapper::new(self.index_chunks.as_ptr()));
-        let ownership_ptr  = std::sync::Arc::new(UnsafePtrWrapper::new(self.vertex_ownership.as_ptr()));
  
         let num_norms = self.normals.len();
         let num_verts = self.vertices_original.len();
@@ -465,9 +555,8 @@ impl Mesh {
             let dead_ptr = dead_ptr.clone();
             let indices_ptr = indices_ptr.clone();
             let vertices_ptr = vertices_ptr.clone();
-            let camera_rotation = camera_rotation.clone();
+            let camera = *camera;
             let normals_ptr = normals_ptr.clone();
-            let ownership_ptr = ownership_ptr.clone();
             let chunk_owner_ptr = chunk_owner_ptr.clone();
             let is_chunk_culled_ptr = is_chunk_culled_ptr.clone();
             let thread = std::thread::spawn(move || unsafe {
@@ -475,7 +564,6 @@ impl Mesh {
                 let indices = std::slice::from_raw_parts(indices_ptr.unwrap(), length);
                 let vertices = std::slice::from_raw_parts(vertices_ptr.unwrap(), num_verts);
                 let normals = std::slice::from_raw_parts(normals_ptr.unwrap(), num_norms);
-                let ownership = std::slice::from_raw_parts(ownership_ptr.unwrap(), num_verts);
                 let chunk_owner = std::slice::from_raw_parts(chunk_owner_ptr.unwrap(), length);
                 let is_chunk_culled = std::slice::from_raw_parts(is_chunk_culled_ptr.unwrap(), culled_chunks_len);
                 for tri_index in start..end {
@@ -484,44 +572,11 @@ impl Mesh {
                         dead[tri_index] = true;
                         continue;
                     }
-                    // all vertices should have the same priority
-                    if ownership[index.x as usize] > meshing_priority_max || ownership[index.x as usize] < meshing_priority_min {
-                        continue;  // not mutated dead either, as the previous result will continue to be used
-                    }
-                    let p0 = &vertices[index.x as usize].position;
-                    let p1 = &vertices[index.y as usize].position;
-                    let p2 = &vertices[index.z as usize].position;
-                    let tri_center = Float4::new(
-                        (p0.x + p1.x + p2.x) * const { 1.0 / 3.0 },
-                        (p0.y + p1.y + p2.y) * const { 1.0 / 3.0 },
-                        (p0.z + p1.z + p2.z) * const { 1.0 / 3.0 },
-                        0.0
-                    );
-                    let view_vector = tri_center.negate().normalized();
-                    let normal_view = rotate(normals[(indices[tri_index].w & 0xFFFF) as usize], &camera_rotation).normalized();
-                    if view_vector.dot(&normal_view) < 0.0 {
-                        dead[tri_index] = true;
-                        continue;
-                    }
-                    let rad = (
-                        (tri_center.x - p0.x) * (tri_center.x - p0.x) +
-                        (tri_center.y - p0.y) * (tri_center.y - p0.y) +
-                        (tri_center.z - p0.z) * (tri_center.z - p0.z)
-                    ).sqrt();
-                    if is_triangle_culled_inline(tri_center, rad, tan_half_fov_y, tan_half_fov_x) {
-                        dead[tri_index] = true;
+                    let result = cull_triangle(index, vertices, normals, &view_matrix, &camera, &view_bounds);
+                    if result == TriangleCull::OutsideView {
                         *culled.write() += 1;
-                        continue;
                     }
-                    dead[tri_index] = false;
-                    
-                    let tri = indices[tri_index].clone();
-                    let d = clip_triangle_near_plane_inplace(
-                        [tri.x, tri.y, tri.z],
-                        0.01,
-                        vertices,
-                    );
-                    dead[tri_index] = d;
+                    dead[tri_index] = result != TriangleCull::Visible;
                 }
             });
             handles.push(thread);
@@ -530,11 +585,12 @@ impl Mesh {
             handle.join().unwrap();
         }
         
+        drop(triangles_span);
+        
+        let project_span = crate::logging::span("remesh", "project");
         let mut thread_handles = vec![];
-        let vert_mut_ptr  = std::sync::Arc::new(UnsafePtrWrapper::new(self.vertices.as_mut_ptr()));
+        let vert_mut_ptr  = std::sync::Arc::new(UnsafePtrWrapper::new(frame.vertices.as_mut_ptr()));
         for (start, end) in &slices {
-            let ownership_ptr = ownership_ptr.clone();
-            let projection_matrix = projection_matrix.clone();
             let (start, end) = (*start, *end);
             let vert_mut_ptr  = vert_mut_ptr.clone();
             let is_chunk_culled_ptr = is_chunk_culled_ptr.clone();
@@ -542,20 +598,18 @@ impl Mesh {
             let thread = std::thread::spawn(move || unsafe {
                 let len = end - start;
                 let vertices = std::slice::from_raw_parts_mut(vert_mut_ptr.unwrap().add(start), len);
-                let ownership = std::slice::from_raw_parts(ownership_ptr.unwrap().add(start), len);
                 let vert_chunk_index = std::slice::from_raw_parts(vert_chunk_index_ptr.unwrap().add(start), len);
                 let is_chunk_culled = std::slice::from_raw_parts(is_chunk_culled_ptr.unwrap(), culled_chunks_len);
                 for (i, vertex) in vertices.iter_mut().enumerate() {
-                    if is_chunk_culled[vert_chunk_index[i]] || ownership[i] < meshing_priority_min || ownership[i] > meshing_priority_max {
-                        continue;  // using the old results (hopefully they're ok, sometimes a complete remesh will be necessary though)
+                    if is_chunk_culled[vert_chunk_index[i]] {
+                        continue;  // nothing alive references these, so whatever was left in the frame doesn't matter
                     }
                     let ndc = transform_vertex(
                         vertex.position,
-                        projection_matrix
+                        &projection_matrix
                     );
-                    let mut vert = ndc_to_screen(ndc, window_size.0 as f32, window_size.1 as f32);
-                    vert.z = vertex.position.z;
-                    vertex.position = vert;
+                    // z is the 0..1 depth (linear in screen space, so it can be interpolated as is) and w is 1/w for perspective correction
+                    vertex.position = ndc_to_screen(ndc, window_size.0 as f32, window_size.1 as f32);
                 }
             });
             thread_handles.push(thread);
@@ -564,20 +618,59 @@ impl Mesh {
             handle.join().unwrap();
         }
         
+        drop(project_span);
+        
+        // the entities go through the same steps, but there's few enough of them not to bother with threads
+        let entities_span = crate::logging::span("remesh", "entities");
+        let chunk_vertex_count = self.vertices_original.len();
+        for (i, vertex) in self.entity_vertices.iter().enumerate() {
+            if self.entity_culled[self.entity_vertex_owner[i]] {
+                continue;
+            }
+            let mut vert = *vertex;
+            vert.position = view_matrix.transform_point(Vec3::from(vert.position)).to_float4(0.0);
+            frame.vertices[chunk_vertex_count + i] = vert;
+        }
+        self.entity_dead.clear();
+        for (triangle, owner) in self.entity_triangles.iter().zip(&self.entity_triangle_owner) {
+            if self.entity_culled[*owner] {
+                self.entity_dead.push(true);
+                continue;
+            }
+            let result = cull_triangle(triangle, &frame.vertices, &self.normals, &view_matrix, camera, &view_bounds);
+            if result == TriangleCull::OutsideView {
+                *culled.write() += 1;
+            }
+            self.entity_dead.push(result != TriangleCull::Visible);
+        }
+        for (i, vertex) in frame.vertices[chunk_vertex_count..].iter_mut().enumerate() {
+            if self.entity_culled[self.entity_vertex_owner[i]] {
+                continue;
+            }
+            let ndc = transform_vertex(vertex.position, &projection_matrix);
+            vertex.position = ndc_to_screen(ndc, window_size.0 as f32, window_size.1 as f32);
+        }
+        drop(entities_span);
         let middle_split = start.elapsed();
         
-        for i in 0..self.binned_indices.len() / 64 {
-            self.binned_indices[i * 64] = 0;
+        let _bin_span = crate::logging::span("remesh", "bin");
+        // sized to the current window so only the bins the shader will actually read get uploaded
+        let bin_count = (window_size.0 as f32 / CELL_SIZE as f32).ceil() as usize * (window_size.1 as f32 / CELL_SIZE as f32).ceil() as usize;
+        frame.binned_indices.resize(bin_count * 64, 0);
+        for i in 0..bin_count {
+            frame.binned_indices[i * 64] = 0;
         }
         
         let window_width = (window_size.0 as f32 / CELL_SIZE as f32).ceil() as usize * 64;
-        for tri_index in 0..self.indices.len() {
-            if self.dead[tri_index] { continue; }
+        // the entity triangles come straight after the chunk ones in the frame, so the indices carry on from them
+        for (tri_index, dead) in self.dead.iter().chain(&self.entity_dead).enumerate() {
+            if *dead { continue; }
             // finding all bounding box cells it falls within
             // getting the bounding box
-            let v1 = &self.vertices[self.indices[tri_index].x as usize];
-            let v2 = &self.vertices[self.indices[tri_index].y as usize];
-            let v3 = &self.vertices[self.indices[tri_index].z as usize];
+            let triangle = &frame.indices[tri_index];
+            let v1 = &frame.vertices[triangle.x as usize];
+            let v2 = &frame.vertices[triangle.y as usize];
+            let v3 = &frame.vertices[triangle.z as usize];
             let min_x = v1.position.x.min(v2.position.x.min(v3.position.x));
             let max_x = v1.position.x.max(v2.position.x.max(v3.position.x));
             let min_y = v1.position.y.min(v2.position.y.min(v3.position.y));
@@ -592,26 +685,103 @@ impl Mesh {
                 for y in min_y_bin..max_y_bin {
                     // placing it into the bin
                     let bin_index_base = y as usize * window_width + x_coord;
-                    self.binned_indices[bin_index_base] += 1;
-                    let current_count = self.binned_indices[bin_index_base];
+                    frame.binned_indices[bin_index_base] += 1;
+                    let current_count = frame.binned_indices[bin_index_base];
                     if current_count >= 63 { continue; }
-                    self.binned_indices[bin_index_base + current_count as usize] = tri_index as u32;
+                    frame.binned_indices[bin_index_base + current_count as usize] = tri_index as u32;
                 }
             }
         }
         
+        let mut stats = RemeshStats {
+            chunks: self.chunks.len(),
+            chunks_culled: culled_chunks,
+            chunks_occluded: occluded_chunks,
+            triangles: self.indices.len() + self.entity_triangles.len(),
+            triangles_drawn: self.dead.iter().chain(&self.entity_dead).filter(|dead| !**dead).count(),
+            entities: self.entity_bounds.len(),
+            entities_culled: self.entity_culled.iter().filter(|culled| **culled).count(),
+            bins: bin_count,
+            ..RemeshStats::default()
+        };
+        for bin in 0..bin_count {
+            let count = frame.binned_indices[bin * 64];
+            stats.bins_used += (count > 0) as usize;
+            stats.bins_full += (count >= 63) as usize;
+            stats.max_bin_triangles = stats.max_bin_triangles.max(count);
+        }
+        for lod in &self.chunk_lods {
+            stats.lod_counts[*lod as usize] += 1;
+        }
+        frame.stats = stats;
+        
         let duration = start.elapsed();
-        println!("\n\nRemeshed in: {:?}     Binned in {:?}      Transformed in {:?}      Culled: {}      Chunks Culled: {}\n\n", duration, duration - middle_split, middle_split, *culled.read(), culled_chunks);
+        let level = if print_debug { crate::logging::Level::Info } else { crate::logging::Level::Debug };
+        log_at!(level, "Remeshed in {:?}      Binned in {:?}      Transformed in {:?}      Culled: {}      Chunks Culled: {}      Chunks Occluded: {}", duration, duration - middle_split, middle_split, *culled.read(), culled_chunks, occluded_chunks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::Model;
+    use crate::math::Quat;
+
+    fn cube_instance(position: Vec3, rotation: Quat) -> EntityInstance {
+        EntityInstance {
+            model: std::sync::Arc::new(Model::cuboid(Vec3::splat(1.0), [0, 0, 0])),
+            transform: Mat4::translation(position) * Mat4::from_quat(rotation),
+        }
     }
     
-    pub fn update_shader_buffers(&self, shader_handler: &mut ShaderHandler, window_size: (u32, u32), camera_rotation: Float4) {
-        let camera_vector = rotate(Float4::new(0.0, 0.0, 1.0, 0.0), &camera_rotation.negate()).normalized();
-        //shader_handler.get_shader().update_buffer(3,  camera_position                 ).unwrap();
-        shader_handler.get_shader().update_buffer(4,  camera_vector                   ).unwrap();
-        shader_handler.get_shader().update_buffer_slice(5, &self.vertices[0..self.vertices_original.len()]   ).unwrap();
-        shader_handler.get_shader().update_buffer_slice(6, self.normals.as_slice()    ).unwrap();
-        shader_handler.get_shader().update_buffer_slice(7, self.indices.as_slice()    ).unwrap();
-        shader_handler.get_shader().update_buffer_slice(8, &self.binned_indices[0..((window_size.0 as f32 / CELL_SIZE as f32).ceil() * (window_size.1 as f32 / CELL_SIZE as f32).ceil()) as usize * 64]).unwrap();
+    #[test]
+    fn the_entity_epoch_only_changes_with_the_triangles() {
+        let mut mesh = Mesh::new(MeshParts { normals: crate::face_normals(), ..Default::default() });
+        mesh.set_entities(&[cube_instance(Vec3::new(0.0, 0.0, 0.0), Quat::IDENTITY)]);
+        let epoch = mesh.entity_epoch;
+        
+        mesh.set_entities(&[cube_instance(Vec3::new(3.0, 1.0, -2.0), Quat::IDENTITY)]);
+        assert_eq!(mesh.entity_epoch, epoch, "only moving an entity changed the epoch");
+        
+        // turning it a quarter turn moves which face each triangle snaps to
+        mesh.set_entities(&[cube_instance(Vec3::new(3.0, 1.0, -2.0), Quat::from_axis_angle(Vec3::Y, std::f32::consts::FRAC_PI_2))]);
+        assert_ne!(mesh.entity_epoch, epoch, "turning an entity didn't change the epoch");
+        let epoch = mesh.entity_epoch;
+        
+        mesh.set_entities(&[
+            cube_instance(Vec3::new(3.0, 1.0, -2.0), Quat::from_axis_angle(Vec3::Y, std::f32::consts::FRAC_PI_2)),
+            cube_instance(Vec3::new(0.0, 0.0, 0.0), Quat::IDENTITY),
+        ]);
+        assert_ne!(mesh.entity_epoch, epoch, "adding an entity didn't change the epoch");
+        let epoch = mesh.entity_epoch;
+        
+        mesh.set_entities(&[]);
+        assert_ne!(mesh.entity_epoch, epoch, "removing every entity didn't change the epoch");
+    }
+    
+    #[test]
+    fn entities_in_occluded_chunks_are_culled() {
+        // a column of chunks along z through a block of solid ones: the camera's in an open one at the start,
+        // with a solid one between it and another open one at the end
+        let mut mesh = Mesh::new(MeshParts { normals: crate::face_normals(), ..Default::default() });
+        for x in 0..3 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    mesh.add_chunk(Float4::new(x as f32 * 16.0, y as f32 * 16.0, z as f32 * 16.0, 0.0), Float4::new(16.0, 16.0, 16.0, 0.0));
+                    if !matches!((x, y, z), (1, 1, 0 | 2)) {
+                        let chunk_index = mesh.chunks.len() - 1;
+                        mesh.set_chunk_connectivity(chunk_index, ChunkConnectivity::closed());
+                    }
+                }
+            }
+        }
+        mesh.set_entities(&[
+            cube_instance(Vec3::new(24.0, 22.0, 12.0), Quat::IDENTITY),
+            cube_instance(Vec3::new(24.0, 22.0, 40.0), Quat::IDENTITY),
+        ]);
+        let camera = Camera::new(Vec3::new(24.0, 24.0, 2.0), 70.0, 0.1, 1000.0);
+        mesh.check_remesh(&mut MeshFrame::default(), (WINDOW_START_WIDTH, WINDOW_START_HEIGHT), &camera, false);
+        assert!(!mesh.entity_culled[0], "the entity in the camera's chunk was culled");
+        assert!(mesh.entity_culled[1], "the entity behind the solid chunk wasn't culled");
     }
 }
-
@@ -0,0 +1,276 @@
+use crate::shader_handling::Float4;
+
+/// The faces of a chunk, in the same order as `FACE_OFFSETS`
+/// (-x, +x, -y, +y, -z, +z)
+pub const FACE_COUNT: usize = 6;
+
+static FACE_OFFSETS: [(i32, i32, i32); FACE_COUNT] = [
+    (-1,  0,  0),
+    ( 1,  0,  0),
+    ( 0, -1,  0),
+    ( 0,  1,  0),
+    ( 0,  0, -1),
+    ( 0,  0,  1),
+];
+
+fn opposite_face(face: usize) -> usize {
+    face ^ 1
+}
+
+/// Which pairs of chunk faces can see each other through the open (non solid) tiles of a chunk
+/// this is the "cave culling" graph; a chunk can only be seen through if the face it's entered
+/// from connects to the face being left through
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkConnectivity {
+    bits: u64,  // one bit per (from, to) pair; kept symmetric
+}
+
+impl Default for ChunkConnectivity {
+    fn default() -> Self {
+        Self::fully_open()
+    }
+}
+
+impl ChunkConnectivity {
+    /// Every face connects to every other face (an empty chunk, or one that hasn't been analysed yet)
+    pub fn fully_open() -> Self {
+        ChunkConnectivity { bits: (1u64 << (FACE_COUNT * FACE_COUNT)) - 1 }
+    }
+    
+    /// No face connects to any other (a completely solid chunk)
+    pub fn closed() -> Self {
+        ChunkConnectivity { bits: 0 }
+    }
+    
+    pub fn connected(&self, from: usize, to: usize) -> bool {
+        self.bits & (1 << (from * FACE_COUNT + to)) != 0
+    }
+    
+    fn connect(&mut self, from: usize, to: usize) {
+        self.bits |= 1 << (from * FACE_COUNT + to);
+        self.bits |= 1 << (to * FACE_COUNT + from);
+    }
+    
+    /// Flood fills every open region of the chunk and connects all the faces each region touches
+    pub fn from_tiles(tile_data: &[[[u32; 16]; 16]; 16]) -> Self {
+        let mut connectivity = ChunkConnectivity::closed();
+        let mut visited = [false; 16 * 16 * 16];
+        let mut stack = Vec::with_capacity(16 * 16 * 16);
+        for start in 0..16 * 16 * 16 {
+            let (x, y, z) = (start / 256, (start / 16) % 16, start % 16);
+            if visited[start] || crate::block::is_opaque(tile_data[x][y][z]) {
+                continue;
+            }
+            
+            // walking this open region and collecting the faces it reaches
+            let mut touched = [false; FACE_COUNT];
+            visited[start] = true;
+            stack.push((x, y, z));
+            while let Some((x, y, z)) = stack.pop() {
+                touched[0] |= x == 0;
+                touched[1] |= x == 15;
+                touched[2] |= y == 0;
+                touched[3] |= y == 15;
+                touched[4] |= z == 0;
+                touched[5] |= z == 15;
+                for (dx, dy, dz) in FACE_OFFSETS {
+                    let (nx, ny, nz) = (x as i32 + dx, y as i32 + dy, z as i32 + dz);
+                    if nx < 0 || ny < 0 || nz < 0 || nx > 15 || ny > 15 || nz > 15 {
+                        continue;
+                    }
+                    let (nx, ny, nz) = (nx as usize, ny as usize, nz as usize);
+                    let index = nx * 256 + ny * 16 + nz;
+                    if visited[index] || crate::block::is_opaque(tile_data[nx][ny][nz]) {
+                        continue;
+                    }
+                    visited[index] = true;
+                    stack.push((nx, ny, nz));
+                }
+            }
+            
+            for from in 0..FACE_COUNT {
+                for to in from..FACE_COUNT {
+                    if touched[from] && touched[to] {
+                        connectivity.connect(from, to);
+                    }
+                }
+            }
+        }
+        connectivity
+    }
+}
+
+/// Walks the chunk grid outward from the camera through the face connectivity of each chunk
+/// anything the walk can't reach is hidden behind solid terrain regardless of the frustum
+#[derive(Clone, Debug, Default)]
+pub struct OcclusionCuller {
+    grid: std::collections::HashMap<(i32, i32, i32), usize>,
+    cell_size: Float4,
+    min_cell: (i32, i32, i32),
+    max_cell: (i32, i32, i32),
+}
+
+impl OcclusionCuller {
+    /// Builds the lookup from grid cell to chunk index; assumes every chunk has the same size and is grid aligned
+    pub fn new(chunks: &[(Float4, Float4)]) -> Self {
+        let mut culler = OcclusionCuller {
+            grid: std::collections::HashMap::with_capacity(chunks.len()),
+            cell_size: chunks.first().map(|(_, size)| *size).unwrap_or(Float4::new(16.0, 16.0, 16.0, 0.0)),
+            min_cell: (i32::MAX, i32::MAX, i32::MAX),
+            max_cell: (i32::MIN, i32::MIN, i32::MIN),
+        };
+        for (chunk_index, (position, _size)) in chunks.iter().enumerate() {
+            let cell = culler.cell_of(position);
+            culler.min_cell = (culler.min_cell.0.min(cell.0), culler.min_cell.1.min(cell.1), culler.min_cell.2.min(cell.2));
+            culler.max_cell = (culler.max_cell.0.max(cell.0), culler.max_cell.1.max(cell.1), culler.max_cell.2.max(cell.2));
+            culler.grid.insert(cell, chunk_index);
+        }
+        culler
+    }
+    
+    pub fn chunk_count(&self) -> usize {
+        self.grid.len()
+    }
+    
+    fn cell_of(&self, position: &Float4) -> (i32, i32, i32) {
+        (
+            (position.x / self.cell_size.x).floor() as i32,
+            (position.y / self.cell_size.y).floor() as i32,
+            (position.z / self.cell_size.z).floor() as i32,
+        )
+    }
+    
+    /// The chunk whose grid cell a point falls in, if that cell is loaded
+    pub fn chunk_at(&self, position: Float4) -> Option<usize> {
+        self.grid.get(&self.cell_of(&position)).copied()
+    }
+    
+    /// Marks every chunk the walk couldn't reach as culled, returning how many were newly rejected
+    /// chunks already culled by the frustum aren't walked through (or counted)
+    pub fn cull(&self, camera_position: Float4, connectivity: &[ChunkConnectivity], is_chunk_culled: &mut [bool]) -> usize {
+        if self.grid.is_empty() {
+            return 0;
+        }
+        // the walk is bounded to the loaded region plus a one cell shell of open air around it
+        let min = (self.min_cell.0 - 1, self.min_cell.1 - 1, self.min_cell.2 - 1);
+        let max = (self.max_cell.0 + 1, self.max_cell.1 + 1, self.max_cell.2 + 1);
+        let size = ((max.0 - min.0 + 1) as usize, (max.1 - min.1 + 1) as usize, (max.2 - min.2 + 1) as usize);
+        let flat_index = |cell: (i32, i32, i32)| -> usize {
+            (cell.0 - min.0) as usize * size.1 * size.2 + (cell.1 - min.1) as usize * size.2 + (cell.2 - min.2) as usize
+        };
+        
+        // a camera outside the loaded region starts from the closest cell of the air shell
+        let camera_cell = self.cell_of(&camera_position);
+        let start = (
+            camera_cell.0.clamp(min.0, max.0),
+            camera_cell.1.clamp(min.1, max.1),
+            camera_cell.2.clamp(min.2, max.2),
+        );
+        
+        let mut reached = vec![false; size.0 * size.1 * size.2];
+        let mut queue = std::collections::VecDeque::new();
+        // (cell, face it was entered through, mask of the directions travelled so far)
+        queue.push_back((start, None::<usize>, 0u8));
+        reached[flat_index(start)] = true;
+        while let Some((cell, entered_through, directions)) = queue.pop_front() {
+            let chunk = self.grid.get(&cell).copied();
+            for (face, offset) in FACE_OFFSETS.iter().enumerate() {
+                // never walking back towards the camera keeps the walk from wrapping around solid terrain
+                if directions & (1 << opposite_face(face)) != 0 {
+                    continue;
+                }
+                if let (Some(chunk), Some(entered_through)) = (chunk, entered_through)
+                    && !connectivity[chunk].connected(entered_through, face) {
+                    continue;
+                }
+                let next = (cell.0 + offset.0, cell.1 + offset.1, cell.2 + offset.2);
+                if next.0 < min.0 || next.1 < min.1 || next.2 < min.2 || next.0 > max.0 || next.1 > max.1 || next.2 > max.2 {
+                    continue;
+                }
+                let index = flat_index(next);
+                if reached[index] {
+                    continue;
+                }
+                if let Some(next_chunk) = self.grid.get(&next) && is_chunk_culled[*next_chunk] {
+                    continue;
+                }
+                reached[index] = true;
+                queue.push_back((next, Some(opposite_face(face)), directions | (1 << face)));
+            }
+        }
+        
+        let mut occluded = 0;
+        for (cell, chunk) in &self.grid {
+            if !is_chunk_culled[*chunk] && !reached[flat_index(*cell)] {
+                is_chunk_culled[*chunk] = true;
+                occluded += 1;
+            }
+        }
+        occluded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block;
+    
+    #[test]
+    fn a_solid_chunk_connects_nothing() {
+        assert_eq!(ChunkConnectivity::from_tiles(&[[[block::STONE; 16]; 16]; 16]), ChunkConnectivity::closed());
+    }
+    
+    #[test]
+    fn an_empty_chunk_connects_everything() {
+        assert_eq!(ChunkConnectivity::from_tiles(&[[[block::AIR; 16]; 16]; 16]), ChunkConnectivity::fully_open());
+    }
+    
+    #[test]
+    fn a_tunnel_only_connects_its_ends() {
+        let mut tiles = [[[block::STONE; 16]; 16]; 16];
+        for row in &mut tiles {
+            row[8][8] = block::AIR;
+        }
+        let connectivity = ChunkConnectivity::from_tiles(&tiles);
+        for from in 0..FACE_COUNT {
+            for to in 0..FACE_COUNT {
+                if from != to {
+                    let ends = (from == 0 && to == 1) || (from == 1 && to == 0);
+                    assert_eq!(connectivity.connected(from, to), ends, "faces {} and {}", from, to);
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn chunks_sealed_behind_solid_ones_are_culled() {
+        // a row of chunks along x in the middle of a block of solid ones: the camera's in an open one at the start,
+        // then a tunnel through to another open one, then a solid one sealing off the open one at the end
+        let mut chunks = vec![];
+        let mut connectivity = vec![];
+        for x in 0..5 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    chunks.push((Float4::new(x as f32 * 16.0, y as f32 * 16.0, z as f32 * 16.0, 0.0), Float4::new(16.0, 16.0, 16.0, 0.0)));
+                    connectivity.push(match (x, y, z) {
+                        (0 | 2 | 4, 1, 1) => ChunkConnectivity::fully_open(),
+                        (1, 1, 1) => {
+                            let mut tunnel = ChunkConnectivity::closed();
+                            tunnel.connect(0, 1);
+                            tunnel
+                        }
+                        _ => ChunkConnectivity::closed(),
+                    });
+                }
+            }
+        }
+        let culler = OcclusionCuller::new(&chunks);
+        let mut is_chunk_culled = vec![false; chunks.len()];
+        let culled = culler.cull(Float4::new(8.0, 24.0, 24.0, 0.0), &connectivity, &mut is_chunk_culled);
+        let chunk = |x: usize| x * 9 + 4;
+        assert!(!is_chunk_culled[chunk(2)], "the chunk at the end of the tunnel should be visible");
+        assert!(!is_chunk_culled[chunk(3)], "the solid chunk after it should be visible from it");
+        assert!(is_chunk_culled[chunk(4)], "the chunk sealed off behind the solid one should be culled");
+        assert_eq!(culled, is_chunk_culled.iter().filter(|culled| **culled).count());
+    }
+}
@@ -0,0 +1,242 @@
+use crate::world::{BlockPosition, World};
+
+// a* over the block grid, for anything that has to get somewhere on foot (mobs, automation...)
+// a node is the block an agent's feet are in: air (for the agent's whole height) with something solid underneath
+
+/// What it costs to walk one block straight or diagonally (scaled so the costs stay integers)
+static STRAIGHT_COST: u32 = 10;
+static DIAGONAL_COST: u32 = 14;
+/// Extra cost for climbing more than the agent can step up, so paths only jump when walking around is much longer
+static JUMP_COST: u32 = 10;
+/// Extra cost per block dropped
+static DROP_COST: u32 = 2;
+
+/// How big an agent is and what it can climb, all in whole blocks
+#[derive(Clone, Copy, Debug)]
+pub struct Agent {
+    pub height: i32,       // blocks of headroom it needs
+    pub step_height: i32,  // climbed by just walking
+    pub jump_height: i32,  // climbed by jumping (at least the step height)
+    pub max_drop: i32,     // the furthest it'll fall in one go
+}
+
+impl Agent {
+    /// Something the size of the player (1.8 tall, steps up one block, jumps a bit over one)
+    pub fn player_sized() -> Self {
+        Agent { height: 2, step_height: 1, jump_height: 1, max_drop: 3 }
+    }
+}
+
+/// Why no path was found
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoPath {
+    /// the start isn't somewhere the agent can stand
+    StartNotStandable,
+    /// nor is the goal
+    GoalNotStandable,
+    /// every reachable node was searched without getting to the goal
+    Unreachable { explored: usize },
+    /// gave up after searching `max_nodes` without getting to the goal (it might still be reachable)
+    SearchLimit { explored: usize },
+}
+
+impl std::fmt::Display for NoPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NoPath::StartNotStandable => write!(f, "the start isn't standable"),
+            NoPath::GoalNotStandable => write!(f, "the goal isn't standable"),
+            NoPath::Unreachable { explored } => write!(f, "the goal can't be reached (searched {} nodes)", explored),
+            NoPath::SearchLimit { explored } => write!(f, "gave up after searching {} nodes", explored),
+        }
+    }
+}
+
+/// A walkable route, from the start to the goal (both included)
+#[derive(Clone, Debug)]
+pub struct Path {
+    pub positions: Vec<BlockPosition>,
+    pub cost: u32,
+    pub explored: usize,  // how many nodes the search expanded to find it
+}
+
+/// Whether `height` blocks starting at `position` are all free to move through
+fn is_clear(world: &World, position: BlockPosition, height: i32) -> bool {
+    (0..height).all(|y| !world.is_solid(BlockPosition::new(position.x, position.y + y, position.z)))
+}
+
+pub fn is_standable(world: &World, position: BlockPosition, agent: &Agent) -> bool {
+    is_clear(world, position, agent.height) && world.is_solid(BlockPosition::new(position.x, position.y - 1, position.z))
+}
+
+/// Everywhere the agent can get to in one move from `from`, along with what that move costs
+fn neighbours(world: &World, from: BlockPosition, agent: &Agent, out: &mut Vec<(BlockPosition, u32)>) {
+    out.clear();
+    for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+        let column = BlockPosition::new(from.x + dx, from.y, from.z + dz);
+        if is_standable(world, column, agent) {
+            out.push((column, STRAIGHT_COST));
+            continue;
+        }
+        // climbing needs the headroom above where it's standing as well as somewhere to land
+        let mut climbed = false;
+        for dy in 1..=agent.jump_height.max(agent.step_height) {
+            if !is_clear(world, BlockPosition::new(from.x, from.y + agent.height, from.z), dy) {
+                break;
+            }
+            let target = BlockPosition::new(column.x, from.y + dy, column.z);
+            if is_standable(world, target, agent) {
+                let cost = STRAIGHT_COST + if dy > agent.step_height { JUMP_COST } else { 0 };
+                out.push((target, cost));
+                climbed = true;
+                break;
+            }
+        }
+        if climbed || !is_clear(world, column, agent.height) {
+            continue;
+        }
+        // stepping off an edge falls straight down the neighbouring column
+        for dy in 1..=agent.max_drop {
+            let target = BlockPosition::new(column.x, from.y - dy, column.z);
+            if world.is_solid(target) {
+                break;
+            }
+            if is_standable(world, target, agent) {
+                out.push((target, STRAIGHT_COST + DROP_COST * dy as u32));
+                break;
+            }
+        }
+    }
+    // diagonals stay on the same level and can't cut the corners of anything in the way
+    for (dx, dz) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+        let target = BlockPosition::new(from.x + dx, from.y, from.z + dz);
+        if is_standable(world, target, agent)
+            && is_clear(world, BlockPosition::new(from.x + dx, from.y, from.z), agent.height)
+            && is_clear(world, BlockPosition::new(from.x, from.y, from.z + dz), agent.height) {
+            out.push((target, DIAGONAL_COST));
+        }
+    }
+}
+
+/// A lower bound on the cost between two nodes: the diagonal distance across the ground, ignoring height
+fn estimate(from: BlockPosition, to: BlockPosition) -> u32 {
+    let dx = (from.x - to.x).unsigned_abs();
+    let dz = (from.z - to.z).unsigned_abs();
+    DIAGONAL_COST * dx.min(dz) + STRAIGHT_COST * (dx.max(dz) - dx.min(dz))
+}
+
+/// Finds the cheapest walkable path from `start` to `goal`, expanding at most `max_nodes` nodes
+/// both are where the agent's feet would be, and anything outside of the loaded chunks counts as air
+pub fn find_path(world: &World, start: BlockPosition, goal: BlockPosition, agent: &Agent, max_nodes: usize) -> Result<Path, NoPath> {
+    let _span = crate::logging::span("pathfinding", "find_path");
+    if !is_standable(world, start, agent) {
+        return Err(NoPath::StartNotStandable);
+    }
+    if !is_standable(world, goal, agent) {
+        return Err(NoPath::GoalNotStandable);
+    }
+    
+    // (cost so far, where it came from) for every node seen
+    let mut visited: std::collections::HashMap<BlockPosition, (u32, Option<BlockPosition>)> = std::collections::HashMap::new();
+    // ordered by the estimated total cost; the reverse makes the max heap pop the cheapest first
+    let mut open = std::collections::BinaryHeap::new();
+    visited.insert(start, (0, None));
+    open.push(std::cmp::Reverse((estimate(start, goal), 0u32, (start.x, start.y, start.z))));
+    let mut explored = 0;
+    let mut next = vec![];
+    while let Some(std::cmp::Reverse((_, cost, (x, y, z)))) = open.pop() {
+        let current = BlockPosition::new(x, y, z);
+        if cost > visited[&current].0 {
+            continue;  // a cheaper way here was already found after this one got queued
+        }
+        if current == goal {
+            let mut positions = vec![current];
+            while let Some(previous) = visited[positions.last().unwrap()].1 {
+                positions.push(previous);
+            }
+            positions.reverse();
+            return Ok(Path { positions, cost, explored });
+        }
+        explored += 1;
+        if explored > max_nodes {
+            return Err(NoPath::SearchLimit { explored: max_nodes });
+        }
+        neighbours(world, current, agent, &mut next);
+        for (neighbour, step_cost) in next.iter().copied() {
+            let new_cost = cost + step_cost;
+            if visited.get(&neighbour).is_some_and(|(known_cost, _)| *known_cost <= new_cost) {
+                continue;
+            }
+            visited.insert(neighbour, (new_cost, Some(current)));
+            open.push(std::cmp::Reverse((new_cost + estimate(neighbour, goal), new_cost, (neighbour.x, neighbour.y, neighbour.z))));
+        }
+    }
+    Err(NoPath::Unreachable { explored })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block;
+    
+    fn wall(world: &mut World, x: i32, height: i32, z_range: std::ops::Range<i32>) {
+        for y in 1..=height {
+            for z in z_range.clone() {
+                world.set_block(BlockPosition::new(x, y, z), block::GRASS);
+            }
+        }
+    }
+    
+    // on the test floor, across three chunk boundaries from each other
+    const START: BlockPosition = BlockPosition { x: -10, y: 1, z: 8 };
+    const GOAL: BlockPosition = BlockPosition { x: 40, y: 1, z: 8 };
+    
+    #[test]
+    fn flat_paths_are_straight_across_chunk_boundaries() {
+        let path = find_path(&World::test_floor(block::GRASS), START, GOAL, &Agent::player_sized(), 10_000).expect("no path over a flat floor");
+        assert_eq!(path.positions.len(), 51);
+        assert_eq!(path.cost, 50 * STRAIGHT_COST);
+    }
+    
+    #[test]
+    fn walls_get_walked_around_through_their_gap() {
+        let mut world = World::test_floor(block::GRASS);
+        wall(&mut world, 15, 2, 0..14);
+        let path = find_path(&world, START, GOAL, &Agent::player_sized(), 10_000).expect("no path around the wall");
+        assert!(path.positions.iter().any(|position| position.x == 15 && position.z >= 14), "didn't go through the gap: {:?}", path.positions);
+    }
+    
+    #[test]
+    fn one_block_ledges_get_climbed() {
+        let mut world = World::test_floor(block::GRASS);
+        for x in 10..20 {
+            wall(&mut world, x, 1, 0..16);
+        }
+        let path = find_path(&world, START, GOAL, &Agent::player_sized(), 10_000).expect("no path over the platform");
+        assert!(path.positions.iter().any(|position| position.y == 2), "never climbed onto the platform: {:?}", path.positions);
+        assert_eq!(path.positions.last(), Some(&GOAL));
+    }
+    
+    #[test]
+    fn walls_too_high_to_climb_are_unreachable() {
+        let mut world = World::test_floor(block::GRASS);
+        wall(&mut world, 15, 2, 0..16);
+        let agent = Agent::player_sized();
+        let result = find_path(&world, START, GOAL, &agent, 100_000);
+        assert!(matches!(result, Err(NoPath::Unreachable { .. })), "crossing a 2 high wall should be unreachable, got {:?}", result.map(|path| path.positions));
+        // but a taller jumper goes straight over it
+        let jumper = Agent { jump_height: 2, ..agent };
+        assert!(find_path(&world, START, GOAL, &jumper, 10_000).is_ok(), "a 2 block jumper couldn't get over the wall");
+    }
+    
+    #[test]
+    fn the_search_stops_at_its_cap() {
+        let result = find_path(&World::test_floor(block::GRASS), START, GOAL, &Agent::player_sized(), 20);
+        assert!(matches!(result, Err(NoPath::SearchLimit { explored: 20 })), "expected to hit the 20 node cap, got {:?}", result.map(|path| path.positions));
+    }
+
+    #[test]
+    fn goals_inside_terrain_are_rejected() {
+        let result = find_path(&World::test_floor(block::GRASS), START, BlockPosition::new(5, 0, 8), &Agent::player_sized(), 10_000);
+        assert!(matches!(result, Err(NoPath::GoalNotStandable)), "a goal inside the floor should be rejected, got {:?}", result.map(|path| path.positions));
+    }
+}
@@ -0,0 +1,286 @@
+use crate::block;
+use crate::block_shape::{self, ShapeBox};
+use crate::math::Vec3;
+use crate::world::{BlockPosition, World};
+
+/// The gap kept between a box and whatever it ran into, so float error never leaves them overlapping
+const SKIN: f32 = 0.001;
+
+/// An axis aligned box in world space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl BoundingBox {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        BoundingBox { min, max }
+    }
+    
+    /// A box standing on `base` (the center of its bottom face)
+    pub fn from_base(base: Vec3, width: f32, height: f32) -> Self {
+        let half_width = width * 0.5;
+        BoundingBox {
+            min: Vec3::new(base.x - half_width, base.y, base.z - half_width),
+            max: Vec3::new(base.x + half_width, base.y + height, base.z + half_width),
+        }
+    }
+    
+    pub fn of_block(position: BlockPosition) -> Self {
+        let center = position.center();
+        BoundingBox { min: center - Vec3::splat(0.5), max: center + Vec3::splat(0.5) }
+    }
+    
+    /// One of a block's shape boxes (measured in sixteenths from its lowest corner) in world space
+    pub fn of_shape_box(position: BlockPosition, shape_box: &ShapeBox) -> Self {
+        let corner = position.center() - Vec3::splat(0.5);
+        let sixteenths = |point: [u8; 3]| Vec3::new(point[0] as f32, point[1] as f32, point[2] as f32) / 16.0;
+        BoundingBox { min: corner + sixteenths(shape_box.from), max: corner + sixteenths(shape_box.to) }
+    }
+    
+    pub fn offset(self, offset: Vec3) -> Self {
+        BoundingBox { min: self.min + offset, max: self.max + offset }
+    }
+    
+    pub fn union(self, other: BoundingBox) -> Self {
+        BoundingBox { min: self.min.min(other.min), max: self.max.max(other.max) }
+    }
+    
+    /// Whether the boxes share any space (just touching doesn't count)
+    pub fn overlaps(self, other: BoundingBox) -> bool {
+        self.min.x < other.max.x && other.min.x < self.max.x
+            && self.min.y < other.max.y && other.min.y < self.max.y
+            && self.min.z < other.max.z && other.min.z < self.max.z
+    }
+}
+
+fn component(vector: Vec3, axis: usize) -> f32 {
+    match axis {
+        0 => vector.x,
+        1 => vector.y,
+        _ => vector.z,
+    }
+}
+
+fn along(axis: usize, amount: f32) -> Vec3 {
+    match axis {
+        0 => Vec3::new(amount, 0.0, 0.0),
+        1 => Vec3::new(0.0, amount, 0.0),
+        _ => Vec3::new(0.0, 0.0, amount),
+    }
+}
+
+/// The collision boxes of every solid block touching `region`, following their shapes so slabs and stairs only stop what runs into their actual parts
+fn solid_blocks(world: &World, region: BoundingBox) -> Vec<BoundingBox> {
+    // starting a block lower, since a fence below can reach up into the region
+    let min = BlockPosition::containing(region.min - Vec3::new(0.0, 1.0, 0.0));
+    let max = BlockPosition::containing(region.max);
+    let mut blocks = vec![];
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                let position = BlockPosition::new(x, y, z);
+                let cell = world.block(position);
+                if !block::is_solid(cell) {
+                    continue;
+                }
+                let neighbours = position.horizontal_neighbours().map(|neighbour| world.block(neighbour));
+                for shape_box in block_shape::collision_boxes(cell, neighbours) {
+                    blocks.push(BoundingBox::of_shape_box(position, &shape_box));
+                }
+            }
+        }
+    }
+    blocks
+}
+
+/// How far `bounds` can move along `axis` (up to `delta`) before running into one of `obstacles`
+/// anything already overlapping the box is ignored, so something stuck inside a block can still get out
+fn clip_axis(bounds: &BoundingBox, obstacles: &[BoundingBox], axis: usize, mut delta: f32) -> f32 {
+    for obstacle in obstacles {
+        // only boxes lined up with it on the other two axes can be hit
+        let lined_up = (0..3).filter(|other| *other != axis).all(|other| {
+            component(bounds.max, other) > component(obstacle.min, other) && component(bounds.min, other) < component(obstacle.max, other)
+        });
+        if !lined_up {
+            continue;
+        }
+        if delta > 0.0 {
+            let gap = component(obstacle.min, axis) - component(bounds.max, axis);
+            if gap >= -SKIN {
+                delta = delta.min((gap - SKIN).max(0.0));
+            }
+        } else if delta < 0.0 {
+            let gap = component(bounds.min, axis) - component(obstacle.max, axis);
+            if gap >= -SKIN {
+                delta = delta.max(-(gap - SKIN).max(0.0));
+            }
+        }
+    }
+    delta
+}
+
+/// Where a move ended up and what it ran into
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MoveResult {
+    pub offset: Vec3,        // how far the box actually moved
+    pub blocked: [bool; 3],  // which axes got cut short
+    pub on_ground: bool,     // stopped by something below while moving down
+}
+
+/// Sweeps `bounds` by `delta` one axis at a time, stopping at solid blocks (so it can't tunnel however far it moves)
+/// with a `step_height` it'll climb ledges up to that high instead of stopping at them, like walking up a block
+pub fn move_and_collide(world: &World, bounds: BoundingBox, delta: Vec3, step_height: f32) -> MoveResult {
+    let region = bounds.union(bounds.offset(delta)).union(bounds.offset(delta + Vec3::new(0.0, step_height.max(0.0), 0.0)));
+    let obstacles = solid_blocks(world, BoundingBox::new(region.min - Vec3::splat(SKIN), region.max + Vec3::splat(SKIN)));
+    
+    // vertical first, so sliding along the ground doesn't catch on the edges of the blocks underneath
+    let sweep = |start: BoundingBox, order: [usize; 3], delta: Vec3| -> (BoundingBox, Vec3) {
+        let mut moved = start;
+        let mut offset = Vec3::ZERO;
+        for axis in order {
+            let amount = clip_axis(&moved, &obstacles, axis, component(delta, axis));
+            moved = moved.offset(along(axis, amount));
+            offset += along(axis, amount);
+        }
+        (moved, offset)
+    };
+    let (_, offset) = sweep(bounds, [1, 0, 2], delta);
+    let blocked = [0, 1, 2].map(|axis| (component(offset, axis) - component(delta, axis)).abs() > 1e-5);
+    let mut result = MoveResult {
+        offset,
+        blocked,
+        on_ground: blocked[1] && delta.y < 0.0,
+    };
+    
+    if step_height > 0.0 && (blocked[0] || blocked[2]) {
+        // trying the move again from `step_height` up, then dropping back down onto whatever it's now on top of
+        let (raised, up) = sweep(bounds, [1, 0, 2], Vec3::new(0.0, step_height, 0.0));
+        let (across, sideways) = sweep(raised, [0, 2, 1], Vec3::new(delta.x, 0.0, delta.z));
+        let (_, down) = sweep(across, [1, 0, 2], Vec3::new(0.0, -up.y + delta.y.min(0.0), 0.0));
+        let stepped_offset = up + sideways + down;
+        let horizontal = |offset: Vec3| offset.x * offset.x + offset.z * offset.z;
+        if horizontal(stepped_offset) > horizontal(offset) + 1e-6 && stepped_offset.y > 1e-5 {
+            result = MoveResult {
+                offset: stepped_offset,
+                blocked: [
+                    (sideways.x - delta.x).abs() > 1e-5,
+                    false,
+                    (sideways.z - delta.z).abs() > 1e-5,
+                ],
+                on_ground: true,
+            };
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_state::{self, FACING, HALF};
+    use crate::chunk::Chunk;
+    use crate::shader_handling::Float4;
+    
+    /// One chunk with a floor at y = 0, so its top is at y = 0.5
+    fn flat_chunk() -> World {
+        let mut world = World::new(vec![Chunk::new(Float4::new(0.0, 0.0, 0.0, 0.0), 0)]);
+        for x in 0..16 {
+            for z in 0..16 {
+                world.set_block(BlockPosition::new(x, 0, z), block::GRASS);
+            }
+        }
+        world
+    }
+    
+    /// The floor with a one block thick wall at x = 12
+    fn walled_chunk() -> World {
+        let mut world = flat_chunk();
+        for y in 1..4 {
+            for z in 0..16 {
+                world.set_block(BlockPosition::new(12, y, z), block::GRASS);
+            }
+        }
+        world
+    }
+    
+    #[test]
+    fn fast_moves_dont_tunnel_through_walls() {
+        let world = walled_chunk();
+        let bounds = BoundingBox::from_base(Vec3::new(4.0, 0.5, 8.0), 0.6, 1.8);
+        let result = move_and_collide(&world, bounds, Vec3::new(100.0, 0.0, 0.0), 0.0);
+        assert!(result.blocked[0], "a 100 block move wasn't stopped");
+        assert!(bounds.max.x + result.offset.x <= 11.5, "moved {} and ended inside or past the wall", result.offset.x);
+        assert!(bounds.max.x + result.offset.x > 11.5 - 0.01, "stopped {} short of the wall", 11.5 - bounds.max.x - result.offset.x);
+    }
+    
+    #[test]
+    fn sliding_along_a_wall_keeps_the_other_axis() {
+        let world = walled_chunk();
+        let bounds = BoundingBox::from_base(Vec3::new(11.0, 0.5, 4.0), 0.6, 1.8);
+        let result = move_and_collide(&world, bounds, Vec3::new(1.0, -0.1, 2.0), 0.0);
+        assert!(result.blocked[0] && !result.blocked[2], "blocked {:?}", result.blocked);
+        assert!((result.offset.z - 2.0).abs() < 1e-5, "only slid {} along the wall", result.offset.z);
+        assert!(result.on_ground, "standing on the floor should count as on the ground");
+    }
+    
+    /// Where the bottom of a thin box dropped straight down over `(x, z)` comes to rest
+    fn landing_height(world: &World, x: f32, z: f32) -> f32 {
+        let bounds = BoundingBox::from_base(Vec3::new(x, 5.0, z), 0.2, 0.2);
+        let result = move_and_collide(world, bounds, Vec3::new(0.0, -10.0, 0.0), 0.0);
+        assert!(result.on_ground, "fell through everything at {}, {}", x, z);
+        bounds.min.y + result.offset.y
+    }
+
+    #[test]
+    fn slabs_only_collide_with_their_half() {
+        let mut world = flat_chunk();
+        world.set_block(BlockPosition::new(4, 1, 8), block::STONE_SLAB);
+        world.set_block(BlockPosition::new(8, 1, 8), block_state::with(block::STONE_SLAB, &HALF, 1));
+        assert!((landing_height(&world, 4.0, 8.0) - 1.0).abs() < 0.01, "landed at {} on a bottom slab", landing_height(&world, 4.0, 8.0));
+        assert!((landing_height(&world, 8.0, 8.0) - 1.5).abs() < 0.01, "landed at {} on a top slab", landing_height(&world, 8.0, 8.0));
+        
+        // there's room under a top slab
+        let low = BoundingBox::from_base(Vec3::new(6.0, 0.5, 8.0), 0.2, 0.4);
+        let result = move_and_collide(&world, low, Vec3::new(4.0, 0.0, 0.0), 0.0);
+        assert!(!result.blocked[0], "a box half a block tall couldn't pass under a top slab");
+    }
+    
+    #[test]
+    fn stairs_collide_with_their_base_and_step() {
+        let mut world = flat_chunk();
+        // the step on the +x half of one, and the -z half of the other
+        world.set_block(BlockPosition::new(4, 1, 8), block::COBBLESTONE_STAIRS);
+        world.set_block(BlockPosition::new(8, 1, 8), block_state::with(block::COBBLESTONE_STAIRS, &FACING, 3));
+        let heights = [(4.25, 8.0, 1.5), (3.75, 8.0, 1.0), (8.0, 7.75, 1.5), (8.0, 8.25, 1.0)];
+        for (x, z, expected) in heights {
+            let landed = landing_height(&world, x, z);
+            assert!((landed - expected).abs() < 0.01, "landed at {} over {}, {}, expected {}", landed, x, z, expected);
+        }
+    }
+    
+    #[test]
+    fn slabs_get_stepped_up_but_fences_dont() {
+        let mut world = flat_chunk();
+        // slabs along one half of the chunk, and a fence across all of it further on
+        for z in 0..16 {
+            if z >= 8 {
+                world.set_block(BlockPosition::new(6, 1, z), block::STONE_SLAB);
+            }
+            world.set_block(BlockPosition::new(10, 1, z), block::FENCE);
+        }
+        let bounds = BoundingBox::from_base(Vec3::new(4.0, 0.5, 12.0), 0.6, 1.8);
+        let onto_slab = move_and_collide(&world, bounds, Vec3::new(2.0, 0.0, 0.0), 1.0);
+        assert!((onto_slab.offset.y - 0.5).abs() < 0.01 && (onto_slab.offset.x - 2.0).abs() < 1e-5, "didn't step up onto the slab: {:?}", onto_slab.offset);
+        
+        let bounds = BoundingBox::from_base(Vec3::new(4.0, 0.5, 4.0), 0.6, 1.8);
+        let into_fence = move_and_collide(&world, bounds, Vec3::new(8.0, 0.0, 0.0), 1.0);
+        assert!(into_fence.blocked[0], "stepped over a fence");
+        assert!(bounds.max.x + into_fence.offset.x <= 10.0 - 2.0 / 16.0, "ended at {} inside the fence", bounds.max.x + into_fence.offset.x);
+        assert_eq!(into_fence.offset.y, 0.0);
+        
+        // a fence is a block and a half tall to stand on too
+        assert!((landing_height(&world, 10.0, 8.0) - 2.0).abs() < 0.01, "landed at {} on a fence", landing_height(&world, 10.0, 8.0));
+    }
+}
@@ -0,0 +1,223 @@
+use crate::camera::{Camera, CameraInput};
+use crate::math::Vec3;
+use crate::physics::{move_and_collide, BoundingBox};
+use crate::world::World;
+
+pub static PLAYER_WIDTH: f32 = 0.6;
+pub static PLAYER_HEIGHT: f32 = 1.8;
+/// How far above the bottom of the box the camera sits
+pub static EYE_HEIGHT: f32 = 1.62;
+/// Ledges up to this high get walked up rather than blocking (one block)
+static STEP_HEIGHT: f32 = 1.0;
+static GRAVITY: f32 = 32.0;
+static TERMINAL_VELOCITY: f32 = 60.0;
+/// Enough to clear a bit over one block
+static JUMP_SPEED: f32 = 9.0;
+static WALK_SPEED: f32 = 4.5;
+static SPRINT_MULTIPLIER: f32 = 1.5;
+/// How quickly the horizontal velocity catches up to the input (per second), on the ground and in the air
+static GROUND_ACCELERATION: f32 = 14.0;
+static AIR_ACCELERATION: f32 = 3.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovementMode {
+    /// gravity and collision
+    Walk,
+    /// the free flying camera, straight through terrain
+    Noclip,
+}
+
+pub struct Player {
+    pub position: Vec3,  // the center of the bottom of the box
+    pub velocity: Vec3,
+    pub on_ground: bool,
+    pub mode: MovementMode,
+}
+
+impl Player {
+    pub fn new(position: Vec3) -> Self {
+        Player { position, velocity: Vec3::ZERO, on_ground: false, mode: MovementMode::Noclip }
+    }
+    
+    pub fn bounds(&self) -> BoundingBox {
+        BoundingBox::from_base(self.position, PLAYER_WIDTH, PLAYER_HEIGHT)
+    }
+    
+    pub fn eye_position(&self) -> Vec3 {
+        self.position + Vec3::new(0.0, EYE_HEIGHT, 0.0)
+    }
+    
+    /// Switches between walking and noclip; the camera carries on from wherever the player was looking from
+    pub fn toggle_noclip(&mut self, camera: &mut Camera) {
+        match self.mode {
+            MovementMode::Walk => {
+                self.mode = MovementMode::Noclip;
+                camera.velocity = Vec3::ZERO;
+            },
+            MovementMode::Noclip => {
+                self.mode = MovementMode::Walk;
+                self.position = camera.position - Vec3::new(0.0, EYE_HEIGHT, 0.0);
+                self.velocity = Vec3::ZERO;
+                self.on_ground = false;
+            },
+        }
+    }
+    
+    /// Runs one simulation tick and moves the camera along with the player
+    /// noclip is just the camera's own flying; walking uses the movement axes relative to where the camera faces, and up to jump
+    pub fn step(&mut self, world: &World, input: &CameraInput, camera: &mut Camera, delta_time: f32) {
+        match self.mode {
+            MovementMode::Noclip => {
+                camera.step(input, delta_time);
+                self.position = camera.position - Vec3::new(0.0, EYE_HEIGHT, 0.0);
+            },
+            MovementMode::Walk => {
+                self.walk(world, input, camera.forward_flat(), camera.left_flat(), delta_time);
+                camera.position = self.eye_position();
+            },
+        }
+    }
+    
+    /// One tick of walking, with `forward` and `left` being the flat directions the movement axes are relative to
+    pub fn walk(&mut self, world: &World, input: &CameraInput, forward: Vec3, left: Vec3, delta_time: f32) {
+        let mut wish = forward * input.forward + left * input.left;
+        if wish.length_squared() > 1.0 {
+            wish = wish.normalized();  // diagonal movement shouldn't be faster
+        }
+        let speed = WALK_SPEED * if input.sprint { SPRINT_MULTIPLIER } else { 1.0 };
+        let target = wish * speed;
+        let acceleration = if self.on_ground { GROUND_ACCELERATION } else { AIR_ACCELERATION };
+        let blend = (acceleration * delta_time).min(1.0);
+        self.velocity.x += (target.x - self.velocity.x) * blend;
+        self.velocity.z += (target.z - self.velocity.z) * blend;
+        
+        if self.on_ground && input.up > 0.0 {
+            self.velocity.y = JUMP_SPEED;
+        }
+        self.velocity.y = (self.velocity.y - GRAVITY * delta_time).max(-TERMINAL_VELOCITY);
+        
+        let step_height = if self.on_ground { STEP_HEIGHT } else { 0.0 };
+        let result = move_and_collide(world, self.bounds(), self.velocity * delta_time, step_height);
+        self.position += result.offset;
+        self.on_ground = result.on_ground;
+        // whatever got hit takes the velocity along that axis with it
+        if result.blocked[0] {
+            self.velocity.x = 0.0;
+        }
+        if result.blocked[1] {
+            self.velocity.y = 0.0;
+        }
+        if result.blocked[2] {
+            self.velocity.z = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block;
+    use crate::world::BlockPosition;
+    
+    const TICK: f32 = 1.0 / 20.0;
+    
+    /// Fills the slab of the floor's width from `x_range` at every height in `y_range`
+    fn fill(world: &mut World, x_range: std::ops::Range<i32>, y_range: std::ops::Range<i32>) {
+        for x in x_range {
+            for y in y_range.clone() {
+                for z in 0..16 {
+                    world.set_block(BlockPosition::new(x, y, z), block::GRASS);
+                }
+            }
+        }
+    }
+    
+    fn walking_player(position: Vec3) -> Player {
+        let mut player = Player::new(position);
+        player.mode = MovementMode::Walk;
+        player.on_ground = true;
+        player
+    }
+    
+    /// Walks `player` east (+x) for `ticks` ticks
+    fn walk_east(player: &mut Player, world: &World, ticks: usize) {
+        let input = CameraInput { forward: 1.0, ..CameraInput::default() };
+        for _ in 0..ticks {
+            player.walk(world, &input, Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), TICK);
+        }
+    }
+    
+    #[test]
+    fn falling_lands_on_the_ground() {
+        let world = World::test_floor(block::GRASS);
+        let mut player = Player::new(Vec3::new(4.0, 10.0, 8.0));
+        player.mode = MovementMode::Walk;
+        for _ in 0..60 {
+            player.walk(&world, &CameraInput::default(), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), TICK);
+        }
+        assert!(player.on_ground, "not on the ground after falling");
+        assert!((player.position.y - 0.5).abs() < 0.01, "fell to y = {}, expected to rest on the floor at 0.5", player.position.y);
+        assert_eq!(player.velocity.y, 0.0);
+    }
+    
+    #[test]
+    fn walls_stop_the_player_in_front_of_them() {
+        let mut world = World::test_floor(block::GRASS);
+        fill(&mut world, 8..9, 1..3);
+        let mut player = walking_player(Vec3::new(4.0, 0.5, 8.0));
+        walk_east(&mut player, &world, 60);
+        let wall_face = 7.5 - PLAYER_WIDTH * 0.5;
+        assert!(player.position.x <= wall_face && player.position.x > wall_face - 0.01, "stopped at x = {}, expected just before {}", player.position.x, wall_face);
+        assert!((player.position.y - 0.5).abs() < 0.01, "climbed a two block wall to y = {}", player.position.y);
+        assert_eq!(player.velocity.x, 0.0);
+    }
+    
+    #[test]
+    fn one_block_ledges_get_stepped_up() {
+        let mut world = World::test_floor(block::GRASS);
+        fill(&mut world, 8..16, 1..2);
+        let mut player = walking_player(Vec3::new(4.0, 0.5, 8.0));
+        walk_east(&mut player, &world, 40);
+        assert!(player.position.x > 9.0, "only got to x = {}", player.position.x);
+        assert!((player.position.y - 1.5).abs() < 0.01, "ended at y = {}, expected on top of the ledge at 1.5", player.position.y);
+    }
+    
+    #[test]
+    fn two_block_ledges_dont_get_stepped_up() {
+        let mut world = World::test_floor(block::GRASS);
+        fill(&mut world, 8..16, 1..3);
+        let mut player = walking_player(Vec3::new(4.0, 0.5, 8.0));
+        walk_east(&mut player, &world, 40);
+        assert!(player.position.x < 7.5 - PLAYER_WIDTH * 0.5, "walked into the ledge to x = {}", player.position.x);
+        assert!((player.position.y - 0.5).abs() < 0.01, "climbed to y = {}", player.position.y);
+    }
+    
+    #[test]
+    fn walking_across_chunk_boundaries() {
+        // the floor is continuous across the chunk at x = 16 and a wall stands just past the next one at x = 32
+        let mut world = World::test_floor(block::GRASS);
+        fill(&mut world, 32..33, 1..3);
+        let mut player = walking_player(Vec3::new(12.0, 0.5, 8.0));
+        walk_east(&mut player, &world, 100);
+        let wall_face = 31.5 - PLAYER_WIDTH * 0.5;
+        assert!(player.position.x <= wall_face && player.position.x > wall_face - 0.01, "stopped at x = {}, expected just before the wall in the next chunk at {}", player.position.x, wall_face);
+        assert!((player.position.y - 0.5).abs() < 0.01, "ended at y = {} after crossing into the next chunk", player.position.y);
+        assert!(player.on_ground);
+    }
+    
+    #[test]
+    fn jumping_into_a_ceiling_comes_back_down() {
+        let mut world = World::test_floor(block::GRASS);
+        fill(&mut world, 0..16, 3..4);
+        let mut player = walking_player(Vec3::new(8.0, 0.5, 8.0));
+        let jump = CameraInput { up: 1.0, ..CameraInput::default() };
+        let idle = CameraInput::default();
+        let mut highest: f32 = 0.0;
+        for tick in 0..40 {
+            player.walk(&world, if tick == 0 { &jump } else { &idle }, Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), TICK);
+            highest = highest.max(player.position.y + PLAYER_HEIGHT);
+        }
+        assert!(highest <= 2.5, "the head reached {} through a ceiling at 2.5", highest);
+        assert!(player.on_ground, "didn't land after bumping the ceiling");
+    }
+}
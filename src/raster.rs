@@ -0,0 +1,230 @@
+use crate::shader_handling::Float2;
+
+// a cpu mirror of the coverage rules in triangles.metal, so the fill rule and the perspective correction can be tested without a gpu
+// (only built for the tests)
+// anything changed here has to be changed in the kernel as well (and the other way around)
+
+/// Sub-pixel precision of the rasterizer, in bits (SUBPIXEL_BITS in the kernel)
+pub const SUBPIXEL_BITS: i64 = 8;
+const SUBPIXEL_SCALE: i64 = 1 << SUBPIXEL_BITS;
+const SUBPIXEL_HALF: i64 = SUBPIXEL_SCALE / 2;
+/// How far off screen (in pixels) a vertex can be before it gets clamped, so the kernel's fixed point coordinates fit in an int
+const GUARD_BAND: f32 = 4194304.0;
+
+/// Snaps a screen space position onto the sub-pixel grid
+pub fn to_fixed(position: Float2) -> (i64, i64) {
+    (
+        (position.x.clamp(-GUARD_BAND, GUARD_BAND) * SUBPIXEL_SCALE as f32).round() as i64,
+        (position.y.clamp(-GUARD_BAND, GUARD_BAND) * SUBPIXEL_SCALE as f32).round() as i64,
+    )
+}
+
+/// Twice the signed area of (a, b, p); positive when p is on the inner side of a -> b for triangles wound like `rasterize_triangle` expects
+fn edge(a: (i64, i64), b: (i64, i64), p: (i64, i64)) -> i64 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+/// Pixels exactly on an edge only belong to the triangle if it's a top or left edge, so shared edges are only drawn once
+fn is_top_left(a: (i64, i64), b: (i64, i64)) -> bool {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    (dy == 0 && dx > 0) || dy < 0
+}
+
+/// The first pixel whose center is at or after `min` and the last whose center is at or before `max` (both in fixed point)
+fn pixel_span(min: i64, max: i64) -> (i64, i64) {
+    ((min - SUBPIXEL_HALF + SUBPIXEL_SCALE - 1) >> SUBPIXEL_BITS, (max - SUBPIXEL_HALF) >> SUBPIXEL_BITS)
+}
+
+/// Calls `plot(x, y, barycentrics)` for every pixel of a `width` x `height` target whose center the triangle covers
+/// the barycentrics are weights for the vertices in the order they were given, whichever way they're wound
+pub fn rasterize_triangle(vertices: [Float2; 3], width: u32, height: u32, mut plot: impl FnMut(u32, u32, [f32; 3])) {
+    let mut points = vertices.map(to_fixed);
+    let mut order = [0, 1, 2];
+    let mut area = edge(points[0], points[1], points[2]);
+    if area == 0 {
+        return;  // degenerate, covers nothing
+    }
+    // one winding means one case for both the edge tests and the fill rule
+    if area < 0 {
+        points.swap(1, 2);
+        order.swap(1, 2);
+        area = -area;
+    }
+    let inverse_area = 1.0 / area as f32;
+    
+    let (min_x, max_x) = pixel_span(points.iter().map(|p| p.0).min().unwrap(), points.iter().map(|p| p.0).max().unwrap());
+    let (min_y, max_y) = pixel_span(points.iter().map(|p| p.1).min().unwrap(), points.iter().map(|p| p.1).max().unwrap());
+    let (min_x, max_x) = (min_x.max(0), max_x.min(width as i64 - 1));
+    let (min_y, max_y) = (min_y.max(0), max_y.min(height as i64 - 1));
+    
+    let bias = [
+        if is_top_left(points[1], points[2]) { 0 } else { -1 },
+        if is_top_left(points[2], points[0]) { 0 } else { -1 },
+        if is_top_left(points[0], points[1]) { 0 } else { -1 },
+    ];
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            let sample = (x * SUBPIXEL_SCALE + SUBPIXEL_HALF, y * SUBPIXEL_SCALE + SUBPIXEL_HALF);
+            let edges = [
+                edge(points[1], points[2], sample),
+                edge(points[2], points[0], sample),
+                edge(points[0], points[1], sample),
+            ];
+            if edges[0] + bias[0] < 0 || edges[1] + bias[1] < 0 || edges[2] + bias[2] < 0 {
+                continue;
+            }
+            let mut barycentrics = [0.0; 3];
+            for i in 0..3 {
+                barycentrics[order[i]] = edges[i] as f32 * inverse_area;
+            }
+            plot(x as u32, y as u32, barycentrics);
+        }
+    }
+}
+
+/// Turns screen space barycentrics into ones that are linear in view space, using each vertex's 1/w (perspective_weights in the kernel)
+pub fn perspective_weights(screen_weights: [f32; 3], inverse_w: [f32; 3]) -> [f32; 3] {
+    let weighted = [screen_weights[0] * inverse_w[0], screen_weights[1] * inverse_w[1], screen_weights[2] * inverse_w[2]];
+    let total = weighted[0] + weighted[1] + weighted[2];
+    [weighted[0] / total, weighted[1] / total, weighted[2] / total]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{Mat4, Vec3};
+    use crate::meshing::{ndc_to_screen, transform_vertex};
+    use crate::shader_handling::Float4;
+    
+    /// Tessellates a quad into a jittered grid of triangles (with some vertices and edges landing exactly on pixel centers,
+    /// alternating diagonals and windings) and makes sure every pixel inside the quad gets drawn exactly once
+    fn assert_quad_covered_once(width: u32, height: u32, columns: usize, rows: usize) {
+        // the quad's own corners are kept off the pixel centers so which pixels it should cover isn't ambiguous
+        let (left, top) = (3.3, 2.7);
+        let (right, bottom) = (width as f32 - 4.1, height as f32 - 3.9);
+        
+        let mut seed = 0x2545F491u32;
+        let mut jitter = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            (seed % 1000) as f32 / 1000.0 - 0.5
+        };
+        let mut grid = vec![Float2::new(0.0, 0.0); (columns + 1) * (rows + 1)];
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let mut x = left + (right - left) * column as f32 / columns as f32;
+                let mut y = top + (bottom - top) * row as f32 / rows as f32;
+                if column != 0 && column != columns && row != 0 && row != rows {
+                    if (row + column) % 3 == 0 {
+                        // snapped onto a pixel center, so edges pass exactly through samples
+                        x = x.floor() + 0.5;
+                        y = y.floor() + 0.5;
+                    } else {
+                        x += jitter() * (right - left) / columns as f32 * 0.5;
+                        y += jitter() * (bottom - top) / rows as f32 * 0.5;
+                    }
+                }
+                grid[row * (columns + 1) + column] = Float2::new(x, y);
+            }
+        }
+        
+        let mut coverage = vec![0u32; (width * height) as usize];
+        for row in 0..rows {
+            for column in 0..columns {
+                let corner = |c: usize, r: usize| grid[(row + r) * (columns + 1) + column + c];
+                let (a, b, c, d) = (corner(0, 0), corner(1, 0), corner(1, 1), corner(0, 1));
+                let triangles = if (row + column) % 2 == 0 {
+                    [[a, b, c], [a, d, c]]  // the second one is wound the other way
+                } else {
+                    [[a, b, d], [b, c, d]]
+                };
+                for triangle in triangles {
+                    rasterize_triangle(triangle, width, height, |x, y, _| coverage[(y * width + x) as usize] += 1);
+                }
+            }
+        }
+        
+        let mut errors = vec![];
+        for y in 0..height {
+            for x in 0..width {
+                let (center_x, center_y) = (x as f32 + 0.5, y as f32 + 0.5);
+                let inside = center_x > left && center_x < right && center_y > top && center_y < bottom;
+                let count = coverage[(y * width + x) as usize];
+                if count != inside as u32 {
+                    errors.push(format!("({}, {}) drawn {} times", x, y, count));
+                }
+            }
+        }
+        assert!(errors.is_empty(), "{} pixels had the wrong coverage, first few: {}", errors.len(), errors[..errors.len().min(8)].join(", "));
+    }
+    
+    /// Renders a checkerboard floor stretching away from a perspective camera through the same projection as `check_remesh`
+    /// and compares every pixel's interpolated uv against the exact uv found by intersecting its view ray with the floor
+    /// makes sure no pixel landed on the wrong square and returns the largest uv error (in checker squares)
+    fn checkerboard_uv_error(width: u32, height: u32) -> f32 {
+        let fov_y = 60f32.to_radians();
+        let aspect = width as f32 / height as f32;
+        let projection = Mat4::perspective(fov_y, aspect, 0.1, 100.0);
+        
+        // the floor is at y = -1 in view space (+z forward), with one checker square per unit
+        let corners = [(-4.0, 0.5), (4.0, 0.5), (4.0, 24.0), (-4.0, 24.0)];
+        let projected = corners.map(|(x, z)| {
+            let ndc = transform_vertex(Float4::new(x, -1.0, z, 0.0), &projection);
+            ndc_to_screen(ndc, width as f32, height as f32)
+        });
+        let uvs = corners.map(|(x, z)| (x + 4.0, z));
+        
+        // the exact uv under a pixel center, undoing ndc_to_screen and the projection to get the view ray
+        let focal = 1.0 / (fov_y * 0.5).tan();
+        let exact_uv = |x: u32, y: u32| -> Option<(f32, f32)> {
+            let ndc_x = (x as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+            let ndc_y = 1.0 - (y as f32 + 0.5) / height as f32 * 2.0;
+            let ray = Vec3::new(-ndc_x * aspect / focal, -ndc_y / focal, 1.0);
+            if ray.y >= 0.0 {
+                return None;
+            }
+            let t = -1.0 / ray.y;
+            Some((ray.x * t + 4.0, ray.z * t))
+        };
+        
+        let mut max_error = 0.0f32;
+        let mut wrong_squares = vec![];
+        for triangle in [[0, 1, 2], [0, 2, 3]] {
+            let screen = triangle.map(|i| Float2::new(projected[i].x, projected[i].y));
+            let inverse_w = triangle.map(|i| projected[i].w);
+            rasterize_triangle(screen, width, height, |x, y, barycentrics| {
+                let weights = perspective_weights(barycentrics, inverse_w);
+                let u = weights[0] * uvs[triangle[0]].0 + weights[1] * uvs[triangle[1]].0 + weights[2] * uvs[triangle[2]].0;
+                let v = weights[0] * uvs[triangle[0]].1 + weights[1] * uvs[triangle[1]].1 + weights[2] * uvs[triangle[2]].1;
+                let Some((exact_u, exact_v)) = exact_uv(x, y) else {
+                    return;
+                };
+                let error = (u - exact_u).abs().max((v - exact_v).abs());
+                max_error = max_error.max(error);
+                let checker = |u: f32, v: f32| (u.floor() + v.floor()) as i32 & 1;
+                // pixels right on a line between squares can go either way from float error alone
+                let near_line = (exact_u - exact_u.round()).abs() < 0.01 || (exact_v - exact_v.round()).abs() < 0.01;
+                if !near_line && checker(u, v) != checker(exact_u, exact_v) {
+                    wrong_squares.push(format!("({}, {})", x, y));
+                }
+            });
+        }
+        assert!(wrong_squares.is_empty(), "{} pixels sampled the wrong checker square, first few: {}", wrong_squares.len(), wrong_squares[..wrong_squares.len().min(8)].join(", "));
+        max_error
+    }
+    
+    #[test]
+    fn textures_are_interpolated_perspective_correctly() {
+        let max_error = checkerboard_uv_error(640, 480);
+        assert!(max_error < 0.001, "the largest uv error was {} of a checker square", max_error);
+    }
+    
+    #[test]
+    fn tessellated_quads_cover_every_pixel_once() {
+        // no cracks between triangles and no pixels drawn twice along shared edges
+        for (width, height, columns, rows) in [(256, 192, 16, 12), (100, 80, 7, 9), (640, 480, 40, 30)] {
+            assert_quad_covered_once(width, height, columns, rows);
+        }
+    }
+}
@@ -96,7 +96,7 @@ impl Uint4 {
 }
 
 #[repr(C)]
-#[derive(Debug, Clone, Default, Copy)]
+#[derive(Debug, Clone, Default, Copy, PartialEq, Eq)]
 pub struct Uchar4 {
     pub x: u8,
     pub y: u8,
@@ -210,6 +210,22 @@ impl Shader {
         Ok(())
     }
     
+    /// Overwrites part of the specified buffer from a slice, starting `offset` elements in (leaving the rest alone)
+    pub fn update_buffer_range<T>(&mut self, index: usize, offset: usize, data: &[T]) -> Result<(), ShaderError> {
+        let end = (offset + data.len()) * std::mem::size_of::<T>();
+        if end as u64 > self.buffers[index].length() {
+            return Err(ShaderError { details: format!("Tried to write up to byte {} of buffer {}, which is only {} bytes long.", end, index, self.buffers[index].length()) });
+        }
+        let ptr = self.buffers[index].contents() as *mut T;
+        if ptr.is_null() {
+            return Err(ShaderError { details: "Failed to get buffer contents; the pointer to its contents was null.".to_string() });
+        }
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), ptr.add(offset), data.len());
+        }
+        Ok(())
+    }
+    
     /// Executes the shader with the given grid and threadgroup sizes
     pub fn execute(&self, grid_size: MTLSize, threadgroup_size: MTLSize, callback: Option<impl FnOnce() -> ()>) {
         let command_buffer = self.command_queue.new_command_buffer();
@@ -248,17 +264,23 @@ impl ShaderError {
     }
 }
 
+/// A vertex as the kernel reads it (matching `Vertex` in triangles.metal); 32 bytes
 #[repr(C)]
 #[derive(Clone, Copy, Default)]
 pub struct Vertex {
     pub(crate) position: Float4,
-    pub(crate) uv: Float4,
-    pub(crate) light: Float4,
+    pub(crate) uv: Float2,
+    pub(crate) light: Uchar4,
+    pub(crate) tint: Uchar4,  // what tinted texels are multiplied by (see `texture_atlas::TINTED_ALPHA`); filling what the float4 alignment would pad anyways
 }
 
 impl Vertex {
-    pub fn new(position: Float4, uv: Float2, light: Float4) -> Self {
-        Vertex { position, uv: Float4::new(uv.x, uv.y, 0.0, 0.0), light }
+    pub fn new(position: Float4, uv: Float2, light: Uchar4) -> Self {
+        Vertex { position, uv, light, tint: Uchar4::new(255, 255, 255, 0) }
+    }
+    
+    pub fn with_tint(self, tint: Uchar4) -> Self {
+        Vertex { tint, ..self }
     }
 }
 
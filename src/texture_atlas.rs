@@ -0,0 +1,227 @@
+use crate::shader_handling::Uchar4;
+use crate::triangle::MAX_TEXTURE_INDEX;
+
+// every texture is stored as its full mip chain, one after another, in the same layout the kernel indexes (see sample_texture)
+
+/// The width and height of a block texture at mip 0
+pub const TEXTURE_SIZE: usize = 16;
+/// 16x16 down to 1x1
+pub const MIP_LEVELS: usize = 5;
+/// Where each mip level starts within a texture's chain (MIP_OFFSETS in the kernel)
+pub const MIP_OFFSETS: [usize; MIP_LEVELS] = [0, 256, 256 + 64, 256 + 64 + 16, 256 + 64 + 16 + 4];
+/// The texels taken up by one texture including all of its mips (TEXELS_PER_TEXTURE in the kernel)
+pub const TEXELS_PER_TEXTURE: usize = MIP_OFFSETS[MIP_LEVELS - 1] + 1;
+/// Texels with an alpha from this up to 128 get multiplied by their biome's tint, so grass and foliage are drawn grayscale with it
+/// (TINTED_ALPHA in the kernel; from 128 up they're cut out)
+pub const TINTED_ALPHA: u8 = 64;
+
+/// A texture that cycles through frames (water, lava, fire...), each shown for its own number of ticks
+#[derive(Clone, Debug)]
+struct Animation {
+    index: u16,  // the texture it's drawn into
+    frames: Vec<Vec<Uchar4>>,  // each frame's full mip chain
+    durations: Vec<u64>,  // in ticks
+    shown: usize,  // which frame is in the atlas right now
+}
+
+impl Animation {
+    /// Which frame should be showing at a tick of the game clock (so it stays in step however often it's checked)
+    fn frame_at(&self, tick: u64) -> usize {
+        let mut remaining = tick % self.durations.iter().sum::<u64>();
+        for (frame, duration) in self.durations.iter().enumerate() {
+            if remaining < *duration {
+                return frame;
+            }
+            remaining -= duration;
+        }
+        0
+    }
+}
+
+/// The block textures and their mips, ready to be uploaded into the texture buffer
+#[derive(Clone, Debug, Default)]
+pub struct TextureAtlas {
+    texels: Vec<Uchar4>,
+    animations: Vec<Animation>,
+}
+
+impl TextureAtlas {
+    pub fn new() -> Self {
+        TextureAtlas { texels: vec![], animations: vec![] }
+    }
+    
+    pub fn texture_count(&self) -> usize {
+        self.texels.len() / TEXELS_PER_TEXTURE
+    }
+    
+    pub fn texels(&self) -> &[Uchar4] {
+        &self.texels
+    }
+    
+    /// One texture's full mip chain, to upload on its own when only it changed
+    pub fn texture_texels(&self, index: u16) -> &[Uchar4] {
+        let start = index as usize * TEXELS_PER_TEXTURE;
+        &self.texels[start..start + TEXELS_PER_TEXTURE]
+    }
+    
+    /// The index the next texture added gets, as long as triangles can still refer to it
+    fn next_index(&self) -> Result<u16, String> {
+        let index = self.texture_count();
+        if index > MAX_TEXTURE_INDEX as usize {
+            return Err(format!("The atlas is full, triangles can only refer to {} textures", MAX_TEXTURE_INDEX as usize + 1));
+        }
+        Ok(index as u16)
+    }
+    
+    /// Adds a row major 16x16 texture and generates its mips, returning the index triangles should use for it
+    pub fn add_texture(&mut self, texels: &[Uchar4]) -> Result<u16, String> {
+        let chain = mip_chain(texels)?;
+        let index = self.next_index()?;
+        self.texels.extend_from_slice(&chain);
+        Ok(index)
+    }
+    
+    /// Adds an animated texture from a strip of 16x16 frames stacked top to bottom, each shown for its duration in ticks;
+    /// it starts on the first frame, and `animate` swaps the rest in as the game clock goes
+    pub fn add_animated_texture(&mut self, strip: &[Uchar4], durations: &[u64]) -> Result<u16, String> {
+        let frame_texels = TEXTURE_SIZE * TEXTURE_SIZE;
+        if durations.is_empty() || durations.contains(&0) {
+            return Err("Animated textures need at least one frame, and every frame has to last at least a tick".to_string());
+        }
+        if strip.len() != frame_texels * durations.len() {
+            return Err(format!("An animated texture with {} frames needs a strip of {} texels, got {}", durations.len(), frame_texels * durations.len(), strip.len()));
+        }
+        let frames = strip.chunks(frame_texels).map(mip_chain).collect::<Result<Vec<_>, String>>()?;
+        let index = self.next_index()?;
+        self.texels.extend_from_slice(&frames[0]);
+        self.animations.push(Animation { index, frames, durations: durations.to_vec(), shown: 0 });
+        Ok(index)
+    }
+    
+    /// Moves every animated texture on to its frame for `tick`, returning the textures that changed (the only ones that need uploading again)
+    pub fn animate(&mut self, tick: u64) -> Vec<u16> {
+        let mut changed = vec![];
+        for animation in &mut self.animations {
+            let frame = animation.frame_at(tick);
+            if frame == animation.shown {
+                continue;
+            }
+            animation.shown = frame;
+            let start = animation.index as usize * TEXELS_PER_TEXTURE;
+            self.texels[start..start + TEXELS_PER_TEXTURE].copy_from_slice(&animation.frames[frame]);
+            changed.push(animation.index);
+        }
+        changed
+    }
+}
+
+/// A row major 16x16 texture followed by its mips, in the layout the kernel indexes
+fn mip_chain(texels: &[Uchar4]) -> Result<Vec<Uchar4>, String> {
+    if texels.len() != TEXTURE_SIZE * TEXTURE_SIZE {
+        return Err(format!("Block textures must be {}x{} ({} texels), got {} texels", TEXTURE_SIZE, TEXTURE_SIZE, TEXTURE_SIZE * TEXTURE_SIZE, texels.len()));
+    }
+    let mut chain = Vec::with_capacity(TEXELS_PER_TEXTURE);
+    chain.extend_from_slice(texels);
+    let mut level = texels.to_vec();
+    let mut size = TEXTURE_SIZE;
+    for offset in &MIP_OFFSETS[1..] {
+        level = downsample(&level, size);
+        size /= 2;
+        debug_assert_eq!(chain.len(), *offset);
+        chain.extend_from_slice(&level);
+    }
+    Ok(chain)
+}
+
+/// Halves a square texture by averaging each 2x2 block (the alpha too, so cut-out texels fade out rather than vanishing)
+fn downsample(texels: &[Uchar4], size: usize) -> Vec<Uchar4> {
+    let half = size / 2;
+    let mut result = Vec::with_capacity(half * half);
+    for y in 0..half {
+        for x in 0..half {
+            let block = [
+                texels[(y * 2) * size + x * 2],
+                texels[(y * 2) * size + x * 2 + 1],
+                texels[(y * 2 + 1) * size + x * 2],
+                texels[(y * 2 + 1) * size + x * 2 + 1],
+            ];
+            // rounding to nearest so repeated halving doesn't keep darkening things
+            let average = |channel: fn(&Uchar4) -> u8| -> u8 {
+                ((block.iter().map(|texel| channel(texel) as u32).sum::<u32>() + 2) / 4) as u8
+            };
+            result.push(Uchar4::new(average(|t| t.x), average(|t| t.y), average(|t| t.z), average(|t| t.w)));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn solid(value: u8) -> Vec<Uchar4> {
+        vec![Uchar4::new(value, value, value, 255); TEXTURE_SIZE * TEXTURE_SIZE]
+    }
+    
+    #[test]
+    fn mip_chains_follow_the_kernel_layout() {
+        // a checkerboard of black and white averages out to gray from the first mip down
+        let checkerboard: Vec<Uchar4> = (0..TEXTURE_SIZE * TEXTURE_SIZE)
+            .map(|i| if (i % TEXTURE_SIZE + i / TEXTURE_SIZE).is_multiple_of(2) { Uchar4::new(0, 0, 0, 255) } else { Uchar4::new(255, 255, 255, 255) })
+            .collect();
+        let chain = mip_chain(&checkerboard).unwrap();
+        assert_eq!(chain.len(), TEXELS_PER_TEXTURE);
+        assert_eq!(TEXELS_PER_TEXTURE, 341);
+        assert_eq!(&chain[..MIP_OFFSETS[1]], &checkerboard[..]);
+        for (level, offset) in MIP_OFFSETS.iter().enumerate().skip(1) {
+            let size = TEXTURE_SIZE >> level;
+            let end = MIP_OFFSETS.get(level + 1).copied().unwrap_or(TEXELS_PER_TEXTURE);
+            assert_eq!(end - offset, size * size, "mip {} is the wrong size", level);
+            assert!(chain[*offset..end].iter().all(|texel| *texel == Uchar4::new(128, 128, 128, 255)), "mip {} isn't gray", level);
+        }
+        assert!(mip_chain(&checkerboard[1..]).is_err(), "a texture that isn't 16x16 was accepted");
+    }
+    
+    #[test]
+    fn downsampling_rounds_to_nearest() {
+        let texels = [Uchar4::new(0, 1, 1, 0), Uchar4::new(0, 1, 1, 255), Uchar4::new(1, 0, 1, 255), Uchar4::new(1, 0, 0, 255)];
+        // sums of 2, 2, 3 and 765 over four texels
+        assert_eq!(downsample(&texels, 2), vec![Uchar4::new(1, 1, 1, 191)]);
+        let texels = [Uchar4::new(1, 0, 0, 0), Uchar4::new(0, 0, 0, 0), Uchar4::new(0, 0, 0, 0), Uchar4::new(0, 0, 0, 0)];
+        assert_eq!(downsample(&texels, 2), vec![Uchar4::new(0, 0, 0, 0)], "a quarter rounds down");
+    }
+    
+    #[test]
+    fn animations_wrap_around_with_uneven_frame_lengths() {
+        let animation = Animation { index: 0, frames: vec![vec![]; 3], durations: vec![2, 5, 1], shown: 0 };
+        let frames: Vec<usize> = (0..10).map(|tick| animation.frame_at(tick)).collect();
+        assert_eq!(frames, [0, 0, 1, 1, 1, 1, 1, 2, 0, 0]);
+        assert_eq!(animation.frame_at(8 * 1000 + 7), 2);
+    }
+    
+    #[test]
+    fn only_the_textures_that_changed_get_animated() {
+        let mut atlas = TextureAtlas::new();
+        let stone = atlas.add_texture(&solid(100)).unwrap();
+        let fast = atlas.add_animated_texture(&[solid(0), solid(50)].concat(), &[1, 1]).unwrap();
+        let slow = atlas.add_animated_texture(&[solid(200), solid(250)].concat(), &[4, 4]).unwrap();
+        assert_eq!((stone, fast, slow), (0, 1, 2));
+        assert_eq!(atlas.animate(0), Vec::<u16>::new(), "both start on their first frame");
+        assert_eq!(atlas.animate(1), vec![fast]);
+        assert_eq!(atlas.texture_texels(fast)[0], Uchar4::new(50, 50, 50, 255));
+        assert_eq!(atlas.animate(1), Vec::<u16>::new(), "nothing changes on the same tick");
+        assert_eq!(atlas.animate(4), vec![fast, slow]);
+        assert_eq!(atlas.texture_texels(slow)[0], Uchar4::new(250, 250, 250, 255));
+        assert_eq!(atlas.texture_texels(stone)[0], Uchar4::new(100, 100, 100, 255));
+    }
+    
+    #[test]
+    fn textures_past_the_last_index_are_rejected() {
+        let mut atlas = TextureAtlas::new();
+        atlas.texels.resize(MAX_TEXTURE_INDEX as usize * TEXELS_PER_TEXTURE, Uchar4::new(0, 0, 0, 0));
+        assert_eq!(atlas.add_texture(&solid(0)), Ok(MAX_TEXTURE_INDEX));
+        assert!(atlas.add_texture(&solid(0)).is_err(), "a texture past the last index was accepted");
+        assert!(atlas.add_animated_texture(&solid(0), &[1]).is_err(), "an animated texture past the last index was accepted");
+        assert_eq!(atlas.texture_count(), MAX_TEXTURE_INDEX as usize + 1);
+    }
+}
@@ -0,0 +1,178 @@
+use crate::shader_handling::Uint4;
+
+// the layout of the attribute word (`w` of the uint4 the kernel reads), mirrored by the triangle_* functions in triangles.metal
+// normal: 8 bits, flags: 8 bits, material: 4 bits, texture: 12 bits
+const NORMAL_SHIFT: u32 = 0;
+const NORMAL_MASK: u32 = 0xFF;
+const FLAGS_SHIFT: u32 = 8;
+const FLAGS_MASK: u32 = 0xFF;
+const MATERIAL_SHIFT: u32 = 16;
+const MATERIAL_MASK: u32 = 0xF;
+const TEXTURE_SHIFT: u32 = 20;
+const TEXTURE_MASK: u32 = 0xFFF;
+
+pub const MAX_MATERIAL: u8 = MATERIAL_MASK as u8;
+pub const MAX_TEXTURE_INDEX: u16 = TEXTURE_MASK as u16;
+
+/// Per-face flags that change how a triangle gets culled and shaded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TriangleFlags(u8);
+
+impl TriangleFlags {
+    pub const NONE: Self = TriangleFlags(0);
+    /// blended over whatever's already drawn instead of replacing it (and doesn't write depth)
+    pub const TRANSLUCENT: Self = TriangleFlags(1 << 0);
+    /// drawn at full brightness, ignoring the sun and the vertex light
+    pub const EMISSIVE: Self = TriangleFlags(1 << 1);
+    /// never backface culled, for faces that should be visible from both sides
+    pub const DOUBLE_SIDED: Self = TriangleFlags(1 << 2);
+    
+    #[cfg(test)]
+    pub fn from_bits(bits: u8) -> Self {
+        TriangleFlags(bits)
+    }
+    
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+    
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+    
+    #[cfg(test)]
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+    
+    #[cfg(test)]
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+impl std::ops::BitOr for TriangleFlags {
+    type Output = Self;
+    fn bitor(self, other: Self) -> Self {
+        TriangleFlags(self.0 | other.0)
+    }
+}
+
+/// A triangle of the mesh; gets packed into a `Uint4` (the three vertex indices plus an attribute word) for the gpu
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Triangle {
+    pub indices: [u32; 3],
+    pub normal: u8,     // index into the normals buffer
+    pub texture: u16,   // index into the texture buffer
+    pub material: u8,   // how the surface gets shaded; only 4 bits of room
+    pub flags: TriangleFlags,
+}
+
+impl Triangle {
+    pub fn new(indices: [u32; 3], normal: u8, texture: u16) -> Self {
+        Triangle { indices, normal, texture, material: 0, flags: TriangleFlags::NONE }
+    }
+    
+    #[cfg(test)]
+    pub fn with_material(mut self, material: u8) -> Self {
+        self.material = material;
+        self
+    }
+    
+    pub fn with_flags(mut self, flags: TriangleFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+    
+    /// The same triangle with its vertex indices shifted, for when its vertices get appended after others
+    pub fn offset_indices(mut self, offset: u32) -> Self {
+        for index in &mut self.indices {
+            *index += offset;
+        }
+        self
+    }
+    
+    /// Packs the triangle into the layout the kernel reads; anything too large for its field gets truncated
+    pub fn encode(&self) -> Uint4 {
+        debug_assert!(self.material <= MAX_MATERIAL && self.texture <= MAX_TEXTURE_INDEX, "Triangle attributes out of range: {:?}", self);
+        Uint4::new(
+            self.indices[0],
+            self.indices[1],
+            self.indices[2],
+            (self.normal as u32 & NORMAL_MASK) << NORMAL_SHIFT |
+                (self.flags.bits() as u32 & FLAGS_MASK) << FLAGS_SHIFT |
+                (self.material as u32 & MATERIAL_MASK) << MATERIAL_SHIFT |
+                (self.texture as u32 & TEXTURE_MASK) << TEXTURE_SHIFT,
+        )
+    }
+    
+    /// The other way around from `encode`; the kernel does its own unpacking, so this is for reading triangles back on the cpu
+    #[cfg(test)]
+    pub fn decode(packed: &Uint4) -> Self {
+        Triangle {
+            indices: [packed.x, packed.y, packed.z],
+            normal: ((packed.w >> NORMAL_SHIFT) & NORMAL_MASK) as u8,
+            texture: ((packed.w >> TEXTURE_SHIFT) & TEXTURE_MASK) as u16,
+            material: ((packed.w >> MATERIAL_SHIFT) & MATERIAL_MASK) as u8,
+            flags: TriangleFlags::from_bits(((packed.w >> FLAGS_SHIFT) & FLAGS_MASK) as u8),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    static ALL_FLAGS: [TriangleFlags; 3] = [TriangleFlags::TRANSLUCENT, TriangleFlags::EMISSIVE, TriangleFlags::DOUBLE_SIDED];
+    
+    fn round_trip(triangle: Triangle) {
+        assert_eq!(Triangle::decode(&triangle.encode()), triangle);
+    }
+    
+    #[test]
+    fn fields_round_trip_at_their_limits() {
+        let indices = [0, 1, u32::MAX];
+        for normal in 0..=u8::MAX {
+            round_trip(Triangle::new(indices, normal, 0));
+        }
+        for texture in [0, 1, 0x800, MAX_TEXTURE_INDEX] {
+            for material in 0..=MAX_MATERIAL {
+                round_trip(Triangle::new(indices, u8::MAX, texture).with_material(material));
+            }
+        }
+        // every combination of the flags (including the bits that aren't named yet)
+        for bits in 0..=u8::MAX {
+            round_trip(Triangle::new(indices, 3, MAX_TEXTURE_INDEX).with_material(MAX_MATERIAL).with_flags(TriangleFlags::from_bits(bits)));
+        }
+    }
+    
+    #[test]
+    fn a_full_field_stays_out_of_its_neighbours() {
+        let empty = Triangle::new([0; 3], 0, 0);
+        let packed = |triangle: Triangle| triangle.encode().w;
+        assert_eq!(packed(Triangle { normal: u8::MAX, ..empty }), NORMAL_MASK << NORMAL_SHIFT);
+        assert_eq!(packed(empty.with_flags(TriangleFlags::from_bits(u8::MAX))), FLAGS_MASK << FLAGS_SHIFT);
+        assert_eq!(packed(empty.with_material(MAX_MATERIAL)), MATERIAL_MASK << MATERIAL_SHIFT);
+        assert_eq!(packed(Triangle { texture: MAX_TEXTURE_INDEX, ..empty }), TEXTURE_MASK << TEXTURE_SHIFT);
+        // and the fields between them cover the whole word without overlapping
+        let masks = [NORMAL_MASK << NORMAL_SHIFT, FLAGS_MASK << FLAGS_SHIFT, MATERIAL_MASK << MATERIAL_SHIFT, TEXTURE_MASK << TEXTURE_SHIFT];
+        assert_eq!(masks.iter().fold(0, |all, mask| all | mask), u32::MAX);
+        assert_eq!(masks.iter().map(|mask| mask.count_ones()).sum::<u32>(), 32);
+        // decoding a word with only one field set leaves the others empty
+        let decoded = Triangle::decode(&Uint4::new(0, 0, 0, TEXTURE_MASK << TEXTURE_SHIFT));
+        assert_eq!((decoded.normal, decoded.flags, decoded.material, decoded.texture), (0, TriangleFlags::NONE, 0, MAX_TEXTURE_INDEX));
+    }
+    
+    #[test]
+    fn flags_combine_and_come_apart() {
+        let mut flags = TriangleFlags::NONE;
+        for flag in ALL_FLAGS {
+            assert!(!flags.contains(flag));
+            flags.insert(flag);
+            assert!(flags.contains(flag));
+        }
+        assert_eq!(flags, TriangleFlags::TRANSLUCENT | TriangleFlags::EMISSIVE | TriangleFlags::DOUBLE_SIDED);
+        flags.remove(TriangleFlags::EMISSIVE);
+        assert!(flags.contains(TriangleFlags::TRANSLUCENT | TriangleFlags::DOUBLE_SIDED) && !flags.contains(TriangleFlags::EMISSIVE));
+    }
+}
@@ -0,0 +1,221 @@
+use crate::block::{self, BlockId};
+use crate::chunk::Chunk;
+use crate::math::Vec3;
+
+// block queries across chunk boundaries, for anything that needs to know what's where (collision, block updates...)
+// block (x, y, z) is the unit cube centered on that point, the same as the full detail mesh in `remesh_tile`
+
+pub const CHUNK_SIZE: i32 = 16;
+
+/// The integer coordinates of a block in the world
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BlockPosition {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl BlockPosition {
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        BlockPosition { x, y, z }
+    }
+    
+    /// The block a point is inside of
+    pub fn containing(point: Vec3) -> Self {
+        BlockPosition::new((point.x + 0.5).floor() as i32, (point.y + 0.5).floor() as i32, (point.z + 0.5).floor() as i32)
+    }
+    
+    pub fn above(self) -> Self {
+        BlockPosition::new(self.x, self.y + 1, self.z)
+    }
+    
+    pub fn below(self) -> Self {
+        BlockPosition::new(self.x, self.y - 1, self.z)
+    }
+    
+    pub fn horizontal_neighbours(self) -> [Self; 4] {
+        [
+            BlockPosition::new(self.x + 1, self.y, self.z),
+            BlockPosition::new(self.x - 1, self.y, self.z),
+            BlockPosition::new(self.x, self.y, self.z + 1),
+            BlockPosition::new(self.x, self.y, self.z - 1),
+        ]
+    }
+    
+    /// The block next to this one through `face` (+y, -y, +x, -x, +z, -z)
+    pub fn beside(self, face: usize) -> Self {
+        match face {
+            0 => BlockPosition::new(self.x, self.y + 1, self.z),
+            1 => BlockPosition::new(self.x, self.y - 1, self.z),
+            2 => BlockPosition::new(self.x + 1, self.y, self.z),
+            3 => BlockPosition::new(self.x - 1, self.y, self.z),
+            4 => BlockPosition::new(self.x, self.y, self.z + 1),
+            _ => BlockPosition::new(self.x, self.y, self.z - 1),
+        }
+    }
+    
+    pub fn center(self) -> Vec3 {
+        Vec3::new(self.x as f32, self.y as f32, self.z as f32)
+    }
+    
+    /// Which chunk the block is in and where it is inside of it
+    pub fn split(self) -> ((i32, i32, i32), (usize, usize, usize)) {
+        (
+            (self.x.div_euclid(CHUNK_SIZE), self.y.div_euclid(CHUNK_SIZE), self.z.div_euclid(CHUNK_SIZE)),
+            (self.x.rem_euclid(CHUNK_SIZE) as usize, self.y.rem_euclid(CHUNK_SIZE) as usize, self.z.rem_euclid(CHUNK_SIZE) as usize),
+        )
+    }
+}
+
+/// Where a ray first hit a block
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub position: BlockPosition,
+    pub face: usize,  // the side of the block the ray went in through
+    pub point: Vec3,  // where on that side
+}
+
+/// Every loaded chunk, along with a lookup from chunk coordinates to where it is in `chunks`
+pub struct World {
+    chunks: Vec<Chunk>,
+    chunk_lookup: std::collections::HashMap<(i32, i32, i32), usize>,
+}
+
+impl World {
+    pub fn new(chunks: Vec<Chunk>) -> Self {
+        let mut world = World { chunks: Vec::with_capacity(chunks.len()), chunk_lookup: std::collections::HashMap::new() };
+        for chunk in chunks {
+            world.add_chunk(chunk);
+        }
+        world
+    }
+    
+    /// Loads another chunk into the world (for world generation, which adds them one at a time)
+    pub fn add_chunk(&mut self, chunk: Chunk) {
+        let coordinates = (
+            (chunk.position.x / CHUNK_SIZE as f32).floor() as i32,
+            (chunk.position.y / CHUNK_SIZE as f32).floor() as i32,
+            (chunk.position.z / CHUNK_SIZE as f32).floor() as i32,
+        );
+        self.chunk_lookup.insert(coordinates, self.chunks.len());
+        self.chunks.push(chunk);
+    }
+    
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+    
+    pub fn chunks_mut(&mut self) -> &mut [Chunk] {
+        &mut self.chunks
+    }
+    
+    /// The block at a position; anywhere that isn't loaded is air
+    pub fn block(&self, position: BlockPosition) -> BlockId {
+        let (chunk, (x, y, z)) = position.split();
+        match self.chunk_lookup.get(&chunk) {
+            Some(index) => self.chunks[*index].tile_data[x][y][z],
+            None => block::AIR,
+        }
+    }
+    
+    pub fn is_solid(&self, position: BlockPosition) -> bool {
+        block::is_solid(self.block(position))
+    }
+    
+    /// Whether the chunk a position is in is loaded
+    pub fn is_loaded(&self, position: BlockPosition) -> bool {
+        self.chunk_lookup.contains_key(&position.split().0)
+    }
+    
+    /// Changes a block and marks its chunk for remeshing; returns false if the chunk isn't loaded
+    pub fn set_block(&mut self, position: BlockPosition, id: BlockId) -> bool {
+        match self.set_block_unmarked(position, id) {
+            Some(index) => {
+                self.mark_mutated(index);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Changes a block without marking its chunk for remeshing, returning the chunk's index to mark later with `mark_mutated`
+    /// (for batching up lots of changes), or `None` if the chunk isn't loaded
+    pub fn set_block_unmarked(&mut self, position: BlockPosition, id: BlockId) -> Option<usize> {
+        let (chunk, (x, y, z)) = position.split();
+        let index = *self.chunk_lookup.get(&chunk)?;
+        self.chunks[index].tile_data[x][y][z] = id;
+        Some(index)
+    }
+    
+    pub fn mark_mutated(&mut self, chunk_index: usize) {
+        self.chunks[chunk_index].mutated = true;
+    }
+    
+    /// The first block a ray hits within `max_distance` (which `direction` is measured in, so it should be normalized),
+    /// stepping through the blocks it passes one boundary at a time; air and fluids are passed through,
+    /// as is whatever block the ray starts in
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<RayHit> {
+        // shifted so blocks span whole numbers, rather than being centered on them
+        let start = [origin.x + 0.5, origin.y + 0.5, origin.z + 0.5];
+        let direction_axes = [direction.x, direction.y, direction.z];
+        let mut block = start.map(|coordinate| coordinate.floor() as i32);
+        // how far along the ray the next boundary on each axis is, and how far apart the boundaries are
+        let mut next_boundary = [f32::INFINITY; 3];
+        let mut boundary_spacing = [f32::INFINITY; 3];
+        for axis in 0..3 {
+            let towards = direction_axes[axis];
+            if towards > 0.0 {
+                next_boundary[axis] = (block[axis] as f32 + 1.0 - start[axis]) / towards;
+            } else if towards < 0.0 {
+                next_boundary[axis] = (start[axis] - block[axis] as f32) / -towards;
+            }
+            if towards != 0.0 {
+                boundary_spacing[axis] = 1.0 / towards.abs();
+            }
+        }
+        loop {
+            let axis = (0..3).min_by(|a, b| next_boundary[*a].total_cmp(&next_boundary[*b])).unwrap_or(0);
+            let distance = next_boundary[axis];
+            if distance > max_distance {
+                return None;
+            }
+            let forwards = direction_axes[axis] > 0.0;
+            block[axis] += if forwards { 1 } else { -1 };
+            next_boundary[axis] += boundary_spacing[axis];
+            let position = BlockPosition::new(block[0], block[1], block[2]);
+            let cell = self.block(position);
+            if cell != block::AIR && block::fluid(cell).is_none() {
+                // going up into a block means coming in through its -y face, and so on
+                let face = [2, 0, 4][axis] + forwards as usize;
+                return Some(RayHit { position, face, point: origin + direction * distance });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl World {
+    /// A floor of `floor_block` at y = 0 over four chunks along x (from -16 to 47, so there are chunk boundaries to cross),
+    /// with nothing marked as mutated yet; its top is at y = 0.5, since blocks are centered on their coordinates
+    pub fn test_floor(floor_block: BlockId) -> World {
+        let mut chunks = vec![];
+        for chunk_x in -1..3 {
+            chunks.push(Chunk::new(crate::shader_handling::Float4::new(chunk_x as f32 * 16.0, 0.0, 0.0, 0.0), 0));
+        }
+        let mut world = World::new(chunks);
+        for x in -16..48 {
+            for z in 0..CHUNK_SIZE {
+                world.set_block(BlockPosition::new(x, 0, z), floor_block);
+            }
+        }
+        for chunk in world.chunks_mut() {
+            chunk.mutated = false;
+        }
+        world
+    }
+    
+    /// How many cells of every loaded chunk hold block `id` (in any state)
+    pub fn count(&self, id: BlockId) -> usize {
+        self.chunks.iter().map(|chunk| chunk.tile_data.iter().flatten().flatten().filter(|cell| block::id(**cell) == id).count()).sum()
+    }
+}